@@ -0,0 +1,89 @@
+use chibiwasm::{Runtime, Value};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn runtime(code: &str) -> Runtime {
+    let wasm = wat::parse_str(code).expect("failed to parse wat");
+    Runtime::from_bytes(wasm, None).expect("failed to instantiate module")
+}
+
+fn fib(c: &mut Criterion) {
+    let code = r#"
+(module
+  (func $fib (export "fib") (param i32) (result i32)
+    (if (result i32) (i32.lt_s (local.get 0) (i32.const 2))
+      (then (local.get 0))
+      (else
+        (i32.add
+          (call $fib (i32.sub (local.get 0) (i32.const 1)))
+          (call $fib (i32.sub (local.get 0) (i32.const 2)))
+        )
+      )
+    )
+  )
+)
+"#;
+    let mut runtime = runtime(code);
+    c.bench_function("fib 20", |b| {
+        b.iter(|| runtime.call("fib".into(), vec![black_box(Value::I32(20))]))
+    });
+}
+
+fn loop_counter(c: &mut Criterion) {
+    let code = r#"
+(module
+  (func (export "count") (param i32) (result i32)
+    (local i32)
+    (block
+      (loop
+        (br_if 1 (i32.ge_u (local.get 1) (local.get 0)))
+        (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+        (br 0)
+      )
+    )
+    (local.get 1)
+  )
+)
+"#;
+    let mut runtime = runtime(code);
+    c.bench_function("loop 100000", |b| {
+        b.iter(|| runtime.call("count".into(), vec![black_box(Value::I32(100_000))]))
+    });
+}
+
+fn memory_copy(c: &mut Criterion) {
+    let code = r#"
+(module
+  (memory 1)
+  ;; copy 8 bytes at a time from the first half of memory to the second half
+  (func (export "copy") (param i32)
+    (local i32)
+    (block
+      (loop
+        (br_if 1 (i32.ge_u (local.get 1) (local.get 0)))
+        (i64.store
+          (i32.add (local.get 1) (i32.const 32768))
+          (i64.load (local.get 1))
+        )
+        (local.set 1 (i32.add (local.get 1) (i32.const 8)))
+        (br 0)
+      )
+    )
+  )
+  (func (export "bulk-copy") (param i32)
+    (memory.copy (i32.const 32768) (i32.const 0) (local.get 0))
+  )
+)
+"#;
+    let mut runtime = runtime(code);
+    let mut group = c.benchmark_group("memory copy 32KiB");
+    group.bench_function("load/store", |b| {
+        b.iter(|| runtime.call("copy".into(), vec![black_box(Value::I32(32768))]))
+    });
+    group.bench_function("memory.copy", |b| {
+        b.iter(|| runtime.call("bulk-copy".into(), vec![black_box(Value::I32(32768))]))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fib, loop_counter, memory_copy);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use chibiwasm::{module::ExternalFuncInst, Importer, Runtime, Store, Value};
+use std::{cell::RefCell, rc::Rc};
+
+const CODE: &str = r#"
+(module
+  (import "env" "print_i32" (func $print_i32 (param i32)))
+  (func (export "count") (param i32)
+    (local i32)
+    (block $exit
+      (loop $loop
+        (br_if $exit (i32.ge_s (local.get 1) (local.get 0)))
+        (call $print_i32 (local.get 1))
+        (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+        (br $loop)
+      )
+    )
+  )
+)
+"#;
+
+// host functions of the "env" module, printed values are also recorded
+#[derive(Default)]
+struct Env {
+    printed: Rc<RefCell<Vec<i32>>>,
+}
+
+impl Importer for Env {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn invoke(
+        &self,
+        _store: Rc<RefCell<Store>>,
+        func: ExternalFuncInst,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        match func.field.as_str() {
+            "print_i32" => {
+                let value: i32 = args[0].clone().try_into()?;
+                println!("{}", value);
+                self.printed.borrow_mut().push(value);
+                Ok(None)
+            }
+            field => anyhow::bail!("unknown host function: {}", field),
+        }
+    }
+}
+
+fn run(n: i32) -> Result<Vec<i32>> {
+    let env = Env::default();
+    let printed = Rc::clone(&env.printed);
+
+    let wasm = wat::parse_str(CODE)?;
+    let mut runtime = Runtime::builder().import(env).build_from_bytes(wasm)?;
+    runtime.call("count".into(), vec![Value::I32(n)])?;
+
+    let printed = printed.borrow().clone();
+    Ok(printed)
+}
+
+fn main() -> Result<()> {
+    run(5)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::run;
+    use anyhow::Result;
+
+    #[test]
+    fn print_i32() -> Result<()> {
+        assert_eq!(run(5)?, vec![0, 1, 2, 3, 4]);
+        assert_eq!(run(0)?, vec![]);
+        Ok(())
+    }
+}
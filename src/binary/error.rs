@@ -1,12 +1,9 @@
 #![allow(clippy::enum_variant_names)]
+use super::types::ExprValue;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("invalid count of memory, must be 1")]
-    InvalidMemoryCount,
-    #[error("invalid count of table, must be 1")]
-    InvalidTableCount,
     #[error("invalid elemtype of table, must be funcref, got {0}")]
     InvalidElmType(u8),
     #[error("invalid init expr instruction in expressions, got {0}")]
@@ -17,4 +14,16 @@ pub enum Error {
     InvalidImportKind(u8),
     #[error("invalid opecode: {0:x}")]
     InvalidOpcode(u8),
+    #[error("invalid flag of data segment, got {0}")]
+    InvalidDataSegmentFlag(u32),
+    #[error("invalid value type: {0:x}")]
+    InvalidValueType(u8),
+    #[error("invalid mutability of global, got {0}")]
+    InvalidMutability(u8),
+    #[error("invalid section id: {0}")]
+    InvalidSectionId(u8),
+    #[error("unexpected value of expressions, got {0:?}")]
+    UnexpectedExprValue(ExprValue),
+    #[error("unsupported feature: {0}")]
+    Unsupported(&'static str),
 }
@@ -1,10 +1,11 @@
-use super::types::Block;
+use super::types::{Block, ValueType};
 use num_derive::FromPrimitive;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct MemoryArg {
     pub align: u32,
     pub offset: u32,
+    pub memory: u32, // index of memory, always 0 without multi-memory
 }
 
 // https://webassembly.github.io/spec/core/binary/instructions.html#expressions
@@ -28,6 +29,8 @@ pub enum Opcode {
     GlobalSet = 0x24,
     Call = 0x10,
     CallIndirect = 0x11,
+    ReturnCall = 0x12,
+    ReturnCallIndirect = 0x13,
     I32Const = 0x41,
     I32Eqz = 0x45,
     I32Eq = 0x46,
@@ -163,7 +166,11 @@ pub enum Opcode {
     MemorySize = 0x3F,
     MemoryGrow = 0x40,
     MmeoryCopyOrFill = 0xFC,
+    RefNull = 0xD0,
+    RefIsNull = 0xD1,
+    RefFunc = 0xD2,
     Select = 0x1B,
+    TypedSelect = 0x1C,
     Drop = 0x1A,
     I32TruncF32S = 0xA8,
     I32TruncF32U = 0xA9,
@@ -210,6 +217,11 @@ pub enum Instruction {
     GlobalGet(u32),
     Call(u32),
     CallIndirect((u32, u32)),
+    ReturnCall(u32),
+    ReturnCallIndirect((u32, u32)),
+    RefNull, // null of funcref, externref is not supported
+    RefIsNull,
+    RefFunc(u32),
     I32Const(i32),
     I32Eqz,
     I32Eq,
@@ -343,10 +355,14 @@ pub enum Instruction {
     I64Store16(MemoryArg),
     I64Store32(MemoryArg),
     Select,
+    TypedSelect(Vec<ValueType>), // valid only with a single type
     MemoryGrow(u32),
-    MemorySize,
-    MemoryCopy(u32, u32),
+    MemorySize(u32),
+    MemoryCopy(u32, u32), // destination memory index, source memory index
     MemoryFill(u32),
+    MemoryInit(u32, u32), // data index, memory index
+    DataDrop(u32),
+    TableGrow(u32),
     Drop,
     I32TruncF32S,
     I32TruncF32U,
@@ -0,0 +1,176 @@
+use super::{
+    module::Module,
+    types::{ExportDesc, FuncType, ImportKind, Limits, Mutability},
+    wat::{instruction_to_wat, value_type},
+};
+use serde_json::{json, Value};
+
+fn func_type(func_type: &FuncType) -> Value {
+    let params: Vec<_> = func_type.params.iter().map(value_type).collect();
+    let results: Vec<_> = func_type.results.iter().map(value_type).collect();
+    json!({ "params": params, "results": results })
+}
+
+fn limits(limits: &Limits) -> Value {
+    json!({ "min": limits.min, "max": limits.max })
+}
+
+fn mutable(mutability: &Mutability) -> bool {
+    matches!(mutability, Mutability::Var)
+}
+
+impl Module {
+    // dump the decoded module as json, instructions are written in the text format
+    pub fn to_json(&self) -> String {
+        let types: Vec<_> = self.type_section.iter().flatten().map(func_type).collect();
+
+        let imports: Vec<_> = self
+            .import_section
+            .iter()
+            .flatten()
+            .map(|import| {
+                let desc = match &import.kind {
+                    ImportKind::Func(idx) => json!({ "kind": "func", "type": idx }),
+                    ImportKind::Table(table) => {
+                        json!({ "kind": "table", "limits": limits(&table.limits) })
+                    }
+                    ImportKind::Memory(memory) => {
+                        json!({ "kind": "memory", "limits": limits(&memory.limits) })
+                    }
+                    ImportKind::Global(global) => json!({
+                        "kind": "global",
+                        "type": value_type(&global.value_type),
+                        "mutable": mutable(&global.mutability),
+                    }),
+                };
+                json!({ "module": import.module, "field": import.field, "desc": desc })
+            })
+            .collect();
+
+        // NOTE: imported functions come first in the function index space
+        let imported_funcs = self
+            .import_section
+            .iter()
+            .flatten()
+            .filter(|import| matches!(import.kind, ImportKind::Func(_)))
+            .count();
+        let functions: Vec<_> = self
+            .function_section
+            .iter()
+            .flatten()
+            .zip(self.code_section.iter().flatten())
+            .enumerate()
+            .map(|(i, (type_idx, body))| {
+                let locals: Vec<_> = body
+                    .locals
+                    .iter()
+                    .flat_map(|local| {
+                        vec![value_type(&local.value_type); local.type_count as usize]
+                    })
+                    .collect();
+                // NOTE: the malformed body of the lazily decoded module is dumped as empty
+                let code = body.instructions().unwrap_or_default();
+                let body: Vec<_> = code.iter().map(instruction_to_wat).collect();
+                json!({
+                    "index": imported_funcs + i,
+                    "type": type_idx,
+                    "locals": locals,
+                    "body": body,
+                })
+            })
+            .collect();
+
+        let tables: Vec<_> = self
+            .table_section
+            .iter()
+            .flatten()
+            .map(|table| limits(&table.limits))
+            .collect();
+        let memories: Vec<_> = self
+            .memory_section
+            .iter()
+            .flatten()
+            .map(|memory| limits(&memory.limits))
+            .collect();
+        let globals: Vec<_> = self
+            .global_section
+            .iter()
+            .flatten()
+            .map(|global| {
+                let global_type = &global.global_type;
+                json!({
+                    "type": value_type(&global_type.value_type),
+                    "mutable": mutable(&global_type.mutability),
+                })
+            })
+            .collect();
+
+        let exports: Vec<_> = self
+            .export_section
+            .iter()
+            .flatten()
+            .map(|export| {
+                let (kind, idx) = match export.desc {
+                    ExportDesc::Func(idx) => ("func", idx),
+                    ExportDesc::Table(idx) => ("table", idx),
+                    ExportDesc::Memory(idx) => ("memory", idx),
+                    ExportDesc::Global(idx) => ("global", idx),
+                };
+                json!({ "name": export.name, "kind": kind, "index": idx })
+            })
+            .collect();
+
+        json!({
+            "magic": self.magic,
+            "version": self.version,
+            "types": types,
+            "imports": imports,
+            "functions": functions,
+            "tables": tables,
+            "memories": memories,
+            "globals": globals,
+            "exports": exports,
+            "start": self.start_section,
+            "elements": self.element_section.as_ref().map_or(0, Vec::len),
+            "data": self.data.as_ref().map_or(0, Vec::len),
+        })
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::binary::module::Decoder;
+    use anyhow::Result;
+    use serde_json::Value;
+
+    #[test]
+    fn test_to_json() -> Result<()> {
+        let source = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (memory 1)
+  (func $add (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+  (func (export "double") (param i32) (result i32)
+    (call $add (local.get 0) (local.get 0))
+  )
+)
+"#;
+        let wasm = wat::parse_str(source)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let json: Value = serde_json::from_str(&module.to_json())?;
+
+        let functions = json["functions"].as_array().unwrap();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0]["index"], 1);
+        assert_eq!(functions[0]["body"][2], "i32.add");
+        assert_eq!(json["exports"][0]["name"], "add");
+        assert_eq!(json["exports"][1]["name"], "double");
+        assert_eq!(json["imports"][0]["field"], "log");
+        assert_eq!(json["memories"][0]["min"], 1);
+
+        Ok(())
+    }
+}
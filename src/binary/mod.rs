@@ -1,5 +1,16 @@
 pub(crate) mod error;
 pub(crate) mod instruction;
+#[cfg(feature = "serde")]
+pub(crate) mod json;
 pub(crate) mod module;
 pub(crate) mod section;
 pub(crate) mod types;
+pub(crate) mod wat;
+
+pub use instruction::{Instruction, Opcode};
+pub use module::{DecodeOptions, Decoder, Module};
+pub use types::{
+    ElemType, ExportType, FuncType, FunctionLocal, GlobalType, ImportDesc, ImportType, Limits,
+    Memory, Mutability, ResourceSummary, Table, ValueType,
+};
+pub use wat::{extern_type_to_wat, func_to_wat};
@@ -1,6 +1,6 @@
 use super::{section::*, types::*};
-use anyhow::{bail, Result};
-use num_traits::FromPrimitive;
+use anyhow::{bail, Context as _, Result};
+use log::warn;
 use std::io;
 use std::{
     io::{BufRead, BufReader, Read},
@@ -22,6 +22,7 @@ pub struct Module {
     pub start_section: Option<u32>,
     pub element_section: Option<Vec<Element>>,
     pub data: Option<Vec<Data>>,
+    pub data_count_section: Option<u32>,
     pub code_section: Option<Vec<FunctionBody>>,
 }
 
@@ -40,18 +41,211 @@ impl Module {
             Section::Element(section) => self.element_section = Some(section),
             Section::Data(section) => self.data = Some(section),
             Section::Start(section) => self.start_section = Some(section),
+            Section::DataCount(section) => self.data_count_section = Some(section),
         };
     }
+
+    // every declared function has its body, and every counted data segment exists
+    fn is_complete(&self) -> bool {
+        let funcs = self.function_section.as_ref().map_or(0, |f| f.len());
+        let bodies = self.code_section.as_ref().map_or(0, |c| c.len());
+        let datas = self.data.as_ref().map_or(0, |d| d.len());
+        let counted = self.data_count_section.is_none_or(|n| n as usize == datas);
+        funcs == bodies && counted
+    }
+
+    // type of each function in the function index space, None if the type index is unknown,
+    // imported functions come first, followed by the defined functions
+    fn function_types(&self) -> impl Iterator<Item = Option<&FuncType>> {
+        let types = self.type_section.as_deref().unwrap_or_default();
+        let imported = self
+            .import_section
+            .iter()
+            .flatten()
+            .filter_map(|import| match import.kind {
+                ImportKind::Func(idx) => Some(idx),
+                _ => None,
+            });
+        let defined = self.function_section.iter().flatten().copied();
+        imported.chain(defined).map(|idx| types.get(idx as usize))
+    }
+
+    // resolve the signature of each function in the function index space
+    // NOTE: the signature of an unknown type is empty, the validator reports it
+    pub fn function_signatures(&self) -> Vec<FuncType> {
+        self.function_types()
+            .map(|ty| ty.cloned().unwrap_or_default())
+            .collect()
+    }
+
+    // local variable declarations of each defined function, params are not included
+    pub fn function_locals(&self) -> Vec<Vec<FunctionLocal>> {
+        self.code_section
+            .iter()
+            .flatten()
+            .map(|body| body.locals.clone())
+            .collect()
+    }
+
+    // every import the module requires, in the order of the import section
+    pub fn imports(&self) -> Result<Vec<ImportDesc>> {
+        let types = self.type_section.as_deref().unwrap_or_default();
+        self.import_section
+            .iter()
+            .flatten()
+            .map(|import| {
+                let ty = match &import.kind {
+                    ImportKind::Func(idx) => types
+                        .get(*idx as usize)
+                        .cloned()
+                        .map(ImportType::Func)
+                        .with_context(|| format!("not found func type by index: {idx}"))?,
+                    ImportKind::Table(table) => ImportType::Table(table.clone()),
+                    ImportKind::Memory(memory) => ImportType::Memory(memory.clone()),
+                    ImportKind::Global(global) => ImportType::Global(global.clone()),
+                };
+                Ok(ImportDesc {
+                    module: import.module.clone(),
+                    field: import.field.clone(),
+                    ty,
+                })
+            })
+            .collect()
+    }
+
+    // every export of the module, in the order of the export section
+    pub fn exports(&self) -> Result<Vec<ExportType>> {
+        let signatures: Vec<_> = self.function_types().collect();
+        let globals: Vec<_> = self
+            .import_section
+            .iter()
+            .flatten()
+            .filter_map(|import| match &import.kind {
+                ImportKind::Global(global) => Some(global),
+                _ => None,
+            })
+            .chain(self.global_section.iter().flatten().map(|g| &g.global_type))
+            .cloned()
+            .collect();
+
+        self.export_section
+            .iter()
+            .flatten()
+            .map(|export| {
+                let ty = match export.desc {
+                    ExportDesc::Func(idx) => signatures
+                        .get(idx as usize)
+                        .copied()
+                        .flatten()
+                        .cloned()
+                        .map(ImportType::Func),
+                    ExportDesc::Table(idx) => self
+                        .tables()
+                        .nth(idx as usize)
+                        .cloned()
+                        .map(ImportType::Table),
+                    ExportDesc::Memory(idx) => self
+                        .memories()
+                        .nth(idx as usize)
+                        .cloned()
+                        .map(ImportType::Memory),
+                    ExportDesc::Global(idx) => {
+                        globals.get(idx as usize).cloned().map(ImportType::Global)
+                    }
+                }
+                .with_context(|| format!("invalid export desc: {:?}", export.desc))?;
+                Ok(ExportType {
+                    name: export.name.clone(),
+                    ty,
+                })
+            })
+            .collect()
+    }
+
+    // every memory, imported memories come first as with functions
+    fn memories(&self) -> impl Iterator<Item = &Memory> {
+        let imported = self
+            .import_section
+            .iter()
+            .flatten()
+            .filter_map(|import| match &import.kind {
+                ImportKind::Memory(memory) => Some(memory),
+                _ => None,
+            });
+        imported.chain(self.memory_section.iter().flatten())
+    }
+
+    // every table, imported tables come first as with functions
+    fn tables(&self) -> impl Iterator<Item = &Table> {
+        let imported = self
+            .import_section
+            .iter()
+            .flatten()
+            .filter_map(|import| match &import.kind {
+                ImportKind::Table(table) => Some(table),
+                _ => None,
+            });
+        imported.chain(self.table_section.iter().flatten())
+    }
+
+    // limits of the memory 0
+    pub fn memory_limits(&self) -> Option<(u32, Option<u32>)> {
+        self.memories()
+            .next()
+            .map(|memory| (memory.limits.min, memory.limits.max))
+    }
+
+    // limits of the table 0
+    pub fn table_limits(&self) -> Option<(u32, Option<u32>)> {
+        self.tables()
+            .next()
+            .map(|table| (table.limits.min, table.limits.max))
+    }
+
+    // resources required to instantiate the module, nothing is executed
+    pub fn resource_summary(&self) -> ResourceSummary {
+        let memories: Vec<_> = self.memories().map(|m| m.limits.clone()).collect();
+        let tables: Vec<_> = self.tables().map(|t| t.limits.clone()).collect();
+        let min_memory_pages = memories.iter().map(|limits| limits.min as u64).sum();
+        let max_memory_pages = memories
+            .iter()
+            .map(|limits| limits.max.map(u64::from))
+            .sum();
+        ResourceSummary {
+            memories,
+            tables,
+            min_memory_pages,
+            max_memory_pages,
+            has_start: self.start_section.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DecodeOptions {
+    // ignore non-fatal quirks like trailing bytes after the last section
+    pub lenient: bool,
+    // decode the instructions of each function on the first call.
+    // NOTE: the malformed bodies are not reported until they are called,
+    // and validation decodes all of them again, so skip it to get the benefit
+    pub lazy: bool,
 }
 
+// NOTE: sections are read one by one from the reader,
+// so only the largest section is buffered rather than the whole binary
 pub struct Decoder<R> {
     reader: BufReader<R>,
+    options: DecodeOptions,
 }
 
 impl<R: io::Read> Decoder<R> {
     pub fn new(reader: R) -> Self {
+        Self::with_options(reader, DecodeOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: DecodeOptions) -> Self {
         let reader = BufReader::new(reader);
-        Self { reader }
+        Self { reader, options }
     }
 
     fn is_end(&mut self) -> Result<bool> {
@@ -86,7 +280,8 @@ impl<R: io::Read> Decoder<R> {
     }
 
     pub fn decode_section_header(&mut self) -> Result<(SectionID, usize)> {
-        let id: SectionID = FromPrimitive::from_u8(self.byte()?).unwrap();
+        let id = self.byte()?;
+        let id = SectionID::try_from(id)?;
         let size = self.u32()? as usize;
         Ok((id, size))
     }
@@ -99,11 +294,27 @@ impl<R: io::Read> Decoder<R> {
 
         let version = self.decode_to_u32()?;
         if version != 1 {
-            bail!("invalid binary version")
+            bail!("unsupported binary version: {version}, expected 1")
         }
         Ok((magic, version))
     }
 
+    fn read_section(&mut self) -> Result<(SectionID, Vec<u8>)> {
+        let (id, size) = self.decode_section_header()?;
+
+        // NOTE: don't allocate the buffer by the size, it may be broken
+        let mut bytes = vec![];
+        (&mut self.reader).take(size as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != size {
+            bail!(
+                "unexpected end of section, expected {} bytes, but got {} bytes",
+                size,
+                bytes.len()
+            );
+        }
+        Ok((id, bytes))
+    }
+
     pub fn decode(&mut self) -> Result<Module> {
         let (magic, version) = self.decode_header()?;
         let mut module = Module {
@@ -112,9 +323,22 @@ impl<R: io::Read> Decoder<R> {
             ..Module::default()
         };
         while self.is_end()? {
-            let (id, size) = self.decode_section_header()?;
-            let bytes = self.bytes(size)?;
-            let section = decode(id, &bytes)?;
+            // NOTE: bytes which cannot be read as a section are treated as trailing bytes,
+            // only when they follow a complete module, otherwise the section is broken
+            let (id, bytes) = match self.read_section() {
+                Ok(section) => section,
+                Err(e) if !module.is_complete() => return Err(e),
+                Err(e) if self.options.lenient => {
+                    warn!("ignore trailing bytes after the last section: {e}");
+                    break;
+                }
+                Err(e) => return Err(e.context("unexpected trailing bytes after the last section")),
+            };
+            let section = if id == SectionID::Code && self.options.lazy {
+                decode_lazy_code_section(&bytes)?
+            } else {
+                decode(id, &bytes)?
+            };
             module.add_section(section);
         }
         Ok(module)
@@ -123,7 +347,13 @@ impl<R: io::Read> Decoder<R> {
 
 #[cfg(test)]
 mod test {
-    use super::Decoder;
+    use super::{DecodeOptions, Decoder};
+    use crate::binary::error::Error;
+    use crate::binary::instruction::Instruction;
+    use crate::binary::types::{
+        ExportDesc, FuncType, FunctionLocal, GlobalType, ImportDesc, ImportType, Limits, Memory,
+        Mutability, ResourceSummary, ValueType,
+    };
     use anyhow::Result;
     use insta::assert_debug_snapshot;
     use wabt::wat2wasm;
@@ -225,6 +455,255 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_function_signatures() -> Result<()> {
+        let source = r#"
+(module
+  (import "test" "print_i32" (func $print_i32 (param i32)))
+  (func $add (export "add") (param i32 i32) (result i32)
+    (local i64 i64 f32)
+    (i32.add
+      (local.get 0)
+      (local.get 1)
+    )
+  )
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+
+        let reader = std::io::Cursor::new(wasm);
+        let mut decoder = Decoder::new(reader);
+        let module = decoder.decode()?;
+
+        let Some(ExportDesc::Func(idx)) = module
+            .export_section
+            .iter()
+            .flatten()
+            .find(|export| export.name == "add")
+            .map(|export| export.desc.clone())
+        else {
+            panic!("not found exported function: add");
+        };
+
+        let signatures = module.function_signatures();
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(
+            signatures[idx as usize],
+            FuncType {
+                params: vec![ValueType::I32, ValueType::I32],
+                results: vec![ValueType::I32],
+            }
+        );
+
+        let locals = module.function_locals();
+        assert_eq!(
+            locals,
+            vec![vec![
+                FunctionLocal {
+                    type_count: 2,
+                    value_type: ValueType::I64,
+                },
+                FunctionLocal {
+                    type_count: 1,
+                    value_type: ValueType::F32,
+                },
+            ]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_imports() -> Result<()> {
+        let source = r#"
+(module
+  (import "env" "print_i32" (func (param i32)))
+  (import "env" "memory" (memory 1 2))
+  (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let func_type = FuncType {
+            params: vec![ValueType::I32],
+            results: vec![],
+        };
+        let imports = module.imports()?;
+        assert_eq!(
+            imports,
+            vec![
+                ImportDesc {
+                    module: "env".into(),
+                    field: "print_i32".into(),
+                    ty: ImportType::Func(func_type.clone()),
+                },
+                ImportDesc {
+                    module: "env".into(),
+                    field: "memory".into(),
+                    ty: ImportType::Memory(Memory {
+                        limits: Limits {
+                            min: 1,
+                            max: Some(2),
+                        },
+                    }),
+                },
+                ImportDesc {
+                    module: "wasi_snapshot_preview1".into(),
+                    field: "proc_exit".into(),
+                    ty: ImportType::Func(func_type),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exports() -> Result<()> {
+        let source = r#"
+(module
+  (import "env" "print_i32" (func (param i32)))
+  (import "env" "counter" (global (mut i32)))
+  (memory (export "memory") 1)
+  (global (export "limit") i64 (i64.const 0))
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+  (export "counter" (global 0))
+  (export "print" (func 0))
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let global_type = |value_type, mutability| GlobalType {
+            value_type,
+            mutability,
+        };
+        let exports = module.exports()?;
+        let types: Vec<_> = exports.iter().map(|e| (e.name.as_str(), &e.ty)).collect();
+        assert_eq!(
+            types,
+            vec![
+                (
+                    "memory",
+                    &ImportType::Memory(Memory {
+                        limits: Limits { min: 1, max: None },
+                    })
+                ),
+                (
+                    "limit",
+                    &ImportType::Global(global_type(ValueType::I64, Mutability::Const))
+                ),
+                (
+                    "add",
+                    &ImportType::Func(FuncType {
+                        params: vec![ValueType::I32, ValueType::I32],
+                        results: vec![ValueType::I32],
+                    })
+                ),
+                (
+                    "counter",
+                    &ImportType::Global(global_type(ValueType::I32, Mutability::Var))
+                ),
+                (
+                    "print",
+                    &ImportType::Func(FuncType {
+                        params: vec![ValueType::I32],
+                        results: vec![],
+                    })
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits() -> Result<()> {
+        let source = r#"
+(module
+  (memory 1 10)
+  (table 2 funcref)
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(module.memory_limits(), Some((1, Some(10))));
+        assert_eq!(module.table_limits(), Some((2, None)));
+
+        let source = r#"
+(module
+  (import "env" "memory" (memory 3 4))
+  (import "env" "table" (table 5 6 funcref))
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(module.memory_limits(), Some((3, Some(4))));
+        assert_eq!(module.table_limits(), Some((5, Some(6))));
+
+        let wasm = wat2wasm("(module)".as_bytes())?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(module.memory_limits(), None);
+        assert_eq!(module.table_limits(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_summary() -> Result<()> {
+        let source = r#"
+(module
+  (import "env" "memory" (memory 1 2))
+  (memory 3 4)
+  (table 5 funcref)
+  (func $main)
+  (start $main)
+)
+            "#;
+        let wasm = wat::parse_str(source)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let summary = module.resource_summary();
+        assert_eq!(
+            summary,
+            ResourceSummary {
+                memories: vec![
+                    Limits {
+                        min: 1,
+                        max: Some(2)
+                    },
+                    Limits {
+                        min: 3,
+                        max: Some(4)
+                    },
+                ],
+                tables: vec![Limits { min: 5, max: None }],
+                min_memory_pages: 4,
+                max_memory_pages: Some(6),
+                has_start: true,
+            }
+        );
+
+        let source = r#"
+(module
+  (memory 1)
+)
+            "#;
+        let wasm = wat::parse_str(source)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let summary = module.resource_summary();
+        assert_eq!(summary.min_memory_pages, 1);
+        assert_eq!(summary.max_memory_pages, None);
+        assert!(!summary.has_start);
+
+        Ok(())
+    }
+
     #[test]
     fn test_return() -> Result<()> {
         let source = r#"
@@ -244,4 +723,291 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_trailing_bytes() -> Result<()> {
+        let source = r#"
+(module
+  (func (export "test") (result i32) (i32.const 1))
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+
+        let garbages: [&[u8]; 2] = [
+            &[0xff, 0x01, 0x02],       // invalid section id
+            &[0x01, 0x10, 0x00, 0x01], // section size is larger than the rest
+        ];
+        for garbage in garbages {
+            let mut bytes = wasm.clone();
+            bytes.extend_from_slice(garbage);
+
+            let result = Decoder::new(bytes.as_slice()).decode();
+            assert!(result.is_err(), "garbage {:?} must be rejected", garbage);
+
+            let options = DecodeOptions {
+                lenient: true,
+                ..DecodeOptions::default()
+            };
+            let module = Decoder::with_options(bytes.as_slice(), options).decode()?;
+            assert_eq!(module.function_section, Some(vec![0]));
+            assert_eq!(module.export_section.map(|e| e.len()), Some(1));
+            assert_eq!(module.code_section.map(|c| c.len()), Some(1));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_broken_middle_section() -> Result<()> {
+        let source = r#"
+(module
+  (memory 1)
+  (func (export "test") (result i32) (i32.const 1))
+  (data (i32.const 0) "hello")
+)
+            "#;
+        let wasm = wat2wasm(source.as_bytes())?;
+
+        // replace the id of the export section with an invalid one
+        let export = wasm
+            .windows(2)
+            .position(|w| w == [0x07, 0x08])
+            .expect("not found export section");
+        let mut bytes = wasm.clone();
+        bytes[export] = 0xff;
+
+        for lenient in [false, true] {
+            let options = DecodeOptions {
+                lenient,
+                ..DecodeOptions::default()
+            };
+            let result = Decoder::with_options(bytes.as_slice(), options).decode();
+            assert_eq!(
+                result.map(|_| ()).unwrap_err().to_string(),
+                "invalid section id: 255",
+                "lenient: {lenient}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_feature() -> Result<()> {
+        let sources = [
+            ("(module (func (drop (v128.const i32x4 0 0 0 0))))", "simd"),
+            ("(module (func (param v128)))", "simd"),
+            (
+                "(module (func (drop (ref.null extern))))",
+                "reference types",
+            ),
+            ("(module (table 1 externref))", "reference types"),
+            (
+                "(module (func (drop (i32.trunc_sat_f32_s (f32.const 1)))))",
+                "non-trapping float-to-int conversions",
+            ),
+        ];
+        for (source, feature) in sources {
+            let wasm = wat::parse_str(source)?;
+            let err = Decoder::new(wasm.as_slice())
+                .decode()
+                .expect_err("unsupported feature must be rejected");
+            match err.downcast_ref::<Error>() {
+                Some(Error::Unsupported(f)) => assert_eq!(*f, feature),
+                _ => panic!("unexpected error: {err:?}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_section_id_and_elem_type() -> Result<()> {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+        let wasm = [&header[..], &[0x0d, 0x00]].concat();
+        let err = Decoder::new(wasm.as_slice()).decode().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidSectionId(0x0d))
+        ));
+
+        // (table 1 <0x40>)
+        let wasm = [&header[..], &[0x04, 0x04, 0x01, 0x40, 0x00, 0x01]].concat();
+        let err = Decoder::new(wasm.as_slice()).decode().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidElmType(0x40))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_opcode() -> Result<()> {
+        // the first byte of the body is the count of locals
+        let wasm = wat::parse_str("(module (func (drop (v128.const i32x4 0 0 0 0))))")?;
+        let err = Decoder::new(wasm.as_slice())
+            .decode()
+            .expect_err("simd must be rejected");
+        assert_eq!(err.to_string(), "unsupported opcode 0xFD at offset 1");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::Unsupported("simd"))
+        ));
+
+        #[rustfmt::skip]
+        let wasm = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00,
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x01, 0xff, 0x0b, // nop, then unknown 0xff
+        ];
+        let err = Decoder::new(wasm.as_slice())
+            .decode()
+            .expect_err("unknown opcode must be rejected");
+        assert_eq!(err.to_string(), "unsupported opcode 0xFF at offset 2");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidOpcode(0xff))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_version() -> Result<()> {
+        let wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00];
+        let err = Decoder::new(wasm.as_slice())
+            .decode()
+            .expect_err("version 2 must be rejected");
+        assert_eq!(err.to_string(), "unsupported binary version: 2, expected 1");
+
+        let wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(Decoder::new(wasm.as_slice()).decode()?.version, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_const_bits() -> Result<()> {
+        // NOTE: the quiet bit is cleared, so these are signaling NaNs
+        let source = r#"
+(module
+  (func (result f32) (f32.const -nan:0x200001))
+  (func (result f64) (f64.const nan:0x4000000000001))
+)
+            "#;
+        let wasm = wat::parse_str(source)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let bodies = module.code_section.expect("not found code section");
+        let Instruction::F32Const(f) = bodies[0].code[0] else {
+            panic!("unexpected instruction: {:?}", bodies[0].code[0]);
+        };
+        assert_eq!(f.to_bits(), 0xffa0_0001);
+        let Instruction::F64Const(f) = bodies[1].code[0] else {
+            panic!("unexpected instruction: {:?}", bodies[1].code[0]);
+        };
+        assert_eq!(f.to_bits(), 0x7ff4_0000_0000_0001);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_i64_const_range() -> Result<()> {
+        // NOTE: i64::MIN and i64::MAX are encoded in 10 bytes of signed LEB128
+        #[rustfmt::skip]
+        let wasm = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7e,
+            0x03, 0x04, 0x03, 0x00, 0x00, 0x00,
+            0x0a, 0x22, 0x03,
+            0x0d, 0x00, 0x42, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f, 0x0b,
+            0x0d, 0x00, 0x42, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x0b,
+            0x04, 0x00, 0x42, 0x7f, 0x0b,
+        ];
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let bodies = module.code_section.expect("not found code section");
+        let values: Vec<_> = bodies
+            .iter()
+            .map(|body| match body.code[0] {
+                Instruction::I64Const(value) => value,
+                ref inst => panic!("unexpected instruction: {:?}", inst),
+            })
+            .collect();
+        assert_eq!(values, vec![i64::MIN, i64::MAX, -1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_arg() -> Result<()> {
+        // NOTE: the align immediate is the exponent, it's encoded even if it's the natural one
+        let source = r#"
+(module
+  (memory 1)
+  (func
+    (drop (i32.load offset=4 align=1 (i32.const 0)))
+    (drop (i32.load (i32.const 0)))
+    (i64.store offset=16 (i32.const 0) (i64.const 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(source)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let bodies = module.code_section.expect("not found code section");
+        let args: Vec<_> = bodies[0]
+            .code
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::I32Load(arg) | Instruction::I64Store(arg) => {
+                    Some((arg.align, arg.offset))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(args, vec![(0, 4), (2, 0), (3, 16)]);
+        // the following instructions are decoded from the correct position
+        assert!(matches!(bodies[0].code[2], Instruction::Drop));
+        assert!(matches!(bodies[0].code[5], Instruction::Drop));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunked_reader() -> Result<()> {
+        // returns at most 3 bytes per read call
+        struct Chunked<'a>(&'a [u8]);
+
+        impl std::io::Read for Chunked<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = self.0.len().min(buf.len()).min(3);
+                let (chunk, rest) = self.0.split_at(len);
+                buf[..len].copy_from_slice(chunk);
+                self.0 = rest;
+                Ok(len)
+            }
+        }
+
+        let source = r#"
+(module
+  (import "env" "log" (func $log (param i32)))
+  (memory 1)
+  (data (i32.const 0) "hello, world")
+  (func $add (export "add") (param i32 i32) (result i32)
+    (local i64 f32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+)
+"#;
+        let wasm = wat::parse_str(source)?;
+        let expected = Decoder::new(wasm.as_slice()).decode()?;
+        let actual = Decoder::new(Chunked(&wasm)).decode()?;
+        assert_eq!(format!("{actual:?}"), format!("{expected:?}"));
+
+        Ok(())
+    }
 }
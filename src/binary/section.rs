@@ -22,24 +22,28 @@ pub enum SectionID {
     Element = 0x09,
     Code = 0x0a,
     Data = 0x0b,
+    DataCount = 0x0c,
 }
 
-impl From<u8> for SectionID {
-    fn from(id: u8) -> Self {
+impl TryFrom<u8> for SectionID {
+    type Error = Error;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
         match id {
-            0x00 => SectionID::Custom,
-            0x01 => SectionID::Type,
-            0x02 => SectionID::Import,
-            0x03 => SectionID::Function,
-            0x04 => SectionID::Table,
-            0x05 => SectionID::Memory,
-            0x06 => SectionID::Global,
-            0x07 => SectionID::Export,
-            0x08 => SectionID::Start,
-            0x09 => SectionID::Element,
-            0x0b => SectionID::Data,
-            0x0a => SectionID::Code,
-            _ => panic!("unknown section id: {}", id),
+            0x00 => Ok(SectionID::Custom),
+            0x01 => Ok(SectionID::Type),
+            0x02 => Ok(SectionID::Import),
+            0x03 => Ok(SectionID::Function),
+            0x04 => Ok(SectionID::Table),
+            0x05 => Ok(SectionID::Memory),
+            0x06 => Ok(SectionID::Global),
+            0x07 => Ok(SectionID::Export),
+            0x08 => Ok(SectionID::Start),
+            0x09 => Ok(SectionID::Element),
+            0x0b => Ok(SectionID::Data),
+            0x0a => Ok(SectionID::Code),
+            0x0c => Ok(SectionID::DataCount),
+            _ => Err(Error::InvalidSectionId(id)),
         }
     }
 }
@@ -124,6 +128,7 @@ pub enum Section {
     Element(Vec<Element>),
     Data(Vec<Data>),
     Code(Vec<FunctionBody>),
+    DataCount(u32),
 }
 
 pub fn decode(id: SectionID, data: &[u8]) -> Result<Section> {
@@ -141,6 +146,7 @@ pub fn decode(id: SectionID, data: &[u8]) -> Result<Section> {
         SectionID::Element => decode_element_section(&mut reader)?,
         SectionID::Data => decode_data_section(&mut reader)?,
         SectionID::Code => decode_code_section(&mut reader)?,
+        SectionID::DataCount => decode_data_count_section(&mut reader)?,
     };
     Ok(section)
 }
@@ -152,41 +158,77 @@ fn decode_custom_section(reader: &mut SectionReader) -> Result<Section> {
     Ok(Section::Custom(Custom { name, data }))
 }
 
+// https://webassembly.github.io/spec/core/binary/modules.html#data-section
 fn decode_data_section(reader: &mut SectionReader) -> Result<Section> {
     let mut data = vec![];
     let count = reader.u32()?;
     for _ in 0..count {
-        let memory_index = reader.u32()?;
-        let offset = decode_expr(reader)?;
+        let mode = match reader.u32()? {
+            0 => DataMode::Active {
+                memory_index: 0,
+                offset: decode_expr(reader)?,
+            },
+            1 => DataMode::Passive,
+            2 => DataMode::Active {
+                memory_index: reader.u32()?,
+                offset: decode_expr(reader)?,
+            },
+            flag => bail!(Error::InvalidDataSegmentFlag(flag)),
+        };
         let size = reader.u32()?;
         let init = reader.bytes(size as usize)?;
-        data.push(Data {
-            memory_index,
-            offset,
-            init,
-        });
+        data.push(Data { mode, init });
     }
 
     Ok(Section::Data(data))
 }
 
+fn decode_data_count_section(reader: &mut SectionReader) -> Result<Section> {
+    let count = reader.u32()?;
+    Ok(Section::DataCount(count))
+}
+
+// NOTE: only funcref (0x00) is defined as elemkind
+fn decode_elem_kind(reader: &mut SectionReader) -> Result<()> {
+    let elem_kind = reader.byte()?;
+    if elem_kind != 0x00 {
+        bail!(Error::InvalidElmType(elem_kind));
+    }
+    Ok(())
+}
+
 fn decode_element_section(reader: &mut SectionReader) -> Result<Section> {
     let mut elements = vec![];
     let count = reader.u32()?;
     for _ in 0..count {
         let mut init = vec![];
-        let table_index = reader.u32()?;
-        let offset = decode_expr(reader)?;
+        // https://webassembly.github.io/spec/core/binary/modules.html#element-section
+        let mode = match reader.u32()? {
+            0 => ElementMode::Active {
+                table_index: 0,
+                offset: decode_expr(reader)?,
+            },
+            2 => {
+                let table_index = reader.u32()?;
+                let offset = decode_expr(reader)?;
+                decode_elem_kind(reader)?;
+                ElementMode::Active {
+                    table_index,
+                    offset,
+                }
+            }
+            3 => {
+                decode_elem_kind(reader)?;
+                ElementMode::Declarative
+            }
+            _ => bail!(Error::Unsupported("reference types")),
+        };
         let count = reader.u32()?;
         for _ in 0..count {
             let index = reader.u32()?;
             init.push(index);
         }
-        elements.push(Element {
-            table_index,
-            offset,
-            init,
-        });
+        elements.push(Element { mode, init });
     }
 
     Ok(Section::Element(elements))
@@ -250,8 +292,9 @@ fn decode_global_type(reader: &mut SectionReader) -> Result<GlobalType> {
     let value_type = reader.byte()?;
     let mutability = reader.byte()?;
     let global_type = GlobalType {
-        value_type: value_type.into(),
-        mutability: Mutability::from_u8(mutability).unwrap(),
+        value_type: value_type.try_into()?,
+        mutability: Mutability::from_u8(mutability)
+            .with_context(|| Error::InvalidMutability(mutability))?,
     };
     Ok(global_type)
 }
@@ -261,7 +304,7 @@ fn decode_global_section(reader: &mut SectionReader) -> Result<Section> {
     let mut globals = vec![];
     for _ in 0..count {
         let global_type = decode_global_type(reader)?;
-        let init_expr = decode_expr_value(reader)?;
+        let init_expr = decode_expr(reader)?;
         let global = Global {
             global_type,
             init_expr,
@@ -271,37 +314,6 @@ fn decode_global_section(reader: &mut SectionReader) -> Result<Section> {
     Ok(Section::Global(globals))
 }
 
-fn decode_expr_value(reader: &mut SectionReader) -> Result<ExprValue> {
-    let byte = reader.byte()?;
-    let op: Opcode = Opcode::from_u8(byte).with_context(|| Error::InvalidOpcode(byte))?;
-    let value = match op {
-        Opcode::I32Const => {
-            let value = reader.i32()?;
-            ExprValue::I32(value)
-        }
-        Opcode::I64Const => {
-            let value = reader.i64()?;
-            ExprValue::I64(value)
-        }
-        Opcode::F32Const => {
-            let value = reader.f32()?;
-            ExprValue::F32(value)
-        }
-        Opcode::F64Const => {
-            let value = reader.f64()?;
-            ExprValue::F64(value)
-        }
-        _ => bail!(Error::InvalidInitExprOpcode(byte)),
-    };
-
-    let byte = reader.byte()?;
-    let op: Opcode = Opcode::from_u8(byte).with_context(|| Error::InvalidOpcode(byte))?;
-    if op != Opcode::End {
-        bail!(Error::InvalidInitExprEndOpcode(byte));
-    }
-    Ok(value)
-}
-
 fn decode_expr(reader: &mut SectionReader) -> Result<Expr> {
     let byte = reader.byte()?;
     let op = Opcode::from_u8(byte).with_context(|| Error::InvalidOpcode(byte))?;
@@ -338,23 +350,18 @@ fn decode_expr(reader: &mut SectionReader) -> Result<Expr> {
 }
 
 fn decode_table(reader: &mut SectionReader) -> Result<Table> {
-    let elem_type = reader.byte()?;
-    if elem_type != 0x70 {
-        bail!(Error::InvalidElmType(elem_type));
-    }
-    let limits = decode_limits(reader)?;
-    let table = Table {
-        elem_type: ElemType::from_u8(elem_type).unwrap(),
-        limits,
+    let elem_type = match reader.byte()? {
+        0x70 => ElemType::FuncRef,
+        0x6F => bail!(Error::Unsupported("reference types")),
+        byte => bail!(Error::InvalidElmType(byte)),
     };
+    let limits = decode_limits(reader)?;
+    let table = Table { elem_type, limits };
     Ok(table)
 }
 
 fn decode_table_secttion(reader: &mut SectionReader) -> Result<Section> {
     let count = reader.u32()?;
-    if count != 1 {
-        bail!(Error::InvalidTableCount);
-    }
     let mut tables = vec![];
     for _ in 0..count {
         let table = decode_table(reader)?;
@@ -383,9 +390,6 @@ fn decode_memory(reader: &mut SectionReader) -> Result<Memory> {
 fn decode_memory_section(reader: &mut SectionReader) -> Result<Section> {
     let count = reader.u32()?;
     let mut mems: Vec<Memory> = vec![];
-    if count != 1 {
-        bail!(Error::InvalidMemoryCount);
-    }
     for _ in 0..count {
         mems.push(decode_memory(reader)?);
     }
@@ -409,14 +413,14 @@ fn decode_type_section(reader: &mut SectionReader) -> Result<Section> {
         // read each params
         let size = reader.u32()?;
         for _ in 0..size {
-            let value_type: ValueType = reader.byte()?.into();
+            let value_type: ValueType = reader.byte()?.try_into()?;
             func.params.push(value_type);
         }
 
         // read each results
         let size = reader.u32()?;
         for _ in 0..size {
-            let value_type: ValueType = reader.byte()?.into();
+            let value_type: ValueType = reader.byte()?.try_into()?;
             func.results.push(value_type);
         }
 
@@ -469,19 +473,52 @@ fn decode_code_section(reader: &mut SectionReader) -> Result<Section> {
     Ok(Section::Code(functions))
 }
 
-fn decode_function_body(reader: &mut SectionReader) -> Result<FunctionBody> {
-    let mut function_body = FunctionBody::default();
+// decode only the locals of each function, the instructions are kept as bytes
+pub fn decode_lazy_code_section(data: &[u8]) -> Result<Section> {
+    let mut reader = SectionReader::new(data);
+    let mut functions: Vec<FunctionBody> = vec![];
+    let count = reader.u32()?;
+
+    for _ in 0..count {
+        let func_body_size = reader.u32()?;
+        let bytes = reader.bytes(func_body_size as usize)?;
+        let locals = decode_locals(&mut SectionReader::new(&bytes))?;
+        functions.push(FunctionBody {
+            locals,
+            code: vec![],
+            bytes: Some(bytes),
+        });
+    }
+    Ok(Section::Code(functions))
+}
+
+// decode the instructions of the function body which is decoded lazily
+pub fn decode_instructions(bytes: &[u8]) -> Result<Vec<Instruction>> {
+    let mut reader = SectionReader::new(bytes);
+    Ok(decode_function_body(&mut reader)?.code)
+}
+
+fn decode_locals(reader: &mut SectionReader) -> Result<Vec<FunctionLocal>> {
+    let mut locals = vec![];
 
     // count of local variable declarations
     let count = reader.u32()?;
     for _ in 0..count {
         let type_count = reader.u32()?;
-        let value_type: ValueType = reader.byte()?.into();
-        function_body.locals.push(FunctionLocal {
+        let value_type: ValueType = reader.byte()?.try_into()?;
+        locals.push(FunctionLocal {
             type_count,
             value_type,
         })
     }
+    Ok(locals)
+}
+
+fn decode_function_body(reader: &mut SectionReader) -> Result<FunctionBody> {
+    let mut function_body = FunctionBody {
+        locals: decode_locals(reader)?,
+        ..FunctionBody::default()
+    };
 
     while reader.is_end()? {
         let inst = decode_instruction(reader)?;
@@ -496,7 +533,7 @@ fn decode_block_type(reader: &mut SectionReader) -> Result<BlockType> {
     let block_type = if byte == 0x40 {
         BlockType::Empty
     } else {
-        let value_type = byte.into();
+        let value_type = byte.try_into()?;
         BlockType::Value(vec![value_type])
     };
     Ok(block_type)
@@ -508,9 +545,29 @@ fn decode_block(reader: &mut SectionReader) -> Result<Block> {
     Ok(block)
 }
 
+// opcodes of the proposals which are not supported yet
+fn unsupported_feature(byte: u8) -> Option<&'static str> {
+    match byte {
+        0xFD => Some("simd"),
+        0xFE => Some("threads"),
+        0x25 | 0x26 => Some("reference types"),
+        _ => None,
+    }
+}
+
+fn decode_opcode(byte: u8) -> Result<Opcode> {
+    if let Some(feature) = unsupported_feature(byte) {
+        bail!(Error::Unsupported(feature));
+    }
+    Opcode::from_u8(byte).with_context(|| Error::InvalidOpcode(byte))
+}
+
 fn decode_instruction(reader: &mut SectionReader) -> Result<Instruction> {
+    // NOTE: the offset is relative to the start of the function body
+    let offset = reader.buf.position();
     let byte = reader.byte()?;
-    let op: Opcode = Opcode::from_u8(byte).with_context(|| Error::InvalidOpcode(byte))?;
+    let op = decode_opcode(byte)
+        .with_context(|| format!("unsupported opcode 0x{byte:02X} at offset {offset}"))?;
     //trace!("decode opcode: {:?}", op);
     let inst = match op {
         Opcode::Unreachable => Instruction::Unreachable,
@@ -538,6 +595,17 @@ fn decode_instruction(reader: &mut SectionReader) -> Result<Instruction> {
         }
         // first u32 is function signature index, second u32 is table index
         Opcode::CallIndirect => Instruction::CallIndirect((reader.u32()?, reader.u32()?)),
+        Opcode::ReturnCall => Instruction::ReturnCall(reader.u32()?),
+        Opcode::RefNull => match reader.byte()? {
+            0x70 => Instruction::RefNull,
+            0x6F => bail!(Error::Unsupported("reference types")),
+            byte => bail!(Error::InvalidElmType(byte)),
+        },
+        Opcode::RefIsNull => Instruction::RefIsNull,
+        Opcode::RefFunc => Instruction::RefFunc(reader.u32()?),
+        Opcode::ReturnCallIndirect => {
+            Instruction::ReturnCallIndirect((reader.u32()?, reader.u32()?))
+        }
         Opcode::Return => Instruction::Return,
         Opcode::LocalGet => {
             let local_idx = reader.u32()?;
@@ -692,29 +760,48 @@ fn decode_instruction(reader: &mut SectionReader) -> Result<Instruction> {
         Opcode::I64Store16 => Instruction::I64Store16(read_memory_arg(reader)?),
         Opcode::I64Store32 => Instruction::I64Store32(read_memory_arg(reader)?),
         Opcode::MemoryGrow => Instruction::MemoryGrow(reader.u32()?),
-        Opcode::MemorySize => {
-            // NOTE: memory index is always 0 now
-            let _ = reader.byte();
-            Instruction::MemorySize
-        }
+        Opcode::MemorySize => Instruction::MemorySize(reader.u32()?),
         // TODO: improve instruction decoding because opecode maybe tow bytes in the version 2
         // this instruction is defined in the version2 of the spec
         Opcode::MmeoryCopyOrFill => {
             let kind = reader.byte()?;
             match kind {
+                0x08 => {
+                    let dataidx = reader.u32()?;
+                    let memidx = reader.u32()?;
+                    Instruction::MemoryInit(dataidx, memidx)
+                }
+                0x09 => {
+                    let dataidx = reader.u32()?;
+                    Instruction::DataDrop(dataidx)
+                }
                 0x0A => {
-                    let src_memidx = reader.u32()?;
                     let dest_memidx = reader.u32()?;
-                    Instruction::MemoryCopy(src_memidx, dest_memidx)
+                    let src_memidx = reader.u32()?;
+                    Instruction::MemoryCopy(dest_memidx, src_memidx)
                 }
                 0x0B => {
                     let memidx = reader.u32()?;
                     Instruction::MemoryFill(memidx)
                 }
-                _ => unreachable!(),
+                0x00..=0x07 => bail!(Error::Unsupported("non-trapping float-to-int conversions")),
+                0x0F => {
+                    let tableidx = reader.u32()?;
+                    Instruction::TableGrow(tableidx)
+                }
+                0x0C..=0x0E | 0x10..=0x11 => bail!(Error::Unsupported("bulk table operations")),
+                _ => bail!("invalid opcode: fc {:x}", kind),
             }
         }
         Opcode::Select => Instruction::Select,
+        Opcode::TypedSelect => {
+            let count = reader.u32()?;
+            let mut value_types = vec![];
+            for _ in 0..count {
+                value_types.push(reader.byte()?.try_into()?);
+            }
+            Instruction::TypedSelect(value_types)
+        }
         Opcode::I32TruncF32S => Instruction::I32TruncF32S,
         Opcode::I32TruncF32U => Instruction::I32TruncF32U,
         Opcode::I32TruncF64S => Instruction::I32TruncF64S,
@@ -743,10 +830,19 @@ fn decode_instruction(reader: &mut SectionReader) -> Result<Instruction> {
     Ok(inst)
 }
 
+// NOTE: the 6th bit of align means that the memory index follows in the multi-memory proposal
+// https://github.com/WebAssembly/multi-memory/blob/main/proposals/multi-memory/Overview.md
 fn read_memory_arg(reader: &mut SectionReader) -> Result<MemoryArg> {
+    let align = reader.u32()?;
+    let (align, memory) = if align & 0x40 != 0 {
+        (align & !0x40, reader.u32()?)
+    } else {
+        (align, 0)
+    };
     let arg = MemoryArg {
-        align: reader.u32()?,
+        align,
         offset: reader.u32()?,
+        memory,
     };
     Ok(arg)
 }
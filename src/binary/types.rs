@@ -1,23 +1,30 @@
-use super::instruction::Instruction;
+use super::{error::Error, instruction::Instruction, section::decode_instructions};
 use num_derive::FromPrimitive;
+use std::borrow::Cow;
 
 // https://webassembly.github.io/spec/core/binary/types.html#value-types
 #[derive(Debug, Clone, PartialEq)]
 pub enum ValueType {
-    I32, // 0x7F
-    I64, // 0x7E
-    F32, // 0x7D
-    F64, // 0x7C
+    I32,     // 0x7F
+    I64,     // 0x7E
+    F32,     // 0x7D
+    F64,     // 0x7C
+    FuncRef, // 0x70
 }
 
-impl From<u8> for ValueType {
-    fn from(value_type: u8) -> Self {
+impl TryFrom<u8> for ValueType {
+    type Error = Error;
+
+    fn try_from(value_type: u8) -> Result<Self, Self::Error> {
         match value_type {
-            0x7F => Self::I32,
-            0x7E => Self::I64,
-            0x7D => Self::F32,
-            0x7C => Self::F64,
-            _ => panic!("Invalid value type: {:X}", value_type),
+            0x7F => Ok(Self::I32),
+            0x7E => Ok(Self::I64),
+            0x7D => Ok(Self::F32),
+            0x7C => Ok(Self::F64),
+            0x7B => Err(Error::Unsupported("simd")),
+            0x70 => Ok(Self::FuncRef),
+            0x6F => Err(Error::Unsupported("reference types")),
+            _ => Err(Error::InvalidValueType(value_type)),
         }
     }
 }
@@ -31,7 +38,6 @@ pub struct FuncType {
 
 // https://webassembly.github.io/spec/core/binary/modules.html#binary-codesec
 #[derive(Debug, Clone, PartialEq)]
-#[allow(dead_code)]
 pub struct FunctionLocal {
     pub type_count: u32,
     pub value_type: ValueType,
@@ -41,6 +47,17 @@ pub struct FunctionLocal {
 pub struct FunctionBody {
     pub locals: Vec<FunctionLocal>,
     pub code: Vec<Instruction>,
+    pub bytes: Option<Vec<u8>>, // whole body, only if the instructions are decoded lazily
+}
+
+impl FunctionBody {
+    // instructions of the body, which are decoded from the bytes if not decoded yet
+    pub fn instructions(&self) -> anyhow::Result<Cow<'_, [Instruction]>> {
+        match self.bytes {
+            Some(ref bytes) => Ok(Cow::Owned(decode_instructions(bytes)?)),
+            None => Ok(Cow::Borrowed(&self.code)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -57,35 +74,35 @@ pub struct Export {
     pub desc: ExportDesc,
 }
 
-#[derive(Debug, PartialEq, FromPrimitive)]
+#[derive(Debug, Clone, PartialEq, FromPrimitive)]
 pub enum ElemType {
     FuncRef = 0x70,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Table {
     pub elem_type: ElemType,
     pub limits: Limits,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Memory {
     pub limits: Limits,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Limits {
     pub min: u32,
     pub max: Option<u32>,
 }
 
-#[derive(Debug, PartialEq, FromPrimitive)]
+#[derive(Debug, Clone, PartialEq, FromPrimitive)]
 pub enum Mutability {
     Const = 0x00,
     Var = 0x01,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GlobalType {
     pub value_type: ValueType,
     pub mutability: Mutability,
@@ -108,11 +125,13 @@ pub enum Expr {
 macro_rules! from_expr_value {
     ($($ty:ty => $atrr:ident),+) => {
         $(
-            impl From<ExprValue> for $ty {
-                fn from(value: ExprValue) -> Self {
+            impl TryFrom<ExprValue> for $ty {
+                type Error = Error;
+
+                fn try_from(value: ExprValue) -> Result<Self, Self::Error> {
                     match value {
-                        ExprValue::$atrr(v) => v,
-                        _ => unreachable!(),
+                        ExprValue::$atrr(v) => Ok(v),
+                        _ => Err(Error::UnexpectedExprValue(value)),
                     }
                 }
             }
@@ -125,7 +144,7 @@ from_expr_value!(i32 => I32, i64 => I64, f32 => F32, f64 => F64);
 #[derive(Debug, PartialEq)]
 pub struct Global {
     pub global_type: GlobalType,
-    pub init_expr: ExprValue,
+    pub init_expr: Expr,
 }
 
 #[derive(Debug, PartialEq)]
@@ -143,17 +162,62 @@ pub struct Import {
     pub kind: ImportKind,
 }
 
+// import with the resolved type, used to prepare the host environment
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportDesc {
+    pub module: String,
+    pub field: String,
+    pub ty: ImportType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportType {
+    Func(FuncType),
+    Table(Table),
+    Memory(Memory),
+    Global(GlobalType),
+}
+
+// export with the resolved type, the kinds are the same as imports
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportType {
+    pub name: String,
+    pub ty: ImportType,
+}
+
+// resources declared by the module, used to check them before instantiation
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ResourceSummary {
+    pub memories: Vec<Limits>, // imported memories come first
+    pub tables: Vec<Limits>,   // imported tables come first
+    pub min_memory_pages: u64,
+    pub max_memory_pages: Option<u64>, // none if any memory has no maximum
+    pub has_start: bool,
+}
+
+// https://webassembly.github.io/spec/core/syntax/modules.html#element-segments
+#[derive(Debug, PartialEq)]
+pub enum ElementMode {
+    Active { table_index: u32, offset: Expr },
+    Declarative, // only declares the functions referenced by ref.func
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Element {
-    pub table_index: u32,
-    pub offset: Expr,   // offset in table
+    pub mode: ElementMode,
     pub init: Vec<u32>, // index of function
 }
 
+// https://webassembly.github.io/spec/core/syntax/modules.html#data-segments
+#[derive(Debug, PartialEq)]
+pub enum DataMode {
+    Passive,
+    Active { memory_index: u32, offset: Expr },
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Data {
-    pub memory_index: u32,
-    pub offset: Expr,
+    pub mode: DataMode,
     pub init: Vec<u8>,
 }
 
@@ -183,3 +247,18 @@ impl BlockType {
 pub struct Block {
     pub block_type: BlockType,
 }
+
+#[cfg(test)]
+mod test {
+    use super::ExprValue;
+    use crate::binary::error::Error;
+
+    #[test]
+    fn expr_value_try_into() {
+        assert_eq!(i32::try_from(ExprValue::I32(1)).unwrap(), 1);
+        assert!(matches!(
+            i64::try_from(ExprValue::F32(1.0)),
+            Err(Error::UnexpectedExprValue(ExprValue::F32(_)))
+        ));
+    }
+}
@@ -0,0 +1,351 @@
+use super::{
+    instruction::{Instruction, MemoryArg},
+    types::{BlockType, ElemType, FuncType, GlobalType, ImportType, Limits, Mutability, ValueType},
+};
+
+// https://webassembly.github.io/spec/core/text/types.html#value-types
+pub fn value_type(value_type: &ValueType) -> &'static str {
+    match value_type {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+        ValueType::FuncRef => "funcref",
+    }
+}
+
+fn value_types(keyword: &str, value_types: &[ValueType]) -> Option<String> {
+    if value_types.is_empty() {
+        return None;
+    }
+    let types: Vec<_> = value_types.iter().map(value_type).collect();
+    Some(format!("({} {})", keyword, types.join(" ")))
+}
+
+fn limits(limits: &Limits) -> String {
+    match limits.max {
+        Some(max) => format!("{} {}", limits.min, max),
+        None => limits.min.to_string(),
+    }
+}
+
+// https://webassembly.github.io/spec/core/text/types.html#global-types
+fn global_type(global_type: &GlobalType) -> String {
+    let ty = value_type(&global_type.value_type);
+    match global_type.mutability {
+        Mutability::Const => ty.into(),
+        Mutability::Var => format!("(mut {})", ty),
+    }
+}
+
+// https://webassembly.github.io/spec/core/text/types.html#external-types
+pub fn extern_type_to_wat(ty: &ImportType) -> String {
+    match ty {
+        ImportType::Func(func_type) => {
+            let mut items = vec!["func".to_string()];
+            items.extend(value_types("param", &func_type.params));
+            items.extend(value_types("result", &func_type.results));
+            format!("({})", items.join(" "))
+        }
+        ImportType::Table(table) => {
+            let elem_type = match table.elem_type {
+                ElemType::FuncRef => "funcref",
+            };
+            format!("(table {} {})", limits(&table.limits), elem_type)
+        }
+        ImportType::Memory(memory) => format!("(memory {})", limits(&memory.limits)),
+        ImportType::Global(global) => format!("(global {})", global_type(global)),
+    }
+}
+
+fn block_instruction(name: &str, block_type: &BlockType) -> String {
+    match block_type {
+        BlockType::Empty => name.into(),
+        BlockType::Value(types) => match value_types("result", types) {
+            Some(result) => format!("{} {}", name, result),
+            None => name.into(),
+        },
+    }
+}
+
+// NOTE: align is encoded as exponent of 2 in the binary format
+fn memory_instruction(name: &str, arg: &MemoryArg) -> String {
+    let align = 1u64 << arg.align;
+    match arg.memory {
+        0 => format!("{} offset={} align={}", name, arg.offset, align),
+        idx => format!("{} {} offset={} align={}", name, idx, arg.offset, align),
+    }
+}
+
+// https://webassembly.github.io/spec/core/text/values.html#floating-point
+macro_rules! float_to_wat {
+    ($name:ident, $ty:ty, $mantissa_bits:expr) => {
+        fn $name(value: $ty) -> String {
+            let sign = if value.is_sign_negative() { "-" } else { "" };
+            if value.is_nan() {
+                let payload = value.to_bits() & ((1 << $mantissa_bits) - 1);
+                format!("{}nan:0x{:x}", sign, payload)
+            } else if value.is_infinite() {
+                format!("{}inf", sign)
+            } else {
+                // NOTE: debug format is the shortest representation for round trip
+                format!("{:?}", value)
+            }
+        }
+    };
+}
+
+float_to_wat!(f32_to_wat, f32, 23);
+float_to_wat!(f64_to_wat, f64, 52);
+
+// https://webassembly.github.io/spec/core/text/instructions.html
+pub fn instruction_to_wat(inst: &Instruction) -> String {
+    match inst {
+        Instruction::Unreachable => "unreachable".into(),
+        Instruction::Nop => "nop".into(),
+        Instruction::Block(block) => block_instruction("block", &block.block_type),
+        Instruction::Loop(block) => block_instruction("loop", &block.block_type),
+        Instruction::If(block) => block_instruction("if", &block.block_type),
+        Instruction::Else => "else".into(),
+        Instruction::End => "end".into(),
+        Instruction::Br(level) => format!("br {}", level),
+        Instruction::BrIf(level) => format!("br_if {}", level),
+        Instruction::BrTable(levels, default) => {
+            let levels: Vec<_> = levels
+                .iter()
+                .chain([default])
+                .map(|level| level.to_string())
+                .collect();
+            format!("br_table {}", levels.join(" "))
+        }
+        Instruction::Return => "return".into(),
+        Instruction::Call(idx) => format!("call {}", idx),
+        Instruction::CallIndirect((type_idx, table_idx)) => {
+            format!("call_indirect {} (type {})", table_idx, type_idx)
+        }
+        Instruction::ReturnCall(idx) => format!("return_call {}", idx),
+        Instruction::RefNull => "ref.null func".into(),
+        Instruction::RefIsNull => "ref.is_null".into(),
+        Instruction::RefFunc(idx) => format!("ref.func {}", idx),
+        Instruction::ReturnCallIndirect((type_idx, table_idx)) => {
+            format!("return_call_indirect {} (type {})", table_idx, type_idx)
+        }
+        Instruction::Drop => "drop".into(),
+        Instruction::Select => "select".into(),
+        Instruction::TypedSelect(types) => match value_types("result", types) {
+            Some(results) => format!("select {}", results),
+            None => "select".into(),
+        },
+        Instruction::LocalGet(idx) => format!("local.get {}", idx),
+        Instruction::LocalSet(idx) => format!("local.set {}", idx),
+        Instruction::LocalTee(idx) => format!("local.tee {}", idx),
+        Instruction::GlobalGet(idx) => format!("global.get {}", idx),
+        Instruction::GlobalSet(idx) => format!("global.set {}", idx),
+        Instruction::I32Load(arg) => memory_instruction("i32.load", arg),
+        Instruction::I64Load(arg) => memory_instruction("i64.load", arg),
+        Instruction::F32Load(arg) => memory_instruction("f32.load", arg),
+        Instruction::F64Load(arg) => memory_instruction("f64.load", arg),
+        Instruction::I32Load8S(arg) => memory_instruction("i32.load8_s", arg),
+        Instruction::I32Load8U(arg) => memory_instruction("i32.load8_u", arg),
+        Instruction::I32Load16S(arg) => memory_instruction("i32.load16_s", arg),
+        Instruction::I32Load16U(arg) => memory_instruction("i32.load16_u", arg),
+        Instruction::I64Load8S(arg) => memory_instruction("i64.load8_s", arg),
+        Instruction::I64Load8U(arg) => memory_instruction("i64.load8_u", arg),
+        Instruction::I64Load16S(arg) => memory_instruction("i64.load16_s", arg),
+        Instruction::I64Load16U(arg) => memory_instruction("i64.load16_u", arg),
+        Instruction::I64Load32S(arg) => memory_instruction("i64.load32_s", arg),
+        Instruction::I64Load32U(arg) => memory_instruction("i64.load32_u", arg),
+        Instruction::I32Store(arg) => memory_instruction("i32.store", arg),
+        Instruction::I64Store(arg) => memory_instruction("i64.store", arg),
+        Instruction::F32Store(arg) => memory_instruction("f32.store", arg),
+        Instruction::F64Store(arg) => memory_instruction("f64.store", arg),
+        Instruction::I32Store8(arg) => memory_instruction("i32.store8", arg),
+        Instruction::I32Store16(arg) => memory_instruction("i32.store16", arg),
+        Instruction::I64Store8(arg) => memory_instruction("i64.store8", arg),
+        Instruction::I64Store16(arg) => memory_instruction("i64.store16", arg),
+        Instruction::I64Store32(arg) => memory_instruction("i64.store32", arg),
+        Instruction::MemorySize(0) => "memory.size".into(),
+        Instruction::MemorySize(idx) => format!("memory.size {}", idx),
+        Instruction::MemoryGrow(0) => "memory.grow".into(),
+        Instruction::MemoryGrow(idx) => format!("memory.grow {}", idx),
+        Instruction::MemoryCopy(0, 0) => "memory.copy".into(),
+        Instruction::MemoryCopy(dst, src) => format!("memory.copy {} {}", dst, src),
+        Instruction::MemoryFill(0) => "memory.fill".into(),
+        Instruction::MemoryFill(idx) => format!("memory.fill {}", idx),
+        Instruction::MemoryInit(idx, _) => format!("memory.init {}", idx),
+        Instruction::DataDrop(idx) => format!("data.drop {}", idx),
+        Instruction::TableGrow(0) => "table.grow".into(),
+        Instruction::TableGrow(idx) => format!("table.grow {}", idx),
+        Instruction::I32Const(value) => format!("i32.const {}", value),
+        Instruction::I64Const(value) => format!("i64.const {}", value),
+        Instruction::F32Const(value) => format!("f32.const {}", f32_to_wat(*value)),
+        Instruction::F64Const(value) => format!("f64.const {}", f64_to_wat(*value)),
+        Instruction::I32Eqz => "i32.eqz".into(),
+        Instruction::I32Eq => "i32.eq".into(),
+        Instruction::I32Ne => "i32.ne".into(),
+        Instruction::I32LtS => "i32.lt_s".into(),
+        Instruction::I32LtU => "i32.lt_u".into(),
+        Instruction::I32GtS => "i32.gt_s".into(),
+        Instruction::I32GtU => "i32.gt_u".into(),
+        Instruction::I32LeS => "i32.le_s".into(),
+        Instruction::I32LeU => "i32.le_u".into(),
+        Instruction::I32GeS => "i32.ge_s".into(),
+        Instruction::I32GeU => "i32.ge_u".into(),
+        Instruction::I32Clz => "i32.clz".into(),
+        Instruction::I32Ctz => "i32.ctz".into(),
+        Instruction::I32Popcnt => "i32.popcnt".into(),
+        Instruction::I32Add => "i32.add".into(),
+        Instruction::I32Sub => "i32.sub".into(),
+        Instruction::I32Mul => "i32.mul".into(),
+        Instruction::I32DivS => "i32.div_s".into(),
+        Instruction::I32DivU => "i32.div_u".into(),
+        Instruction::I32RemS => "i32.rem_s".into(),
+        Instruction::I32RemU => "i32.rem_u".into(),
+        Instruction::I32And => "i32.and".into(),
+        Instruction::I32Or => "i32.or".into(),
+        Instruction::I32Xor => "i32.xor".into(),
+        Instruction::I32ShL => "i32.shl".into(),
+        Instruction::I32ShrS => "i32.shr_s".into(),
+        Instruction::I32ShrU => "i32.shr_u".into(),
+        Instruction::I32RtoL => "i32.rotl".into(),
+        Instruction::I32RtoR => "i32.rotr".into(),
+        Instruction::I32Extend8S => "i32.extend8_s".into(),
+        Instruction::I32Extend16S => "i32.extend16_s".into(),
+        Instruction::I64Eqz => "i64.eqz".into(),
+        Instruction::I64Eq => "i64.eq".into(),
+        Instruction::I64Ne => "i64.ne".into(),
+        Instruction::I64LtS => "i64.lt_s".into(),
+        Instruction::I64LtU => "i64.lt_u".into(),
+        Instruction::I64GtS => "i64.gt_s".into(),
+        Instruction::I64GtU => "i64.gt_u".into(),
+        Instruction::I64LeS => "i64.le_s".into(),
+        Instruction::I64LeU => "i64.le_u".into(),
+        Instruction::I64GeS => "i64.ge_s".into(),
+        Instruction::I64GeU => "i64.ge_u".into(),
+        Instruction::I64Clz => "i64.clz".into(),
+        Instruction::I64Ctz => "i64.ctz".into(),
+        Instruction::I64Popcnt => "i64.popcnt".into(),
+        Instruction::I64Add => "i64.add".into(),
+        Instruction::I64Sub => "i64.sub".into(),
+        Instruction::I64Mul => "i64.mul".into(),
+        Instruction::I64DivS => "i64.div_s".into(),
+        Instruction::I64DivU => "i64.div_u".into(),
+        Instruction::I64RemS => "i64.rem_s".into(),
+        Instruction::I64RemU => "i64.rem_u".into(),
+        Instruction::I64And => "i64.and".into(),
+        Instruction::I64Or => "i64.or".into(),
+        Instruction::I64Xor => "i64.xor".into(),
+        Instruction::I64ShL => "i64.shl".into(),
+        Instruction::I64ShrS => "i64.shr_s".into(),
+        Instruction::I64ShrU => "i64.shr_u".into(),
+        Instruction::I64RtoL => "i64.rotl".into(),
+        Instruction::I64RtoR => "i64.rotr".into(),
+        Instruction::I64Extend8S => "i64.extend8_s".into(),
+        Instruction::I64Extend16S => "i64.extend16_s".into(),
+        Instruction::I64Extend32S => "i64.extend32_s".into(),
+        Instruction::F32Eq => "f32.eq".into(),
+        Instruction::F32Ne => "f32.ne".into(),
+        Instruction::F32Lt => "f32.lt".into(),
+        Instruction::F32Gt => "f32.gt".into(),
+        Instruction::F32Le => "f32.le".into(),
+        Instruction::F32Ge => "f32.ge".into(),
+        Instruction::F32Abs => "f32.abs".into(),
+        Instruction::F32Neg => "f32.neg".into(),
+        Instruction::F32Ceil => "f32.ceil".into(),
+        Instruction::F32Floor => "f32.floor".into(),
+        Instruction::F32Trunc => "f32.trunc".into(),
+        Instruction::F32Nearest => "f32.nearest".into(),
+        Instruction::F32Sqrt => "f32.sqrt".into(),
+        Instruction::F32Add => "f32.add".into(),
+        Instruction::F32Sub => "f32.sub".into(),
+        Instruction::F32Mul => "f32.mul".into(),
+        Instruction::F32Div => "f32.div".into(),
+        Instruction::F32Min => "f32.min".into(),
+        Instruction::F32Max => "f32.max".into(),
+        Instruction::F32Copysign => "f32.copysign".into(),
+        Instruction::F64Abs => "f64.abs".into(),
+        Instruction::F64Neg => "f64.neg".into(),
+        Instruction::F64Ceil => "f64.ceil".into(),
+        Instruction::F64Floor => "f64.floor".into(),
+        Instruction::F64Trunc => "f64.trunc".into(),
+        Instruction::F64Nearest => "f64.nearest".into(),
+        Instruction::F64Sqrt => "f64.sqrt".into(),
+        Instruction::F64Add => "f64.add".into(),
+        Instruction::F64Sub => "f64.sub".into(),
+        Instruction::F64Mul => "f64.mul".into(),
+        Instruction::F64Div => "f64.div".into(),
+        Instruction::F64Min => "f64.min".into(),
+        Instruction::F64Max => "f64.max".into(),
+        Instruction::F64Copysign => "f64.copysign".into(),
+        Instruction::I32WrapI64 => "i32.wrap_i64".into(),
+        Instruction::F64Eq => "f64.eq".into(),
+        Instruction::F64Ne => "f64.ne".into(),
+        Instruction::F64Lt => "f64.lt".into(),
+        Instruction::F64Gt => "f64.gt".into(),
+        Instruction::F64Le => "f64.le".into(),
+        Instruction::F64Ge => "f64.ge".into(),
+        Instruction::I32TruncF32S => "i32.trunc_f32_s".into(),
+        Instruction::I32TruncF32U => "i32.trunc_f32_u".into(),
+        Instruction::I32TruncF64S => "i32.trunc_f64_s".into(),
+        Instruction::I32TruncF64U => "i32.trunc_f64_u".into(),
+        Instruction::I64ExtendI32S => "i64.extend_i32_s".into(),
+        Instruction::I64ExtendI32U => "i64.extend_i32_u".into(),
+        Instruction::I64TruncF32S => "i64.trunc_f32_s".into(),
+        Instruction::I64TruncF32U => "i64.trunc_f32_u".into(),
+        Instruction::I64TruncF64S => "i64.trunc_f64_s".into(),
+        Instruction::I64TruncF64U => "i64.trunc_f64_u".into(),
+        Instruction::F32ConvertI32S => "f32.convert_i32_s".into(),
+        Instruction::F32ConvertI32U => "f32.convert_i32_u".into(),
+        Instruction::F32ConvertI64S => "f32.convert_i64_s".into(),
+        Instruction::F32ConvertI64U => "f32.convert_i64_u".into(),
+        Instruction::F32DemoteF64 => "f32.demote_f64".into(),
+        Instruction::F64ConvertI32S => "f64.convert_i32_s".into(),
+        Instruction::F64ConvertI32U => "f64.convert_i32_u".into(),
+        Instruction::F64ConvertI64S => "f64.convert_i64_s".into(),
+        Instruction::F64ConvertI64U => "f64.convert_i64_u".into(),
+        Instruction::F64PromoteF32 => "f64.promote_f32".into(),
+        Instruction::I32ReinterpretF32 => "i32.reinterpret_f32".into(),
+        Instruction::I64ReinterpretF64 => "i64.reinterpret_f64".into(),
+        Instruction::F32ReinterpretI32 => "f32.reinterpret_i32".into(),
+        Instruction::F64ReinterpretI64 => "f64.reinterpret_i64".into(),
+    }
+}
+
+// https://webassembly.github.io/spec/core/text/modules.html#functions
+pub fn func_to_wat(
+    name: &str,
+    func_type: &FuncType,
+    locals: &[ValueType],
+    body: &[Instruction],
+) -> String {
+    let name = name.escape_default();
+    let mut header = vec![format!("(func (export \"{}\")", name)];
+    header.extend(value_types("param", &func_type.params));
+    header.extend(value_types("result", &func_type.results));
+
+    let mut lines = vec![header.join(" ")];
+    lines.extend(value_types("local", locals).map(|locals| format!("  {}", locals)));
+
+    // NOTE: the last end instruction is the end of function
+    let body = match body.split_last() {
+        Some((Instruction::End, body)) => body,
+        _ => body,
+    };
+
+    let mut depth = 1;
+    for inst in body {
+        if matches!(inst, Instruction::Else | Instruction::End) {
+            depth = usize::max(depth - 1, 1);
+        }
+        lines.push(format!("{}{}", "  ".repeat(depth), instruction_to_wat(inst)));
+        if matches!(
+            inst,
+            Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) | Instruction::Else
+        ) {
+            depth += 1;
+        }
+    }
+    lines.push(")".into());
+
+    lines.join("\n")
+}
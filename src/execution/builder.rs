@@ -0,0 +1,189 @@
+use super::{
+    error::Error,
+    limiter::{MemoryCharge, SharedResourceLimiter},
+    runtime::Runtime,
+    store::{bind_store, Store},
+};
+use crate::binary::{
+    module::{Decoder, Module},
+    types::{FuncType, ImportKind},
+};
+#[cfg(feature = "wasi")]
+use crate::wasi::WasiSnapshotPreview1;
+use crate::{HostFunc, HostFuncs, ImportResolver, Importer};
+use anyhow::{bail, Context as _, Result};
+use std::{cell::RefCell, fs, io::Cursor, rc::Rc};
+
+// configure imports and limits of runtime together
+#[derive(Default)]
+pub struct RuntimeBuilder {
+    importers: Vec<Box<dyn Importer>>,
+    fuel: Option<u64>,
+    max_call_depth: Option<usize>,
+    max_stack_size: Option<usize>,
+    max_memory_pages: Option<u32>,
+    resource_limiter: Option<SharedResourceLimiter>,
+    resolver: Option<ImportResolver>,
+}
+
+impl RuntimeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(feature = "wasi")]
+    pub fn wasi(self, wasi: WasiSnapshotPreview1) -> Self {
+        self.import(wasi)
+    }
+
+    pub fn import(mut self, importer: impl Importer + 'static) -> Self {
+        self.importers.push(Box::new(importer));
+        self
+    }
+
+    // number of instructions that can be executed
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = Some(max_call_depth);
+        self
+    }
+
+    pub fn max_stack_size(mut self, max_stack_size: usize) -> Self {
+        self.max_stack_size = Some(max_stack_size);
+        self
+    }
+
+    pub fn max_memory_pages(mut self, max_memory_pages: u32) -> Self {
+        self.max_memory_pages = Some(max_memory_pages);
+        self
+    }
+
+    // the limiter can be shared with other runtimes
+    pub fn resource_limiter(mut self, limiter: SharedResourceLimiter) -> Self {
+        self.resource_limiter = Some(limiter);
+        self
+    }
+
+    // the resolver is called for each imported function whose module has no importer,
+    // the instantiation fails if it returns None
+    pub fn resolver(
+        mut self,
+        resolver: impl FnMut(&str, &str, &FuncType) -> Option<HostFunc> + 'static,
+    ) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    pub fn build_from_file(self, file: &str) -> Result<Runtime> {
+        let module = Decoder::new(fs::File::open(file)?).decode()?;
+        self.build_from_module(&module)
+    }
+
+    pub fn build_from_bytes<T: AsRef<[u8]>>(self, b: T) -> Result<Runtime> {
+        let module = Decoder::new(Cursor::new(b)).decode()?;
+        self.build_from_module(&module)
+    }
+
+    fn build_from_module(mut self, module: &Module) -> Result<Runtime> {
+        self.resolve_imports(module)?;
+        let store = Store::new(module, self.take_importers())?;
+        let defined_memories = module.memory_section.as_ref().map_or(0, Vec::len);
+        self.build(store, defined_memories)
+    }
+
+    fn resolve_imports(&mut self, module: &Module) -> Result<()> {
+        let Some(resolver) = self.resolver.as_mut() else {
+            return Ok(());
+        };
+
+        let mut resolved: Vec<HostFuncs> = vec![];
+        for import in module.import_section.iter().flatten() {
+            let ImportKind::Func(idx) = import.kind else {
+                continue;
+            };
+            if self.importers.iter().any(|i| i.name() == import.module) {
+                continue;
+            }
+
+            let func_type = module
+                .type_section
+                .iter()
+                .flatten()
+                .nth(idx as usize)
+                .with_context(|| Error::NotFoundFuncType(idx as usize))?;
+            let host_func =
+                resolver(&import.module, &import.field, func_type).with_context(|| {
+                    Error::UnknownImport(import.module.clone(), import.field.clone())
+                })?;
+
+            let index = match resolved.iter().position(|h| h.name == import.module) {
+                Some(index) => index,
+                None => {
+                    resolved.push(HostFuncs {
+                        name: import.module.clone(),
+                        ..Default::default()
+                    });
+                    resolved.len() - 1
+                }
+            };
+            resolved[index]
+                .funcs
+                .insert(import.field.clone(), host_func);
+        }
+
+        for host_funcs in resolved {
+            self.importers.push(Box::new(host_funcs));
+        }
+        Ok(())
+    }
+
+    fn take_importers(&mut self) -> Option<Vec<Box<dyn Importer>>> {
+        if self.importers.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.importers))
+        }
+    }
+
+    // NOTE: the defined memories follow the imported ones in the store
+    fn build(self, store: Store, defined_memories: usize) -> Result<Runtime> {
+        if let Some(max) = self.max_memory_pages {
+            for memory in store.memory.iter() {
+                let size = memory.borrow().size() as u32;
+                if size > max {
+                    bail!(Error::MemoryPageOverflow(max, size));
+                }
+            }
+        }
+
+        // NOTE: the memories already charged are given back when it's dropped on failure,
+        // and the imported memories are charged to the runtime which defines them
+        let mut memory_charge = self.resource_limiter.clone().map(MemoryCharge::new);
+        if let Some(charge) = &mut memory_charge {
+            let imported_memories = store.memory.len().saturating_sub(defined_memories);
+            for memory in store.memory.iter().skip(imported_memories) {
+                if !charge.charge(memory) {
+                    bail!(Error::MemoryLimitExceeded(memory.borrow().data.len()));
+                }
+            }
+        }
+
+        let store = Rc::new(RefCell::new(store));
+        bind_store(&store);
+        let runtime = Runtime {
+            store,
+            fuel: self.fuel,
+            max_call_depth: self.max_call_depth,
+            max_stack_size: self.max_stack_size,
+            max_memory_pages: self.max_memory_pages,
+            resource_limiter: self.resource_limiter,
+            memory_charge: memory_charge.map(Rc::new),
+            ..Default::default()
+        };
+        runtime.start()
+    }
+}
@@ -0,0 +1,95 @@
+use super::{
+    module::InternalFuncInst,
+    runtime::Runtime,
+    store::{internal_funcs, Store},
+};
+use crate::{
+    binary::module::{Decoder, Module},
+    validation::validate,
+    Importer,
+};
+use anyhow::Result;
+use std::{cell::RefCell, fs, io::Cursor, rc::Rc};
+
+// decoded and validated module, which can be instantiated many times.
+// the resolved function types and bodies are shared by all instances
+// NOTE: branch targets are resolved while executing, so there are no side tables to cache
+pub struct CompiledModule {
+    pub module: Module,
+    pub funcs: Vec<InternalFuncInst>,
+}
+
+impl CompiledModule {
+    pub fn new(module: Module) -> Result<Self> {
+        validate(&module)?;
+        let funcs = internal_funcs(&module)?;
+        Ok(Self { module, funcs })
+    }
+
+    pub fn from_file(file: &str) -> Result<Self> {
+        let module = Decoder::new(fs::File::open(file)?).decode()?;
+        Self::new(module)
+    }
+
+    pub fn from_bytes<T: AsRef<[u8]>>(b: T) -> Result<Self> {
+        let module = Decoder::new(Cursor::new(b)).decode()?;
+        Self::new(module)
+    }
+
+    // create a new instance, each of them has its own memories, tables and globals
+    pub fn instantiate(&self, imports: Option<Vec<Box<dyn Importer>>>) -> Result<Runtime> {
+        let store = Store::with_funcs(&self.module, self.funcs.clone(), imports)?;
+        Runtime::instantiate(Rc::new(RefCell::new(store)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CompiledModule;
+    use crate::{module::FuncInst, Value};
+    use anyhow::Result;
+    use std::rc::Rc;
+
+    #[test]
+    fn instantiate_many_times() -> Result<()> {
+        let code = r#"
+(module
+  (global $count (mut i32) (i32.const 0))
+  (func (export "incr") (param i32) (result i32)
+    (global.set $count (i32.add (global.get $count) (local.get 0)))
+    (global.get $count)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let compiled = CompiledModule::from_bytes(wasm)?;
+
+        for i in 0..100 {
+            let mut runtime = compiled.instantiate(None)?;
+            // the global is not shared with other instances
+            let result = runtime.call("incr".into(), vec![Value::I32(i)])?;
+            assert_eq!(result, Some(Value::I32(i)));
+            let result = runtime.call("incr".into(), vec![Value::I32(1)])?;
+            assert_eq!(result, Some(Value::I32(i + 1)));
+
+            let FuncInst::Internal(func) = &runtime.store.borrow().funcs[0] else {
+                panic!("func must be internal");
+            };
+            let (body, compiled_body) = (
+                func.code.body.insts()?,
+                compiled.funcs[0].code.body.insts()?,
+            );
+            assert!(Rc::ptr_eq(&body, &compiled_body));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_module() -> Result<()> {
+        let code = r#"(module (func (result i32) (i64.const 0)))"#;
+        let wasm = wat::parse_str(code)?;
+        assert!(CompiledModule::from_bytes(wasm).is_err());
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::value::Value;
+use crate::binary::types::ValueType;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -15,8 +16,18 @@ pub enum Error {
     MemorySizeNotPageAligned(u32),
     #[error("memory page is overflow. max is {0}, grow size is {1}")]
     MemoryPageOverflow(u32, u32),
+    #[error("table size is overflow. max is {0}, grow size is {1}")]
+    TableSizeOverflow(u32, u32),
+    #[error("cannot allocate table elements, size is {0}")]
+    TableAllocationFailed(u32),
+    #[error("memory is limited by the resource limiter, desired size is {0} bytes")]
+    MemoryLimitExceeded(usize),
     #[error("unexpected stack value type: {0:?}")]
     UnexpectedStackValueType(Value),
+    #[error("type mismatch, expected {0:?}, got {1:?}")]
+    TypeMismatch(ValueType, Value),
+    #[error("type mismatch of operands, left: {0:?}, right: {1:?}")]
+    TypeMismatchOperands(Value, Value),
     #[error("not found local variable with index: {0}")]
     NotFoundLocalVariable(usize),
     #[error("not found global variable with index: {0}")]
@@ -25,6 +36,10 @@ pub enum Error {
     NotFoundImportModule(String),
     #[error("no any imports")]
     NoImports,
+    #[error("unknown import: {0}.{1}")]
+    UnknownImport(String, String),
+    #[error("incompatible import type: {0}.{1}")]
+    IncompatibleImportType(String, String),
     #[error("not found instruction with pc: {0}")]
     NotFoundInstruction(usize),
     #[error("not found label with index: {0}")]
@@ -49,10 +64,16 @@ pub enum Error {
     InvalidBrTableIndex(usize),
     #[error("cannot pop label when instruction: {0}")]
     LabelPopError(String),
+    #[error("else without if")]
+    ElseWithoutIf,
     #[error("not found function by index: {0}")]
     NotFoundFunction(usize),
     #[error("not found table by index: {0}")]
     NotFoundTable(usize),
+    #[error("not found store by id: {0}")]
+    NotFoundStore(usize),
+    #[error("out of bounds table access")]
+    OutOfBoundsTableAccess,
     #[error("undefined element")]
     UndefinedElement,
     #[error("uninitialized element {0}")]
@@ -61,6 +82,34 @@ pub enum Error {
     NotFoundFuncType(usize),
     #[error("indirect call type mismatch")]
     TypeMismatchIndirectCall,
+    #[error("not found data segment by index: {0}")]
+    NotFoundData(usize),
+    #[error("out of bounds memory access")]
+    OutOfBoundsMemoryAccess,
+    #[error("unsupported feature: {0}")]
+    Unsupported(String),
+    #[error("all fuel consumed")]
+    OutOfFuel,
+    #[error("call stack exhausted")]
+    CallStackExhausted,
+    #[error("operand stack overflow")]
+    StackOverflow,
     #[error("not found type section")]
     NotFoundTypeSection,
+    #[error("constant expression can only refer to imported global: {0}")]
+    NotImportedGlobalInConstExpr(usize),
+    #[error("constant expression can only refer to immutable global: {0}")]
+    MutableGlobalInConstExpr(usize),
+    #[error("global initializer type mismatch, expected {0:?}, got {1:?}")]
+    TypeMismatchGlobalInit(ValueType, ValueType),
+    #[error("global is immutable: {0}")]
+    ImmutableGlobal(String),
+    #[error("expected {0} args of [{1}], got {2}")]
+    ArgumentCountMismatch(usize, String, usize),
+    #[error("expected args of [{0}], got [{1}]")]
+    ArgumentTypeMismatch(String, String),
+    #[error("global type mismatch, expected {0:?}, got {1:?}")]
+    TypeMismatchGlobal(ValueType, ValueType),
+    #[error("table element type mismatch, expected FuncRef, got {0:?}")]
+    TypeMismatchTableElement(ValueType),
 }
@@ -74,3 +74,161 @@ pub trait Frelop {
 funop!(f32, f64);
 fbinop!(f32, f64);
 frelop!(f32, f64);
+
+#[cfg(test)]
+mod test {
+    use super::{Fbinop, Frelop, Funop};
+    use crate::execution::{
+        op,
+        value::{StackAccess, Value},
+    };
+    use anyhow::Result;
+
+    macro_rules! assert_frelop {
+        ($ty: ident) => {{
+            let (zero, inf, nan) = (0.0, $ty::INFINITY, $ty::NAN);
+            let tests = [
+                // (lhs, rhs, eq, ne, lt, gt, le, ge)
+                (zero, -zero, 1, 0, 0, 0, 1, 1),
+                (-zero, zero, 1, 0, 0, 0, 1, 1),
+                (-inf, inf, 0, 1, 1, 0, 1, 0),
+                (inf, -inf, 0, 1, 0, 1, 0, 1),
+                (inf, inf, 1, 0, 0, 0, 1, 1),
+                ($ty::MAX, inf, 0, 1, 1, 0, 1, 0),
+                (nan, nan, 0, 1, 0, 0, 0, 0),
+                (nan, zero, 0, 1, 0, 0, 0, 0),
+                (inf, -nan, 0, 1, 0, 0, 0, 0),
+            ];
+            for (l, r, eq, ne, lt, gt, le, ge) in tests {
+                let args = format!("{}, lhs: {l}, rhs: {r}", stringify!($ty));
+                assert_eq!(l.equal(r)?, eq, "eq {args}");
+                assert_eq!(l.not_equal(r)?, ne, "ne {args}");
+                assert_eq!(l.flt(r)?, lt, "lt {args}");
+                assert_eq!(l.fgt(r)?, gt, "gt {args}");
+                assert_eq!(l.fle(r)?, le, "le {args}");
+                assert_eq!(l.fge(r)?, ge, "ge {args}");
+            }
+        }};
+    }
+
+    #[test]
+    fn signed_zero_and_infinity() -> Result<()> {
+        assert_frelop!(f32);
+        assert_frelop!(f64);
+        Ok(())
+    }
+
+    macro_rules! assert_funop {
+        ($ty: ident) => {{
+            let (inf, nan) = ($ty::INFINITY, $ty::NAN);
+            let big: $ty = 4503599627370497.0; // 2^52 + 1, already integral
+            let tests: [($ty, $ty, $ty, $ty, $ty); 12] = [
+                // (value, nearest, trunc, floor, ceil)
+                (2.5, 2.0, 2.0, 2.0, 3.0),
+                (3.5, 4.0, 3.0, 3.0, 4.0),
+                (-2.5, -2.0, -2.0, -3.0, -2.0),
+                (2.7, 3.0, 2.0, 2.0, 3.0),
+                (0.5, 0.0, 0.0, 0.0, 1.0),
+                (-0.5, -0.0, -0.0, -1.0, -0.0),
+                (-0.3, -0.0, -0.0, -1.0, -0.0),
+                (0.0, 0.0, 0.0, 0.0, 0.0),
+                (-0.0, -0.0, -0.0, -0.0, -0.0),
+                (inf, inf, inf, inf, inf),
+                (-inf, -inf, -inf, -inf, -inf),
+                (big, big, big, big, big),
+            ];
+            for (v, nearest, trunc, floor, ceil) in tests {
+                let args = format!("{}, value: {v}", stringify!($ty));
+                // NOTE: compare bits to distinguish the sign of zero
+                assert_eq!(
+                    Funop::nearest(&v)?.to_bits(),
+                    nearest.to_bits(),
+                    "nearest {args}"
+                );
+                assert_eq!(Funop::trunc(&v)?.to_bits(), trunc.to_bits(), "trunc {args}");
+                assert_eq!(Funop::floor(&v)?.to_bits(), floor.to_bits(), "floor {args}");
+                assert_eq!(Funop::ceil(&v)?.to_bits(), ceil.to_bits(), "ceil {args}");
+            }
+            assert!(Funop::nearest(&nan)?.is_nan());
+            assert!(Funop::trunc(&nan)?.is_nan());
+            assert!(Funop::floor(&nan)?.is_nan());
+            assert!(Funop::ceil(&nan)?.is_nan());
+            let zero: $ty = -0.0;
+            assert_eq!(Funop::sqrt(&zero)?.to_bits(), zero.to_bits());
+        }};
+    }
+
+    #[test]
+    fn rounding_edges() -> Result<()> {
+        assert_funop!(f32);
+        assert_funop!(f64);
+        Ok(())
+    }
+
+    macro_rules! assert_copysign {
+        ($ty: ident, $payload: expr) => {{
+            let inf = $ty::INFINITY;
+            let tests: [($ty, $ty, $ty); 6] = [
+                // (magnitude, sign, result)
+                (3.0, -0.0, -3.0),
+                (-3.0, 0.0, 3.0),
+                (-5.0, inf, 5.0),
+                (5.0, -inf, -5.0),
+                (0.0, -1.0, -0.0),
+                (-0.0, 1.0, 0.0),
+            ];
+            for (l, r, expected) in tests {
+                let args = format!("{}, lhs: {l}, rhs: {r}", stringify!($ty));
+                // NOTE: compare bits to distinguish the sign of zero
+                assert_eq!(
+                    Fbinop::copysign(&l, r)?.to_bits(),
+                    expected.to_bits(),
+                    "copysign {args}"
+                );
+            }
+
+            // the payload of nan is kept, only the sign bit is replaced
+            let nan = $ty::from_bits($payload);
+            let result = Fbinop::copysign(&nan, -1.0)?;
+            assert_eq!(result.to_bits(), $payload | (-0.0 as $ty).to_bits());
+            let result = Fbinop::copysign(&-nan, 1.0)?;
+            assert_eq!(result.to_bits(), $payload);
+        }};
+    }
+
+    #[test]
+    fn copysign() -> Result<()> {
+        assert_copysign!(f32, 0x7fa0_0001);
+        assert_copysign!(f64, 0x7ff4_0000_0000_0001);
+        Ok(())
+    }
+
+    #[test]
+    fn operand_order() -> Result<()> {
+        type Op = fn(&mut Vec<Value>) -> Result<()>;
+        // NOTE: the lhs is pushed first, so the rhs is on the top of stack
+        let tests: [(Op, f64, f64, f64); 3] = [
+            (op::sub, 10.0, 3.0, 7.0),
+            (op::div, 9.0, 3.0, 3.0),
+            (op::copysign, 2.0, -1.0, -2.0),
+        ];
+        for (i, (op, l, r, expected)) in tests.into_iter().enumerate() {
+            let mut stack = vec![];
+            stack.push(Value::F32(l as f32));
+            stack.push(Value::F32(r as f32));
+            op(&mut stack)?;
+            assert_eq!(
+                stack.pop1::<Value>()?,
+                Value::F32(expected as f32),
+                "f32 #{i}"
+            );
+
+            stack.push(Value::F64(l));
+            stack.push(Value::F64(r));
+            op(&mut stack)?;
+            assert_eq!(stack.pop1::<Value>()?, Value::F64(expected), "f64 #{i}");
+            assert!(stack.is_empty());
+        }
+        Ok(())
+    }
+}
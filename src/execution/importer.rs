@@ -1,9 +1,17 @@
 use crate::{
+    binary::types::FuncType,
+    error::Error,
     module::{ExternalFuncInst, FuncInst, GlobalInst, InternalMemoryInst, InternalTableInst},
     Store, Value,
 };
-use anyhow::Result;
-use std::{cell::RefCell, rc::Rc};
+use anyhow::{Context as _, Result};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+// host function provided by the resolver, called with the store of the caller
+pub type HostFunc = Rc<dyn Fn(Rc<RefCell<Store>>, Vec<Value>) -> Result<Option<Value>>>;
+
+// called with the module, field and type of each imported function which has no importer
+pub type ImportResolver = Box<dyn FnMut(&str, &str, &FuncType) -> Option<HostFunc>>;
 
 pub trait Importer {
     fn name(&self) -> &str;
@@ -43,3 +51,29 @@ pub trait Importer {
         Ok(None)
     }
 }
+
+// importer of the host functions returned by the resolver
+#[derive(Default)]
+pub struct HostFuncs {
+    pub name: String,
+    pub funcs: HashMap<String, HostFunc>,
+}
+
+impl Importer for HostFuncs {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn invoke(
+        &self,
+        store: Rc<RefCell<Store>>,
+        func: ExternalFuncInst,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let host_func = self
+            .funcs
+            .get(&func.field)
+            .with_context(|| Error::UnknownImport(func.module.clone(), func.field.clone()))?;
+        host_func(store, args)
+    }
+}
@@ -111,3 +111,164 @@ ibinop!(i64);
 irelop!(i32);
 irelop!(i64);
 itestop!();
+
+#[cfg(test)]
+mod test {
+    use super::{Ibinop, Irelop, Iunop};
+    use crate::execution::{
+        op::{div_s, div_u, popcnt, rem_s, rem_u, rotl, rotr, shl, shr_s, shr_u, sub},
+        value::{StackAccess, Value},
+    };
+    use anyhow::Result;
+
+    #[test]
+    fn i32_compare_boundaries() -> Result<()> {
+        let max_u = u32::MAX as i32; // -1 as signed
+        let tests = [
+            // (lhs, rhs, lt_s, lt_u, gt_s, gt_u, le_s, le_u, ge_s, ge_u)
+            (max_u, 0, 1, 0, 0, 1, 1, 0, 0, 1),
+            (0, max_u, 0, 1, 1, 0, 0, 1, 1, 0),
+            (i32::MIN, i32::MAX, 1, 0, 0, 1, 1, 0, 0, 1),
+            (i32::MAX, i32::MIN, 0, 1, 1, 0, 0, 1, 1, 0),
+            (i32::MIN, max_u, 1, 1, 0, 0, 1, 1, 0, 0),
+            (i32::MAX, max_u, 0, 1, 1, 0, 0, 1, 1, 0),
+            (i32::MIN, i32::MIN, 0, 0, 0, 0, 1, 1, 1, 1),
+            (max_u, max_u, 0, 0, 0, 0, 1, 1, 1, 1),
+        ];
+        for (l, r, lt_s, lt_u, gt_s, gt_u, le_s, le_u, ge_s, ge_u) in tests {
+            let args = format!("lhs: {l}, rhs: {r}");
+            assert_eq!(l.lt_s(r)?, lt_s, "lt_s {args}");
+            assert_eq!(l.lt_u(r)?, lt_u, "lt_u {args}");
+            assert_eq!(l.gt_s(r)?, gt_s, "gt_s {args}");
+            assert_eq!(l.gt_u(r)?, gt_u, "gt_u {args}");
+            assert_eq!(l.le_s(r)?, le_s, "le_s {args}");
+            assert_eq!(l.le_u(r)?, le_u, "le_u {args}");
+            assert_eq!(l.ge_s(r)?, ge_s, "ge_s {args}");
+            assert_eq!(l.ge_u(r)?, ge_u, "ge_u {args}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn i64_compare_boundaries() -> Result<()> {
+        let max_u = u64::MAX as i64; // -1 as signed
+        let max_u32 = u32::MAX as i64; // positive in i64
+        let tests = [
+            // (lhs, rhs, lt_s, lt_u, gt_s, gt_u, le_s, le_u, ge_s, ge_u)
+            (max_u, 0, 1, 0, 0, 1, 1, 0, 0, 1),
+            (0, max_u, 0, 1, 1, 0, 0, 1, 1, 0),
+            (i64::MIN, i64::MAX, 1, 0, 0, 1, 1, 0, 0, 1),
+            (i64::MAX, i64::MIN, 0, 1, 1, 0, 0, 1, 1, 0),
+            (i32::MIN as i64, i32::MAX as i64, 1, 0, 0, 1, 1, 0, 0, 1),
+            (max_u32, i32::MAX as i64, 0, 0, 1, 1, 0, 0, 1, 1),
+            (max_u32, max_u, 0, 1, 1, 0, 0, 1, 1, 0),
+            (i64::MIN, i64::MIN, 0, 0, 0, 0, 1, 1, 1, 1),
+        ];
+        for (l, r, lt_s, lt_u, gt_s, gt_u, le_s, le_u, ge_s, ge_u) in tests {
+            let args = format!("lhs: {l}, rhs: {r}");
+            assert_eq!(l.lt_s(r)?, lt_s, "lt_s {args}");
+            assert_eq!(l.lt_u(r)?, lt_u, "lt_u {args}");
+            assert_eq!(l.gt_s(r)?, gt_s, "gt_s {args}");
+            assert_eq!(l.gt_u(r)?, gt_u, "gt_u {args}");
+            assert_eq!(l.le_s(r)?, le_s, "le_s {args}");
+            assert_eq!(l.le_u(r)?, le_u, "le_u {args}");
+            assert_eq!(l.ge_s(r)?, ge_s, "ge_s {args}");
+            assert_eq!(l.ge_u(r)?, ge_u, "ge_u {args}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shift_count_masking() -> Result<()> {
+        // NOTE: the shift count is taken modulo the bit width
+        let v = i32::MIN | 0b110;
+        for (count, shl, shr_s, shr_u) in [
+            (32, v, v, v),
+            (33, 0b1100, i32::MIN >> 1 | 0b11, (v as u32 >> 1) as i32),
+            (-1, 0, -1, 1),
+        ] {
+            assert_eq!(v.shl(count)?, shl, "i32.shl by {count}");
+            assert_eq!(v.shr_s(count)?, shr_s, "i32.shr_s by {count}");
+            assert_eq!(v.shr_u(count)?, shr_u, "i32.shr_u by {count}");
+        }
+
+        let v = i64::MIN | 0b110;
+        for (count, shl, shr_s, shr_u) in [
+            (64, v, v, v),
+            (65, 0b1100, i64::MIN >> 1 | 0b11, (v as u64 >> 1) as i64),
+            (-1, 0, -1, 1),
+            (
+                1 << 32 | 1,
+                0b1100,
+                i64::MIN >> 1 | 0b11,
+                (v as u64 >> 1) as i64,
+            ),
+        ] {
+            assert_eq!(v.shl(count)?, shl, "i64.shl by {count}");
+            assert_eq!(v.shr_s(count)?, shr_s, "i64.shr_s by {count}");
+            assert_eq!(v.shr_u(count)?, shr_u, "i64.shr_u by {count}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bit_counts() -> Result<()> {
+        // NOTE: bits are counted on the unsigned bit pattern
+        assert_eq!(0i32.clz()?, 32);
+        assert_eq!(0i32.ctz()?, 32);
+        assert_eq!(0i64.clz()?, 64);
+        assert_eq!(0i64.ctz()?, 64);
+        assert_eq!((-1i32).clz()?, 0);
+        assert_eq!(i32::MIN.ctz()?, 31);
+        assert_eq!(i64::MIN.ctz()?, 63);
+
+        let mut stack = vec![];
+        for (value, expected) in [
+            (Value::I32(u32::MAX as i32), Value::I32(32)),
+            (Value::I32(i32::MIN), Value::I32(1)),
+            (Value::I32(0), Value::I32(0)),
+            (Value::I64(-1), Value::I64(64)),
+        ] {
+            stack.push(value);
+            popcnt(&mut stack)?;
+            assert_eq!(stack.pop(), Some(expected));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn operand_order() -> Result<()> {
+        type Op = fn(&mut Vec<Value>) -> Result<()>;
+        // NOTE: the lhs is pushed first, so the rhs is on the top of stack
+        let tests: [(Op, i64, i64, i64); 10] = [
+            (sub, 10, 3, 7),
+            (div_s, 10, 3, 3),
+            (div_u, 10, 3, 3),
+            (rem_s, 10, 3, 1),
+            (rem_u, 10, 3, 1),
+            (shl, 1, 4, 16),
+            (shr_s, 16, 4, 1),
+            (shr_u, 16, 4, 1),
+            (rotl, 1, 4, 16),
+            (rotr, 16, 4, 1),
+        ];
+        for (i, (op, l, r, expected)) in tests.into_iter().enumerate() {
+            let mut stack = vec![];
+            stack.push(Value::I32(l as i32));
+            stack.push(Value::I32(r as i32));
+            op(&mut stack)?;
+            assert_eq!(
+                stack.pop1::<Value>()?,
+                Value::I32(expected as i32),
+                "i32 #{i}"
+            );
+
+            stack.push(Value::I64(l));
+            stack.push(Value::I64(r));
+            op(&mut stack)?;
+            assert_eq!(stack.pop1::<Value>()?, Value::I64(expected), "i64 #{i}");
+            assert!(stack.is_empty());
+        }
+        Ok(())
+    }
+}
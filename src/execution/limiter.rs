@@ -0,0 +1,274 @@
+use super::module::MemoryInst;
+use std::{cell::RefCell, rc::Rc};
+
+// consulted by runtimes at instantiation and on memory.grow,
+// a limiter can be shared by multiple runtimes to cap their total resources
+pub trait ResourceLimiter {
+    // current and desired sizes are in bytes, returns false to reject the growth
+    fn memory_growing(&mut self, current: usize, desired: usize) -> bool;
+
+    // called when the growth allowed by memory_growing failed for another reason
+    fn memory_grow_failed(&mut self, _current: usize, _desired: usize) {}
+
+    // called when the runtime which is charged for the memory is dropped, size is in bytes
+    fn memory_released(&mut self, _size: usize) {}
+}
+
+pub type SharedResourceLimiter = Rc<RefCell<dyn ResourceLimiter>>;
+
+// memories charged to the limiter at instantiation,
+// they are given back with their current sizes when it's dropped
+pub(crate) struct MemoryCharge {
+    limiter: SharedResourceLimiter,
+    memories: Vec<MemoryInst>,
+}
+
+impl MemoryCharge {
+    pub(crate) fn new(limiter: SharedResourceLimiter) -> Self {
+        Self {
+            limiter,
+            memories: vec![],
+        }
+    }
+
+    // charge the initial size of the memory, false if the limiter rejects it
+    pub(crate) fn charge(&mut self, memory: &MemoryInst) -> bool {
+        let size = memory.borrow().data.len();
+        if !self.limiter.borrow_mut().memory_growing(0, size) {
+            return false;
+        }
+        self.memories.push(Rc::clone(memory));
+        true
+    }
+}
+
+impl Drop for MemoryCharge {
+    fn drop(&mut self) {
+        let mut limiter = self.limiter.borrow_mut();
+        for memory in &self.memories {
+            limiter.memory_released(memory.borrow().data.len());
+        }
+    }
+}
+
+// cap the total size of linear memories
+// NOTE: memories are given back when the runtime is dropped, even if others import them
+#[derive(Debug, Default, Clone)]
+pub struct MemoryLimiter {
+    pub max: usize,  // in bytes
+    pub used: usize, // in bytes
+}
+
+impl MemoryLimiter {
+    pub fn new(max: usize) -> Self {
+        Self { max, used: 0 }
+    }
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(&mut self, current: usize, desired: usize) -> bool {
+        let delta = desired.saturating_sub(current);
+        match self.used.checked_add(delta) {
+            Some(used) if used <= self.max => {
+                self.used = used;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn memory_grow_failed(&mut self, current: usize, desired: usize) {
+        self.used -= desired.saturating_sub(current);
+    }
+
+    fn memory_released(&mut self, size: usize) {
+        self.used = self.used.saturating_sub(size);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryLimiter;
+    use crate::{
+        module::{ExternalFuncInst, InternalMemoryInst, MemoryInst, PAGE_SIZE},
+        Exports, Importer, Runtime, Store, Value,
+    };
+    use anyhow::Result;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn shared_memory_limiter() -> Result<()> {
+        let code = r#"
+(module
+  (memory 1 2)
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let page = PAGE_SIZE as usize;
+        let limiter = Rc::new(RefCell::new(MemoryLimiter::new(3 * page)));
+
+        let mut first = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm)?;
+        let mut second = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm)?;
+        assert_eq!(limiter.borrow().used, 2 * page);
+
+        let result = first.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        let result = second.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(-1)));
+        assert_eq!(limiter.borrow().used, 3 * page);
+
+        // the initial memory is also limited
+        let result = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm);
+        assert!(result.is_err());
+
+        // the failed growth over the max of memory is given back
+        let limiter = Rc::new(RefCell::new(MemoryLimiter::new(10 * page)));
+        let mut runtime = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm)?;
+        let result = runtime.call("grow".into(), vec![Value::I32(2)])?;
+        assert_eq!(result, Some(Value::I32(-1)));
+        assert_eq!(limiter.borrow().used, page);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_limiter_multiple_memories() -> Result<()> {
+        let code = r#"
+(module
+  (memory 1)
+  (memory 2)
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let page = PAGE_SIZE as usize;
+        let limiter = Rc::new(RefCell::new(MemoryLimiter::new(2 * page)));
+
+        // the first memory is given back when the second one exceeds the limit
+        let result = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm);
+        assert!(result.is_err());
+        assert_eq!(limiter.borrow().used, 0);
+
+        let limiter = Rc::new(RefCell::new(MemoryLimiter::new(3 * page)));
+        Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm)?;
+        assert_eq!(limiter.borrow().used, 3 * page);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_limiter_imported_memory() -> Result<()> {
+        struct Env(MemoryInst);
+
+        impl Importer for Env {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                _store: Rc<RefCell<Store>>,
+                _func: ExternalFuncInst,
+                _args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                Ok(None)
+            }
+
+            fn resolve_memory(
+                &self,
+                _module: &str,
+                _field: &str,
+            ) -> Result<Option<Rc<RefCell<InternalMemoryInst>>>> {
+                Ok(Some(Rc::clone(&self.0)))
+            }
+        }
+
+        let page = PAGE_SIZE as usize;
+        let limiter = Rc::new(RefCell::new(MemoryLimiter::new(2 * page)));
+
+        let wasm = wat::parse_str(r#"(module (memory (export "memory") 2))"#)?;
+        let mut exporter = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm)?;
+        let Exports::Memory(memory) = exporter.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+        assert_eq!(limiter.borrow().used, 2 * page);
+
+        // the shared memory is charged only once
+        let wasm = wat::parse_str(r#"(module (import "env" "memory" (memory 2)))"#)?;
+        let importer = Runtime::builder()
+            .import(Env(memory))
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm)?;
+        assert_eq!(limiter.borrow().used, 2 * page);
+
+        drop(importer);
+        assert_eq!(limiter.borrow().used, 2 * page);
+        drop(exporter);
+        assert_eq!(limiter.borrow().used, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_limiter_release() -> Result<()> {
+        let code = r#"
+(module
+  (memory 1 2)
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let page = PAGE_SIZE as usize;
+        let limiter = Rc::new(RefCell::new(MemoryLimiter::new(2 * page)));
+
+        // the grown memory is also given back when the runtime is dropped
+        for _ in 0..3 {
+            let mut runtime = Runtime::builder()
+                .resource_limiter(limiter.clone())
+                .build_from_bytes(&wasm)?;
+            let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+            assert_eq!(result, Some(Value::I32(1)));
+            assert_eq!(limiter.borrow().used, 2 * page);
+
+            // the clone shares the memory, it's given back once
+            drop(runtime.clone());
+            assert_eq!(limiter.borrow().used, 2 * page);
+        }
+        assert_eq!(limiter.borrow().used, 0);
+
+        // the memory is given back when the start function traps
+        let code = r#"
+(module
+  (memory 1)
+  (func $start (unreachable))
+  (start $start)
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let result = Runtime::builder()
+            .resource_limiter(limiter.clone())
+            .build_from_bytes(&wasm);
+        assert!(result.is_err());
+        assert_eq!(limiter.borrow().used, 0);
+
+        Ok(())
+    }
+}
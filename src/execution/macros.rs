@@ -2,22 +2,25 @@
 macro_rules! load {
     ($stack: expr, $store: expr, $ty: ty, $arg: expr) => {{
         let store = $store.borrow();
+        let idx = $arg.memory as usize;
         let memory = store
             .memory
-            .get(0)
-            .with_context(|| Error::NotFoundMemory(0))?;
+            .get(idx)
+            .with_context(|| Error::NotFoundMemory(idx))?;
         let memory = memory.borrow();
-        let addr = $stack.pop1::<i32>()? as usize;
+        // NOTE: the address is unsigned, so it must not be sign-extended
+        let addr = $stack.pop1::<i32>()? as u32 as usize;
         let value = memory.load::<$ty>(addr, $arg)?;
         $stack.push(value.into());
     }};
     ($stack: expr, $store: expr, $ty: ty, $arg: expr, $tz: ty) => {{
-        let addr = $stack.pop1::<i32>()? as usize;
+        let addr = $stack.pop1::<i32>()? as u32 as usize;
         let store = $store.borrow();
+        let idx = $arg.memory as usize;
         let memory = store
             .memory
-            .get(0)
-            .with_context(|| Error::NotFoundMemory(0))?;
+            .get(idx)
+            .with_context(|| Error::NotFoundMemory(idx))?;
         let memory = memory.borrow();
         let value = memory.load::<$ty>(addr, $arg)? as $tz;
         $stack.push(value.into());
@@ -28,24 +31,26 @@ macro_rules! load {
 macro_rules! store {
     ($stack: expr, $store: expr, $ty: ty, $arg: expr) => {{
         let store = $store.borrow();
+        let idx = $arg.memory as usize;
         let memory = store
             .memory
-            .get(0)
-            .with_context(|| Error::NotFoundMemory(0))?;
+            .get(idx)
+            .with_context(|| Error::NotFoundMemory(idx))?;
         let mut memory = memory.borrow_mut();
         let value = $stack.pop1::<$ty>()?;
-        let addr = $stack.pop1::<i32>()? as usize;
+        let addr = $stack.pop1::<i32>()? as u32 as usize;
         memory.write(addr, $arg, value)?;
     }};
     ($stack: expr, $store: expr, $ty: ty, $arg: expr, $tz: ty) => {{
         let store = $store.borrow();
+        let idx = $arg.memory as usize;
         let memory = store
             .memory
-            .get(0)
-            .with_context(|| Error::NotFoundMemory(0))?;
+            .get(idx)
+            .with_context(|| Error::NotFoundMemory(idx))?;
         let mut memory = memory.borrow_mut();
         let value = $stack.pop1::<$ty>()? as $tz;
-        let addr = $stack.pop1::<i32>()? as usize;
+        let addr = $stack.pop1::<i32>()? as u32 as usize;
         memory.write(addr, $arg, value)?;
     }};
 }
@@ -104,9 +109,6 @@ macro_rules! funop {
                     Ok(-(*self))
                 }
                 fn sqrt(&self) -> Result<Self> {
-                    if (*self) == 0.0 {
-                        return Ok(0.0);
-                    }
                     Ok((*self).sqrt())
                 }
                 fn ceil(&self) -> Result<Self> {
@@ -118,18 +120,9 @@ macro_rules! funop {
                 fn trunc(&self) -> Result<Self> {
                     Ok((*self).trunc())
                 }
+                // NOTE: ties are rounded to even, and the sign of zero is kept
                 fn nearest(&self) -> Result<Self> {
-                    let abs = (*self).abs();
-                    if (0.0..=0.5).contains(&abs) {
-                        return Ok(0.0);
-                    }
-                    let rounded = (*self).round();
-                    let value = match rounded as i64 % 2 {
-                        r if r == 1 => self.floor().unwrap(),
-                        r if r == -1 => self.ceil().unwrap(),
-                        _ => rounded,
-                    };
-                    Ok(value)
+                    Ok((*self).round_ties_even())
                 }
             }
         )+
@@ -1,8 +1,11 @@
+pub mod builder;
+pub mod compiled;
 pub mod error;
 pub(crate) mod float;
 pub mod importer;
 pub(crate) mod indices;
 pub(crate) mod integer;
+pub mod limiter;
 mod macros;
 pub mod module;
 pub(crate) mod op;
@@ -10,7 +13,10 @@ pub mod runtime;
 pub mod store;
 pub mod value;
 
+pub use builder::*;
+pub use compiled::*;
 pub use importer::*;
+pub use limiter::*;
 pub use runtime::*;
 pub use store::*;
 pub use value::*;
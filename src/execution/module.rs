@@ -1,28 +1,73 @@
 use super::indices::TypeIdx;
+use super::store::Store;
 use super::value::{ExternalVal, Numeric, Value};
 use crate::binary::instruction::{Instruction, MemoryArg};
 use crate::binary::module::Module;
+use crate::binary::section::decode_instructions;
 use crate::binary::types::{FuncType, ValueType};
 use crate::execution::error::Error;
-use anyhow::{bail, Result};
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use anyhow::{bail, Context as _, Result};
+use std::cell::{OnceCell, RefCell};
+use std::rc::{Rc, Weak};
 
 // https://www.w3.org/TR/wasm-core-1/#memory-instances%E2%91%A0
 pub const PAGE_SIZE: u32 = 65536; // 64Ki
 
+// https://www.w3.org/TR/wasm-core-1/#memories%E2%91%A0
+pub const MAX_PAGES: u32 = 65536; // 4Gi in bytes
+
+// NOTE: the spec allows 2^32-1 elements, the table without max is capped to avoid huge allocations
+pub const MAX_TABLE_ELEMENTS: u32 = 10_000_000;
+
 #[derive(Debug, Clone)]
 pub struct Func {
     pub type_idx: TypeIdx,
     pub locals: Vec<ValueType>,
-    pub body: Vec<Instruction>,
+    pub body: Body,
+}
+
+// instructions of the function, shared with the frames of each call
+#[derive(Debug, Clone)]
+pub enum Body {
+    Decoded(Rc<[Instruction]>),
+    Lazy(Rc<LazyBody>), // decoded on the first call, shared by all instances
+}
+
+#[derive(Debug, Default)]
+pub struct LazyBody {
+    pub bytes: Vec<u8>,
+    pub insts: OnceCell<Rc<[Instruction]>>,
+}
+
+impl Body {
+    pub fn insts(&self) -> Result<Rc<[Instruction]>> {
+        match self {
+            Body::Decoded(insts) => Ok(Rc::clone(insts)),
+            Body::Lazy(lazy) => {
+                if let Some(insts) = lazy.insts.get() {
+                    return Ok(Rc::clone(insts));
+                }
+                let insts = decode_instructions(&lazy.bytes)?.into();
+                Ok(Rc::clone(lazy.insts.get_or_init(|| insts)))
+            }
+        }
+    }
+
+    // instructions which are already decoded, nothing is decoded
+    pub fn decoded(&self) -> Option<&Rc<[Instruction]>> {
+        match self {
+            Body::Decoded(insts) => Some(insts),
+            Body::Lazy(lazy) => lazy.insts.get(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct InternalFuncInst {
     pub func_type: FuncType,
     pub code: Func,
+    pub store_id: usize, // id of the store which defines the function
+    pub store: Weak<RefCell<Store>>, // the store which defines the function, set on instantiation
 }
 
 #[derive(Debug, Clone)]
@@ -38,6 +83,15 @@ pub enum FuncInst {
     External(ExternalFuncInst),
 }
 
+impl FuncInst {
+    pub fn func_type(&self) -> &FuncType {
+        match self {
+            Self::Internal(func) => &func.func_type,
+            Self::External(func) => &func.func_type,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct InternalTableInst {
     pub funcs: Vec<Option<FuncInst>>,
@@ -45,6 +99,23 @@ pub struct InternalTableInst {
 }
 pub type TableInst = Rc<RefCell<InternalTableInst>>;
 
+impl InternalTableInst {
+    // https://webassembly.github.io/spec/core/exec/modules.html#grow-table
+    pub fn grow(&mut self, grow_size: u32, init: Option<FuncInst>) -> Result<()> {
+        let size = self.funcs.len() as u32;
+        let max = self.max.unwrap_or(MAX_TABLE_ELEMENTS);
+        let len = match size.checked_add(grow_size) {
+            Some(len) if len <= max => len,
+            _ => bail!(Error::TableSizeOverflow(max, size.saturating_add(grow_size))),
+        };
+        if self.funcs.try_reserve(grow_size as usize).is_err() {
+            bail!(Error::TableAllocationFailed(len));
+        }
+        self.funcs.resize(len as usize, init);
+        Ok(())
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct InternalMemoryInst {
     pub data: Vec<u8>,
@@ -59,29 +130,38 @@ impl InternalMemoryInst {
 
     // https://www.w3.org/TR/wasm-core-1/#grow-mem
     pub fn grow(&mut self, grow_size: u32) -> Result<()> {
-        let size = self.size() as u32;
-        if size % PAGE_SIZE != 0 {
+        if self.data.len() % PAGE_SIZE as usize != 0 {
             bail!(Error::MemorySizeNotPageAligned(PAGE_SIZE));
         }
-        let len = size + grow_size;
-        if let Some(max) = self.max {
-            if max < len {
-                bail!(Error::MemoryPageOverflow(max, len));
-            }
+        // NOTE: nothing to do, avoid reallocation
+        if grow_size == 0 {
+            return Ok(());
         }
-        self.data.resize((len * PAGE_SIZE) as usize, 0);
+
+        let size = self.size() as u32;
+        let max = self.max.unwrap_or(MAX_PAGES).min(MAX_PAGES);
+        let len = match size.checked_add(grow_size) {
+            Some(len) if len <= max => len,
+            _ => bail!(Error::MemoryPageOverflow(max, size.saturating_add(grow_size))),
+        };
+        // NOTE: the new pages are zero-filled, even if the spare capacity has stale bytes
+        self.data.resize(len as usize * PAGE_SIZE as usize, 0);
         Ok(())
     }
 
     pub fn load<T: Numeric>(&self, addr: usize, arg: &MemoryArg) -> Result<T> {
-        // TODO: check align and memory size
-        let at = addr + arg.offset as usize;
+        // TODO: check align
+        let at = addr
+            .checked_add(arg.offset as usize)
+            .with_context(|| Error::OutOfBoundsMemoryAccess)?;
         Numeric::read(&self.data, at)
     }
 
     pub fn write<T: Numeric>(&mut self, addr: usize, arg: &MemoryArg, value: T) -> Result<()> {
-        // TODO: check align and memory size
-        let at = addr + arg.offset as usize;
+        // TODO: check align
+        let at = addr
+            .checked_add(arg.offset as usize)
+            .with_context(|| Error::OutOfBoundsMemoryAccess)?;
         Numeric::write(&mut self.data, at, value)
     }
 
@@ -109,7 +189,7 @@ pub struct ExportInst {
 #[derive(Debug, Default, Clone)]
 pub struct ModuleInst {
     pub func_types: Vec<FuncType>,
-    pub exports: HashMap<String, ExportInst>,
+    pub exports: Vec<ExportInst>, // in the order of the export section
 }
 
 impl ModuleInst {
@@ -128,7 +208,7 @@ impl ModuleInst {
         };
 
         // exports
-        let mut exports = HashMap::default();
+        let mut exports = vec![];
         if let Some(ref sections) = module.export_section {
             for export in sections {
                 let desc = match export.desc {
@@ -137,12 +217,11 @@ impl ModuleInst {
                     crate::binary::types::ExportDesc::Memory(idx) => ExternalVal::Memory(idx),
                     crate::binary::types::ExportDesc::Global(idx) => ExternalVal::Global(idx),
                 };
-                let name = export.name.clone();
                 let export_inst = ExportInst {
-                    name: name.clone(),
+                    name: export.name.clone(),
                     desc,
                 };
-                exports.insert(name, export_inst);
+                exports.push(export_inst);
             }
         };
 
@@ -151,4 +230,9 @@ impl ModuleInst {
             exports,
         }
     }
+
+    // NOTE: the first defined export wins when the names are duplicated
+    pub fn export(&self, name: &str) -> Option<&ExportInst> {
+        self.exports.iter().find(|export| export.name == name)
+    }
 }
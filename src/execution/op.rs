@@ -1,12 +1,11 @@
 use super::{
-    module::{ExternalFuncInst, InternalFuncInst},
+    module::{ExternalFuncInst, FuncInst, InternalFuncInst},
     store::Store,
     value::{Frame, Label, LabelKind, StackAccess, Value},
 };
 use crate::{
-    binary::{instruction::Instruction, types::ValueType},
-    execution::error::Error,
-    impl_binary_operation, impl_cvtop_operation, impl_unary_operation,
+    binary::instruction::Instruction, execution::error::Error, impl_binary_operation,
+    impl_cvtop_operation, impl_unary_operation,
 };
 use anyhow::{bail, Context as _, Result};
 use log::trace;
@@ -20,18 +19,17 @@ pub fn local_get(locals: &[Value], stack: &mut impl StackAccess, idx: usize) ->
     Ok(())
 }
 
-pub fn local_set(locals: &mut Vec<Value>, stack: &mut impl StackAccess, idx: usize) -> Result<()> {
+pub fn local_set(locals: &mut [Value], stack: &mut impl StackAccess, idx: usize) -> Result<()> {
     let value: Value = stack.pop1()?;
-    if locals.len() <= idx {
-        for _ in 0..(idx + 1) - locals.len() {
-            locals.push(0.into());
-        }
-    }
-    locals[idx] = value;
+    // NOTE: all locals are allocated with the typed zero on push_frame
+    let local = locals
+        .get_mut(idx)
+        .with_context(|| Error::NotFoundLocalVariable(idx))?;
+    *local = value;
     Ok(())
 }
 
-pub fn local_tee(locals: &mut Vec<Value>, stack: &mut impl StackAccess, idx: usize) -> Result<()> {
+pub fn local_tee(locals: &mut [Value], stack: &mut impl StackAccess, idx: usize) -> Result<()> {
     let value: Value = stack.pop1()?;
     stack.push(value.clone());
     stack.push(value);
@@ -142,46 +140,53 @@ pub fn get_else_or_end_address(insts: &[Instruction], pc: isize) -> Result<usize
     }
 }
 
-pub fn push_frame(stack: &mut Vec<Value>, call_stack: &mut Vec<Frame>, func: &InternalFuncInst) {
+pub fn push_frame(
+    stack: &mut Vec<Value>,
+    call_stack: &mut Vec<Frame>,
+    func: &InternalFuncInst,
+) -> Result<()> {
     let arity = func.func_type.results.len();
     let bottom = stack.len() - func.func_type.params.len();
     let mut locals = stack.split_off(bottom);
 
-    for local in func.code.locals.iter() {
-        match local {
-            ValueType::I32 => locals.push(Value::I32(0)),
-            ValueType::I64 => locals.push(Value::I64(0)),
-            ValueType::F32 => locals.push(Value::F32(0.0)),
-            ValueType::F64 => locals.push(Value::F64(0.0)),
-        }
-    }
+    locals.extend(func.code.locals.iter().map(Value::zero));
 
     let sp = stack.len();
     let frame = Frame {
         pc: -1,
         sp,
-        insts: func.code.body.clone(),
+        insts: func.code.body.insts()?,
         arity,
         locals,
         labels: vec![],
     };
     trace!("call internal function: {:?}", &frame);
     call_stack.push(frame);
+    Ok(())
 }
 
 pub fn stack_unwind(stack: &mut Vec<Value>, sp: usize, arity: usize) -> Result<()> {
-    if arity > 0 {
-        let value = stack.pop().with_context(|| Error::StackPopError)?;
-        stack.drain(sp..);
-        stack.push(value);
-    } else {
-        stack.drain(sp..);
-    }
+    let bottom = stack
+        .len()
+        .checked_sub(arity)
+        .filter(|bottom| *bottom >= sp)
+        .with_context(|| Error::StackPopError)?;
+    // NOTE: keep the result values on the top of stack
+    stack.drain(sp..bottom);
     Ok(())
 }
 
-pub fn br(labels: &mut Vec<Label>, stack: &mut Vec<Value>, level: &u32) -> Result<isize> {
-    let label_index = labels.len() - 1 - (*level as usize);
+// NOTE: returns `None` when the branch targets the implicit label of the function body,
+// the caller must return from the function in that case
+pub fn br(labels: &mut Vec<Label>, stack: &mut Vec<Value>, level: &u32) -> Result<Option<isize>> {
+    let level = *level as usize;
+    if level == labels.len() {
+        return Ok(None);
+    }
+    let label_index = labels
+        .len()
+        .checked_sub(level + 1)
+        .with_context(|| Error::NotFoundLabel(level))?;
     let Label {
         pc,
         start,
@@ -205,7 +210,17 @@ pub fn br(labels: &mut Vec<Label>, stack: &mut Vec<Value>, level: &u32) -> Resul
         stack_unwind(stack, sp, arity)?;
         pc as isize
     };
-    Ok(pc)
+    Ok(Some(pc))
+}
+
+// leave the current frame keeping the results on the top of stack
+pub fn func_return(stack: &mut Vec<Value>, call_stack: &mut Vec<Frame>, inst: &str) -> Result<()> {
+    let frame = call_stack
+        .pop()
+        .with_context(|| Error::CallStackPopError(inst.into()))?;
+    trace!("frame in the {} instruction", inst);
+    let Frame { sp, arity, .. } = frame;
+    stack_unwind(stack, sp, arity)
 }
 
 fn is_wasi_modules(module: &str) -> bool {
@@ -229,32 +244,136 @@ pub fn invoke_external(
         store.clone()
     } else {
         let brrowed = store.borrow();
-        let Some(imports) = brrowed.imports.as_ref() else {
-            bail!(Error::NoImports);
-        };
+        let import = brrowed.importer(&func.module)?;
+
+        // NOTE: host functions don't have their own store, so we use current store
+        match import.get(&func.module)? {
+            Some(module_store) => module_store,
+            None => store.clone(),
+        }
+    };
 
-        let import = imports
-            .get(&func.module)
-            .with_context(|| Error::NotFoundImportModule(func.module.clone()))?;
+    let importer = store.borrow().importer(&func.module)?;
 
-        let Some(module_store) = import.get(&func.module)? else {
-            bail!(Error::NotFoundImportModule(func.module.clone()));
-        };
+    // NOTE: the error of host function is propagated as a trap without any change,
+    // so that the caller can get the original message and downcast it
+    importer.invoke(module_store, func, args)
+}
 
-        module_store.clone()
+// switch to the store of the module which defines the function of the top frame,
+// the function of another module is called through the shared table on the same call stack
+// NOTE: the caller's store is kept with the height of the frame, and restored when it returns
+pub fn enter_foreign_store(
+    store: &mut Rc<RefCell<Store>>,
+    store_stack: &mut Vec<(usize, Rc<RefCell<Store>>)>,
+    call_stack: &[Frame],
+    func: &InternalFuncInst,
+) -> Result<()> {
+    let foreign = func
+        .store
+        .upgrade()
+        .with_context(|| Error::NotFoundStore(func.store_id))?;
+    let height = call_stack
+        .len()
+        .checked_sub(1)
+        .with_context(|| Error::CallStackPopError("call_indirect".into()))?;
+    // NOTE: the function tail called by the foreign frame returns to the same caller
+    if store_stack.last().is_some_and(|(h, _)| *h == height) {
+        *store = foreign;
+    } else {
+        let caller = std::mem::replace(store, foreign);
+        store_stack.push((height, caller));
+    }
+    Ok(())
+}
+
+// resolve the function of call_indirect, and check its signature
+pub fn get_indirect_func(
+    store: &Store,
+    elem_idx: usize,
+    signature_idx: usize,
+    table_idx: usize,
+) -> Result<FuncInst> {
+    let table = store
+        .tables
+        .get(table_idx)
+        .with_context(|| Error::NotFoundTable(table_idx))?;
+    let func = table
+        .borrow()
+        .funcs
+        .get(elem_idx)
+        .with_context(|| Error::UndefinedElement)?
+        .clone()
+        .with_context(|| Error::UninitializedElement(elem_idx))?;
+
+    // validate expect func signature and actual func signature
+    let expect_func_type = store
+        .module
+        .func_types
+        .get(signature_idx)
+        .with_context(|| Error::NotFoundFuncType(signature_idx))?;
+
+    let func_type = match func {
+        FuncInst::Internal(ref func) => &func.func_type,
+        FuncInst::External(ref func) => &func.func_type,
     };
 
-    let store_for_invoke = module_store.clone();
+    if func_type.params != expect_func_type.params || func_type.results != expect_func_type.results
+    {
+        trace!(
+            "expect func signature: {:?}, actual func signature: {:?}",
+            expect_func_type,
+            func_type
+        );
+        bail!(Error::TypeMismatchIndirectCall)
+    }
+
+    Ok(func)
+}
+
+// https://github.com/WebAssembly/tail-call/blob/main/proposals/tail-call/Overview.md
+pub fn return_call(
+    store: Rc<RefCell<Store>>,
+    stack: &mut Vec<Value>,
+    call_stack: &mut Vec<Frame>,
+    func: FuncInst,
+) -> Result<()> {
+    let frame = call_stack
+        .pop()
+        .with_context(|| Error::CallStackPopError("return_call".into()))?;
+    let Frame { sp, arity, .. } = frame;
 
-    let module_store = store.borrow();
-    let importer = module_store
-        .imports
-        .as_ref()
-        .with_context(|| Error::NoImports)?
-        .get(&func.module)
-        .with_context(|| Error::NotFoundImportModule(func.module.clone()))?;
+    // NOTE: discard the operands of the current frame except for the arguments,
+    // the locals are dropped with the frame
+    let params = match func {
+        FuncInst::Internal(ref func) => func.func_type.params.len(),
+        FuncInst::External(ref func) => func.func_type.params.len(),
+    };
+    let bottom = stack
+        .len()
+        .checked_sub(params)
+        .filter(|bottom| *bottom >= sp)
+        .with_context(|| Error::StackPopError)?;
+    stack.drain(sp..bottom);
 
-    importer.invoke(store_for_invoke, func, args)
+    match func {
+        FuncInst::Internal(ref func) => {
+            push_frame(stack, call_stack, func)?;
+            debug_assert_eq!(
+                stack.len(),
+                sp,
+                "operand stack height must be the same as the caller frame after tail call"
+            );
+        }
+        FuncInst::External(func) => {
+            let result = invoke_external(store, stack, func)?;
+            if let Some(value) = result {
+                stack.push(value);
+            }
+            stack_unwind(stack, sp, arity)?;
+        }
+    }
+    Ok(())
 }
 
 impl_unary_operation!(
@@ -293,7 +412,7 @@ impl_cvtop_operation!(
     f64_convert_i32_u,
     f64_convert_i64_s,
     f64_convert_i64_u,
-    f64_demote_f32,
+    f64_promote_f32,
     i32_reinterpret_f32,
     i64_reinterpret_f64,
     f32_reinterpret_i32,
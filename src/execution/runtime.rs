@@ -1,14 +1,21 @@
-use super::module::{FuncInst, InternalFuncInst};
+use super::builder::RuntimeBuilder;
+use super::limiter::{MemoryCharge, SharedResourceLimiter};
+use super::module::{FuncInst, InternalFuncInst, InternalMemoryInst, PAGE_SIZE};
 use super::op::*;
-use super::store::{Exports, Store};
+use super::store::{bind_store, Exports, Store};
 use super::value::{ExternalVal, Frame, Label, StackAccess, Value};
-use crate::binary::instruction::*;
+use crate::binary::{
+    instruction::*,
+    types::{FuncType, ValueType},
+    wat::{func_to_wat, value_type},
+};
 use crate::execution::error::Error;
 use crate::execution::value::LabelKind;
 use crate::{load, store, Importer};
 use anyhow::{bail, Context as _, Result};
 use log::{error, trace};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::io::Read;
 use std::rc::Rc;
 
@@ -17,8 +24,79 @@ pub struct Runtime {
     pub store: Rc<RefCell<Store>>,
     pub stack: Vec<Value>,
     pub call_stack: Vec<Frame>,
+    pub store_stack: Vec<(usize, Rc<RefCell<Store>>)>, // stores of the callers of foreign frames
+    pub fuel: Option<u64>,      // remaining fuel, each instruction consumes 1
+    pub call_fuel: Option<u64>, // remaining fuel of the current call_with_limit
+    pub max_call_depth: Option<usize>, // max length of call stack
+    pub max_stack_size: Option<usize>, // max length of operand stack, or the default
+    pub max_memory_pages: Option<u32>, // max pages of each memory
+    pub max_table_elements: Option<u32>, // max elements of each table
+    pub memory_grow_hook: Option<MemoryGrowHook>,
+    pub trap_hook: Option<TrapHook>,
+    pub resource_limiter: Option<SharedResourceLimiter>,
+    pub stats: Option<CallStats>, // collected only while this is set, see call_with_stats
+    pub last_trap: Option<TrapLocation>, // where the last call was trapped, also attached to the error
+    pub(crate) memory_charge: Option<Rc<MemoryCharge>>, // given back when the last clone is dropped
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunState {
+    Paused,           // the instructions are used up, call run_for again to resume
+    Done(Vec<Value>), // the function returned with the results
+}
+
+// location of the instruction which is executed at the trap
+// NOTE: the offset is the index of the instruction in the function body, not the byte offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapLocation {
+    pub func_idx: usize,
+    pub offset: usize,
+}
+
+// attached to the error of the trapped call, can be recovered by `err.downcast_ref::<Trapped>()`
+// NOTE: it's displayed as the message of the error, so that the message is not changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trapped {
+    pub location: TrapLocation,
+    message: String,
+}
+
+impl std::fmt::Display for Trapped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// trap reported to the trap hook, before the error is returned to the caller
+pub struct Trap<'a> {
+    pub error: &'a anyhow::Error,
+    pub location: Option<TrapLocation>,
+}
+
+impl Trap<'_> {
+    // kind of the trap, None if the error is raised by the host function
+    pub fn kind(&self) -> Option<&Error> {
+        self.error.downcast_ref::<Error>()
+    }
+}
+
+// statistics of the execution, reported by call_with_stats
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CallStats {
+    pub instructions: u64,
+    pub max_stack_size: usize, // max length of operand stack
+    pub max_call_depth: usize, // max length of call stack
+}
+
+// default max length of operand stack, 16MiB since each value takes 16 bytes
+pub const DEFAULT_MAX_STACK_SIZE: usize = 1024 * 1024;
+
+// called with the old and new page counts after memory.grow succeeded
+pub type MemoryGrowHook = Rc<RefCell<Box<dyn FnMut(u32, u32)>>>;
+
+// called whenever a call is trapped
+pub type TrapHook = Rc<RefCell<Box<dyn FnMut(&Trap)>>>;
+
 impl Runtime {
     pub fn from_file(file: &str, imports: Option<Vec<Box<dyn Importer>>>) -> Result<Self> {
         let store = Store::from_file(file, imports)?;
@@ -43,46 +121,174 @@ impl Runtime {
 
     // https://www.w3.org/TR/wasm-core-1/#instantiation%E2%91%A1
     pub fn instantiate(store: Rc<RefCell<Store>>) -> Result<Self> {
-        let start = store.borrow().start;
-        let mut runtime = Self {
+        bind_store(&store);
+        Self::with_store(store).start()
+    }
+
+    // runtime for the store which is already instantiated, the start function is not executed.
+    // host functions can use this to call back into the guest
+    pub fn with_store(store: Rc<RefCell<Store>>) -> Self {
+        Self {
             store,
             ..Default::default()
-        };
+        }
+    }
+
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::default()
+    }
+
+    pub fn on_memory_grow(&mut self, hook: Box<dyn FnMut(u32, u32)>) {
+        self.memory_grow_hook = Some(Rc::new(RefCell::new(hook)));
+    }
+
+    pub fn on_trap(&mut self, hook: Box<dyn FnMut(&Trap)>) {
+        self.trap_hook = Some(Rc::new(RefCell::new(hook)));
+    }
+
+    // table.grow fails beyond this, even if the module declares the larger max
+    pub fn set_max_table_elements(&mut self, max: u32) {
+        self.max_table_elements = Some(max);
+    }
 
-        // https://www.w3.org/TR/wasm-core-1/#start-function%E2%91%A1
+    // https://www.w3.org/TR/wasm-core-1/#start-function%E2%91%A1
+    pub(crate) fn start(mut self) -> Result<Self> {
+        let start = self.store.borrow().start;
         if let Some(idx) = start {
-            let result = runtime.call_start(idx as usize, vec![])?;
+            let result = self.call_start(idx as usize, vec![])?;
             if let Some(out) = result {
-                runtime.stack.push(out);
+                self.stack.push(out);
             }
         }
 
-        Ok(runtime)
+        Ok(self)
     }
 
     // execute function by name
+    // NOTE: returns only the last value if the function has multiple results,
+    // use call_multi to get all of them
     pub fn call(&mut self, name: String, args: Vec<Value>) -> Result<Option<Value>> {
+        Ok(self.call_multi(name, args)?.pop())
+    }
+
+    // execute function by name, traps if the call executes more than max_instructions
+    // NOTE: the limit is independent of the global fuel, both are consumed
+    pub fn call_with_limit(
+        &mut self,
+        name: String,
+        args: Vec<Value>,
+        max_instructions: u64,
+    ) -> Result<Option<Value>> {
+        let prev = self.call_fuel.replace(max_instructions);
+        let result = self.call(name, args);
+        self.call_fuel = prev;
+        result
+    }
+
+    // call every exported function with zero values, useful for smoke testing a module
+    // NOTE: every supported value type has a zero value, so no function is skipped
+    pub fn call_all_exports_with_zeros(&mut self) -> BTreeMap<String, Result<Vec<Value>>> {
+        let funcs: Vec<_> = self
+            .store
+            .borrow()
+            .module
+            .exports
+            .iter()
+            .filter_map(|export| match export.desc {
+                ExternalVal::Func(idx) => Some((export.name.clone(), idx as usize)),
+                _ => None,
+            })
+            .collect();
+
+        let mut results = BTreeMap::new();
+        for (name, idx) in funcs {
+            let result = self.get_func_by_idx(idx).and_then(|func| {
+                let args = func.func_type().params.iter().map(Value::zero).collect();
+                self.call_multi(name.clone(), args)
+            });
+            results.insert(name, result);
+        }
+        results
+    }
+
+    // execute function by name, and returns the statistics of the call together
+    pub fn call_with_stats(
+        &mut self,
+        name: String,
+        args: Vec<Value>,
+    ) -> Result<(Option<Value>, CallStats)> {
+        let prev = self.stats.replace(CallStats::default());
+        let result = self.call(name, args);
+        let stats = std::mem::replace(&mut self.stats, prev).unwrap_or_default();
+        Ok((result?, stats))
+    }
+
+    // execute function by name, and returns all of the results
+    pub fn call_multi(&mut self, name: String, args: Vec<Value>) -> Result<Vec<Value>> {
         trace!("call function: {}", name);
-        for arg in args {
-            self.stack.push(arg);
+        let idx = self.exported_func_idx(name)?;
+        check_args(self.get_func_by_idx(idx)?.func_type(), &args)?;
+        self.stack.extend(args);
+        self.invoke(idx)
+    }
+
+    // execute function by index, useful for functions which are not exported
+    // NOTE: imported functions come first in the function index space
+    pub fn invoke_by_index(&mut self, idx: usize, args: Vec<Value>) -> Result<Vec<Value>> {
+        trace!("call function by index: {}", idx);
+        check_args(self.get_func_by_idx(idx)?.func_type(), &args)?;
+        self.stack.extend(args);
+        self.invoke(idx)
+    }
+
+    // push the frame of exported function without executing it, use step to run it
+    pub fn prepare_call(&mut self, name: String, args: Vec<Value>) -> Result<()> {
+        let idx = self.exported_func_idx(name)?;
+        let FuncInst::Internal(func) = self.get_func_by_idx(idx)? else {
+            bail!("cannot step into external function: {}", idx);
+        };
+        check_args(&func.func_type, &args)?;
+        self.stack.extend(args);
+        check_call_depth(&self.call_stack, self.max_call_depth)?;
+        push_frame(&mut self.stack, &mut self.call_stack, &func)?;
+        Ok(())
+    }
+
+    // run the call prepared by prepare_call for at most n instructions,
+    // executors can interleave many runtimes by calling this until it's done
+    pub fn run_for(&mut self, n: u64) -> Result<RunState> {
+        for _ in 0..n {
+            match self.step() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => return Err(self.trapped(e)),
+            }
+        }
+        if self.call_stack.is_empty() {
+            Ok(RunState::Done(std::mem::take(&mut self.stack)))
+        } else {
+            Ok(RunState::Paused)
         }
+    }
 
-        let idx = {
-            let store = self.store.borrow();
-            let export_inst = store
-                .module
-                .exports
-                .get(&name)
-                .with_context(|| Error::NotFoundExportInstance(name))?;
-            let external_val = &export_inst.desc;
+    // operand stack of the current execution, values are in the order of push
+    pub fn stack_values(&self) -> &[Value] {
+        &self.stack
+    }
 
-            let ExternalVal::Func(idx) = external_val else {
-                bail!("invalid export desc: {:?}", external_val);
-            };
+    fn exported_func_idx(&self, name: String) -> Result<usize> {
+        let store = self.store.borrow();
+        let export_inst = store
+            .module
+            .export(&name)
+            .with_context(|| Error::NotFoundExportInstance(name))?;
+        let external_val = &export_inst.desc;
 
-            *idx as usize
+        let ExternalVal::Func(idx) = external_val else {
+            bail!("invalid export desc: {:?}", external_val);
         };
-        self.invoke(idx)
+
+        Ok(*idx as usize)
     }
 
     // execute function when module has start section
@@ -90,7 +296,7 @@ impl Runtime {
         for arg in args {
             self.stack.push(arg);
         }
-        self.invoke(idx)
+        Ok(self.invoke(idx)?.pop())
     }
 
     // get exported instances by name, like table, memory, global
@@ -98,8 +304,7 @@ impl Runtime {
         let store = self.store.borrow();
         let export_inst = store
             .module
-            .exports
-            .get(&name)
+            .export(&name)
             .with_context(|| Error::NotFoundExportInstance(name))?;
 
         let exports = match export_inst.desc {
@@ -136,44 +341,143 @@ impl Runtime {
         Ok(exports)
     }
 
-    fn invoke_internal(&mut self, func: InternalFuncInst) -> Result<Option<Value>> {
-        let arity = func.func_type.results.len();
+    // write the value of exported mutable global
+    pub fn set_global(&mut self, name: String, value: Value) -> Result<()> {
+        let Exports::Global(global) = self.exports(name.clone())? else {
+            bail!("invalid export desc: {} is not a global", name);
+        };
+        let mut global = global.borrow_mut();
+        if !global.mutability {
+            bail!(Error::ImmutableGlobal(name));
+        }
+        let value_type = global.value.type_of();
+        if value.type_of() != value_type {
+            bail!(Error::TypeMismatchGlobal(value_type, value.type_of()));
+        }
+        global.value = value;
+        Ok(())
+    }
 
-        push_frame(&mut self.stack, &mut self.call_stack, &func);
+    // install the function reference into the table, or clear the entry with the null reference
+    pub fn table_set(&mut self, table_idx: usize, entry_idx: usize, value: Value) -> Result<()> {
+        let store = self.store.borrow();
+        let func = func_ref(&store, value)?;
+        let mut table = store
+            .tables
+            .get(table_idx)
+            .with_context(|| Error::NotFoundTable(table_idx))?
+            .borrow_mut();
+        let entry = table
+            .funcs
+            .get_mut(entry_idx)
+            .with_context(|| Error::OutOfBoundsTableAccess)?;
+        *entry = func;
+        Ok(())
+    }
 
-        self.execute()?;
+    // print exported function as WAT
+    pub fn func_to_wat(&self, name: String) -> Result<String> {
+        let store = self.store.borrow();
+        let export_inst = store
+            .module
+            .export(&name)
+            .with_context(|| Error::NotFoundExportInstance(name.clone()))?;
 
-        let result = if arity > 0 {
-            // NOTE: only returns one value now
-            let value: Value = self.stack.pop1()?;
-            Some(value)
-        } else {
-            None
+        let ExternalVal::Func(idx) = export_inst.desc else {
+            bail!("invalid export desc: {:?}", export_inst.desc);
+        };
+        let func = store
+            .funcs
+            .get(idx as usize)
+            .with_context(|| Error::NotFoundExportedFunction(idx))?;
+        let FuncInst::Internal(func) = func else {
+            bail!("cannot print imported function: {}", name);
         };
 
-        Ok(result)
+        let body = func.code.body.insts()?;
+        let wat = func_to_wat(&name, &func.func_type, &func.code.locals, &body);
+        Ok(wat)
+    }
+
+    fn invoke_internal(&mut self, func: InternalFuncInst) -> Result<Vec<Value>> {
+        let arity = func.func_type.results.len();
+
+        check_call_depth(&self.call_stack, self.max_call_depth)?;
+        push_frame(&mut self.stack, &mut self.call_stack, &func)?;
+
+        self.execute()?;
+
+        let bottom = self
+            .stack
+            .len()
+            .checked_sub(arity)
+            .with_context(|| Error::StackPopError)?;
+        Ok(self.stack.split_off(bottom))
     }
 
     // https://www.w3.org/TR/wasm-core-1/#exec-invoke
-    fn invoke(&mut self, idx: usize) -> Result<Option<Value>> {
+    fn invoke(&mut self, idx: usize) -> Result<Vec<Value>> {
         let func = self.get_func_by_idx(idx)?;
         let result = match func {
             FuncInst::Internal(func) => self.invoke_internal(func),
             FuncInst::External(func) => {
                 let stack = &mut self.stack;
                 invoke_external(Rc::clone(&self.store), stack, func)
+                    .map(|value| value.into_iter().collect())
             }
         };
         match result {
-            Ok(value) => Ok(value),
-            Err(e) => {
-                self.stack = vec![]; // when traped, need to cleanup stack
-                self.call_stack = vec![];
-                Err(e)
+            Ok(values) => {
+                self.last_trap = None;
+                Ok(values)
+            }
+            Err(e) => Err(self.trapped(e)),
+        }
+    }
+
+    fn trapped(&mut self, error: anyhow::Error) -> anyhow::Error {
+        self.last_trap = self.current_location();
+        if let Some(hook) = &self.trap_hook {
+            let location = self.last_trap;
+            (hook.borrow_mut())(&Trap {
+                error: &error,
+                location,
+            });
+        }
+        self.stack = vec![]; // when traped, need to cleanup stack
+        self.call_stack = vec![];
+        // NOTE: the store of the outermost caller is restored
+        if let Some((_, caller)) = self.store_stack.drain(..).next() {
+            self.store = caller;
+        }
+        match self.last_trap {
+            Some(location) => {
+                let message = error.to_string();
+                error.context(Trapped { location, message })
             }
+            None => error,
         }
     }
 
+    // location of the instruction which is executed in the current frame
+    pub fn current_location(&self) -> Option<TrapLocation> {
+        let frame = self.call_stack.last()?;
+        let store = self.store.borrow();
+        // NOTE: each function in the store has its own body, so it can be found by the pointer
+        let func_idx = store.funcs.iter().position(|func| match func {
+            FuncInst::Internal(func) => func
+                .code
+                .body
+                .decoded()
+                .is_some_and(|body| Rc::ptr_eq(body, &frame.insts)),
+            FuncInst::External(_) => false,
+        })?;
+        Some(TrapLocation {
+            func_idx,
+            offset: frame.pc.max(0) as usize,
+        })
+    }
+
     fn get_func_by_idx(&mut self, idx: usize) -> Result<FuncInst> {
         let store = self.store.borrow();
         let func = store
@@ -184,426 +488,663 @@ impl Runtime {
     }
 
     fn execute(&mut self) -> Result<()> {
-        let stack = &mut self.stack;
+        while self.step()? {}
+        Ok(())
+    }
 
-        loop {
-            let Some(frame) = self.call_stack.last_mut() else {
-                trace!("call stack is empty, return");
-                break;
-            };
-            let insts = &frame.insts;
-            frame.pc += 1;
-            let Some(inst) = insts.get(frame.pc as usize) else {
-                trace!("reach the end of function");
-                break;
-            };
-            trace!("pc: {}, inst: {:?}", frame.pc, &inst);
-            match inst {
-                Instruction::Unreachable => bail!("unreachable"),
-                Instruction::Nop => {}
-                Instruction::LocalGet(idx) => {
-                    local_get(&frame.locals, stack, *idx as usize)?;
-                }
-                Instruction::LocalSet(idx) => {
-                    local_set(&mut frame.locals, stack, *idx as usize)?;
-                }
-                Instruction::LocalTee(idx) => {
-                    local_tee(&mut frame.locals, stack, *idx as usize)?;
-                }
-                Instruction::GlobalGet(idx) => {
-                    global_get(&mut self.store.borrow_mut(), stack, *idx as usize)?
-                }
-                Instruction::GlobalSet(idx) => {
-                    global_set(&mut self.store.borrow_mut(), stack, *idx as usize)?
-                }
-                Instruction::I32Add | Instruction::I64Add => add(stack)?,
-                Instruction::I32Sub | Instruction::I64Sub => sub(stack)?,
-                Instruction::I32Mul | Instruction::I64Mul => mul(stack)?,
-                Instruction::I32Clz | Instruction::I64Clz => clz(stack)?,
-                Instruction::I32Ctz | Instruction::I64Ctz => ctz(stack)?,
-                Instruction::I32DivU | Instruction::I64DivU => div_u(stack)?,
-                Instruction::I32DivS | Instruction::I64DivS => div_s(stack)?,
-                Instruction::I32Eq | Instruction::I64Eq => equal(stack)?,
-                Instruction::I32Eqz | Instruction::I64Eqz => eqz(stack)?,
-                Instruction::I32Ne | Instruction::I64Ne => not_equal(stack)?,
-                Instruction::I32LtS | Instruction::I64LtS => lt_s(stack)?,
-                Instruction::I32LtU | Instruction::I64LtU => lt_u(stack)?,
-                Instruction::I32GtS | Instruction::I64GtS => gt_s(stack)?,
-                Instruction::I32GtU | Instruction::I64GtU => gt_u(stack)?,
-                Instruction::I32LeS | Instruction::I64LeS => le_s(stack)?,
-                Instruction::I32LeU | Instruction::I64LeU => le_u(stack)?,
-                Instruction::I32GeS | Instruction::I64GeS => ge_s(stack)?,
-                Instruction::I32GeU | Instruction::I64GeU => ge_u(stack)?,
-                Instruction::I32Popcnt | Instruction::I64Popcnt => popcnt(stack)?,
-                Instruction::I32RemU | Instruction::I64RemU => rem_u(stack)?,
-                Instruction::I32RemS | Instruction::I64RemS => rem_s(stack)?,
-                Instruction::I32And | Instruction::I64And => and(stack)?,
-                Instruction::I32Or | Instruction::I64Or => or(stack)?,
-                Instruction::I32Xor | Instruction::I64Xor => xor(stack)?,
-                Instruction::I32ShL | Instruction::I64ShL => shl(stack)?,
-                Instruction::I32ShrU | Instruction::I64ShrU => shr_u(stack)?,
-                Instruction::I32ShrS | Instruction::I64ShrS => shr_s(stack)?,
-                Instruction::I32RtoL | Instruction::I64RtoL => rotl(stack)?,
-                Instruction::I32RtoR | Instruction::I64RtoR => rotr(stack)?,
-                Instruction::I32Extend8S | Instruction::I64Extend8S => extend8_s(stack)?,
-                Instruction::I32Extend16S | Instruction::I64Extend16S => extend16_s(stack)?,
-                Instruction::I32Const(v) => stack.push((*v).into()),
-                Instruction::I64Extend32S => i64extend_32s(stack)?,
-                Instruction::I64Const(v) => stack.push((*v).into()),
-                Instruction::F32Const(v) => stack.push((*v).into()),
-                Instruction::F64Const(v) => stack.push((*v).into()),
-                Instruction::F32Add | Instruction::F64Add => add(stack)?,
-                Instruction::F32Sub | Instruction::F64Sub => sub(stack)?,
-                Instruction::F32Mul | Instruction::F64Mul => mul(stack)?,
-                Instruction::F32Div | Instruction::F64Div => div(stack)?,
-                Instruction::F32Ceil | Instruction::F64Ceil => ceil(stack)?,
-                Instruction::F32Floor | Instruction::F64Floor => floor(stack)?,
-                Instruction::F32Max | Instruction::F64Max => max(stack)?,
-                Instruction::F32Min | Instruction::F64Min => min(stack)?,
-                Instruction::F32Nearest | Instruction::F64Nearest => nearest(stack)?,
-                Instruction::F32Sqrt | Instruction::F64Sqrt => sqrt(stack)?,
-                Instruction::F32Trunc | Instruction::F64Trunc => trunc(stack)?,
-                Instruction::F32Copysign | Instruction::F64Copysign => copysign(stack)?,
-                Instruction::I32WrapI64 => i32_wrap_i64(stack)?,
-                Instruction::F32Abs | Instruction::F64Abs => abs(stack)?,
-                Instruction::F32Neg | Instruction::F64Neg => neg(stack)?,
-                Instruction::F32Eq | Instruction::F64Eq => equal(stack)?,
-                Instruction::F32Ne | Instruction::F64Ne => not_equal(stack)?,
-                Instruction::F32Lt | Instruction::F64Lt => flt(stack)?,
-                Instruction::F32Gt | Instruction::F64Gt => fgt(stack)?,
-                Instruction::F32Le | Instruction::F64Le => fle(stack)?,
-                Instruction::F32Ge | Instruction::F64Ge => fge(stack)?,
-                Instruction::Drop => {
-                    stack.pop();
-                }
-                Instruction::Return => {
-                    let frame = self
-                        .call_stack
-                        .pop()
-                        .with_context(|| Error::CallStackPopError("return".into()))?;
-                    trace!("frame in the return instruction");
-                    let Frame { sp, arity, .. } = frame;
-                    stack_unwind(stack, sp, arity)?;
-                }
-                Instruction::End => {
-                    match frame.labels.pop() {
-                        // if label is exists, this means the end
-                        // instruction is in a block, if, loop, or else
-                        Some(label) => {
-                            trace!("end instruction, label: {:?}", &label);
-                            let Label { pc, sp, arity, .. } = label;
-                            frame.pc = pc as isize;
-                            stack_unwind(stack, sp, arity)?;
-                        }
-                        // it label is not exists, this means the end of
-                        // function
-                        None => {
-                            let frame = self
-                                .call_stack
-                                .pop()
-                                .with_context(|| Error::CallStackPopError("end".into()))?;
-                            let Frame { sp, arity, .. } = frame;
-                            stack_unwind(stack, sp, arity)?;
-                        }
+    // execute the next instruction, returns false if there is nothing to execute
+    // NOTE: the stacks are not cleaned up on a trap unlike call
+    pub fn step(&mut self) -> Result<bool> {
+        let stack = &mut self.stack;
+        let Some(frame) = self.call_stack.last_mut() else {
+            trace!("call stack is empty, return");
+            return Ok(false);
+        };
+        let insts = &frame.insts;
+        frame.pc += 1;
+        let Some(inst) = insts.get(frame.pc as usize) else {
+            trace!("reach the end of function");
+            return Ok(false);
+        };
+        consume_fuel(&mut self.fuel)?;
+        consume_fuel(&mut self.call_fuel)?;
+        trace!("pc: {}, inst: {:?}", frame.pc, &inst);
+        match inst {
+            Instruction::Unreachable => bail!("unreachable"),
+            Instruction::Nop => {}
+            Instruction::LocalGet(idx) => {
+                local_get(&frame.locals, stack, *idx as usize)?;
+            }
+            Instruction::LocalSet(idx) => {
+                local_set(&mut frame.locals, stack, *idx as usize)?;
+            }
+            Instruction::LocalTee(idx) => {
+                local_tee(&mut frame.locals, stack, *idx as usize)?;
+            }
+            Instruction::GlobalGet(idx) => {
+                global_get(&mut self.store.borrow_mut(), stack, *idx as usize)?
+            }
+            Instruction::GlobalSet(idx) => {
+                global_set(&mut self.store.borrow_mut(), stack, *idx as usize)?
+            }
+            Instruction::I32Add | Instruction::I64Add => add(stack)?,
+            Instruction::I32Sub | Instruction::I64Sub => sub(stack)?,
+            Instruction::I32Mul | Instruction::I64Mul => mul(stack)?,
+            Instruction::I32Clz | Instruction::I64Clz => clz(stack)?,
+            Instruction::I32Ctz | Instruction::I64Ctz => ctz(stack)?,
+            Instruction::I32DivU | Instruction::I64DivU => div_u(stack)?,
+            Instruction::I32DivS | Instruction::I64DivS => div_s(stack)?,
+            Instruction::I32Eq | Instruction::I64Eq => equal(stack)?,
+            Instruction::I32Eqz | Instruction::I64Eqz => eqz(stack)?,
+            Instruction::I32Ne | Instruction::I64Ne => not_equal(stack)?,
+            Instruction::I32LtS | Instruction::I64LtS => lt_s(stack)?,
+            Instruction::I32LtU | Instruction::I64LtU => lt_u(stack)?,
+            Instruction::I32GtS | Instruction::I64GtS => gt_s(stack)?,
+            Instruction::I32GtU | Instruction::I64GtU => gt_u(stack)?,
+            Instruction::I32LeS | Instruction::I64LeS => le_s(stack)?,
+            Instruction::I32LeU | Instruction::I64LeU => le_u(stack)?,
+            Instruction::I32GeS | Instruction::I64GeS => ge_s(stack)?,
+            Instruction::I32GeU | Instruction::I64GeU => ge_u(stack)?,
+            Instruction::I32Popcnt | Instruction::I64Popcnt => popcnt(stack)?,
+            Instruction::I32RemU | Instruction::I64RemU => rem_u(stack)?,
+            Instruction::I32RemS | Instruction::I64RemS => rem_s(stack)?,
+            Instruction::I32And | Instruction::I64And => and(stack)?,
+            Instruction::I32Or | Instruction::I64Or => or(stack)?,
+            Instruction::I32Xor | Instruction::I64Xor => xor(stack)?,
+            Instruction::I32ShL | Instruction::I64ShL => shl(stack)?,
+            Instruction::I32ShrU | Instruction::I64ShrU => shr_u(stack)?,
+            Instruction::I32ShrS | Instruction::I64ShrS => shr_s(stack)?,
+            Instruction::I32RtoL | Instruction::I64RtoL => rotl(stack)?,
+            Instruction::I32RtoR | Instruction::I64RtoR => rotr(stack)?,
+            Instruction::I32Extend8S | Instruction::I64Extend8S => extend8_s(stack)?,
+            Instruction::I32Extend16S | Instruction::I64Extend16S => extend16_s(stack)?,
+            Instruction::I32Const(v) => stack.push((*v).into()),
+            Instruction::I64Extend32S => i64extend_32s(stack)?,
+            Instruction::I64Const(v) => stack.push((*v).into()),
+            Instruction::F32Const(v) => stack.push((*v).into()),
+            Instruction::F64Const(v) => stack.push((*v).into()),
+            Instruction::F32Add | Instruction::F64Add => add(stack)?,
+            Instruction::F32Sub | Instruction::F64Sub => sub(stack)?,
+            Instruction::F32Mul | Instruction::F64Mul => mul(stack)?,
+            Instruction::F32Div | Instruction::F64Div => div(stack)?,
+            Instruction::F32Ceil | Instruction::F64Ceil => ceil(stack)?,
+            Instruction::F32Floor | Instruction::F64Floor => floor(stack)?,
+            Instruction::F32Max | Instruction::F64Max => max(stack)?,
+            Instruction::F32Min | Instruction::F64Min => min(stack)?,
+            Instruction::F32Nearest | Instruction::F64Nearest => nearest(stack)?,
+            Instruction::F32Sqrt | Instruction::F64Sqrt => sqrt(stack)?,
+            Instruction::F32Trunc | Instruction::F64Trunc => trunc(stack)?,
+            Instruction::F32Copysign | Instruction::F64Copysign => copysign(stack)?,
+            Instruction::I32WrapI64 => i32_wrap_i64(stack)?,
+            Instruction::F32Abs | Instruction::F64Abs => abs(stack)?,
+            Instruction::F32Neg | Instruction::F64Neg => neg(stack)?,
+            Instruction::F32Eq | Instruction::F64Eq => equal(stack)?,
+            Instruction::F32Ne | Instruction::F64Ne => not_equal(stack)?,
+            Instruction::F32Lt | Instruction::F64Lt => flt(stack)?,
+            Instruction::F32Gt | Instruction::F64Gt => fgt(stack)?,
+            Instruction::F32Le | Instruction::F64Le => fle(stack)?,
+            Instruction::F32Ge | Instruction::F64Ge => fge(stack)?,
+            // NOTE: any value including references can be dropped
+            Instruction::Drop => {
+                stack.pop().with_context(|| Error::StackPopError)?;
+            }
+            Instruction::Return => {
+                func_return(stack, &mut self.call_stack, "return")?;
+            }
+            Instruction::End => {
+                match frame.labels.pop() {
+                    // if label is exists, this means the end
+                    // instruction is in a block, if, loop, or else
+                    Some(label) => {
+                        trace!("end instruction, label: {:?}", &label);
+                        let Label { pc, sp, arity, .. } = label;
+                        frame.pc = pc as isize;
+                        stack_unwind(stack, sp, arity)?;
+                    }
+                    // if label is not exists, this means the end of
+                    // function
+                    None => {
+                        let frame = self
+                            .call_stack
+                            .pop()
+                            .with_context(|| Error::CallStackPopError("end".into()))?;
+                        let Frame { sp, arity, .. } = frame;
+                        stack_unwind(stack, sp, arity)?;
                     }
                 }
-                Instruction::Br(level) => {
-                    let labels = &mut frame.labels;
-                    let pc = br(labels, stack, level)?;
-                    frame.pc = pc;
+            }
+            Instruction::Br(level) => {
+                let labels = &mut frame.labels;
+                match br(labels, stack, level)? {
+                    Some(pc) => frame.pc = pc,
+                    None => func_return(stack, &mut self.call_stack, "br")?,
                 }
-                Instruction::BrIf(level) => {
-                    let value: Value = stack.pop1()?;
-                    if value.is_true() {
-                        let labels = &mut frame.labels;
-                        let pc = br(labels, stack, level)?;
-                        frame.pc = pc;
+            }
+            Instruction::BrIf(level) => {
+                let value: Value = stack.pop1()?;
+                if value.is_true()? {
+                    let labels = &mut frame.labels;
+                    match br(labels, stack, level)? {
+                        Some(pc) => frame.pc = pc,
+                        None => func_return(stack, &mut self.call_stack, "br_if")?,
                     }
                 }
-                Instruction::BrTable(label_idxs, default_idx) => {
-                    let value: i32 = stack.pop1::<Value>()?.into();
-                    let idx = value as usize;
-
-                    let level = if idx < label_idxs.len() {
-                        label_idxs
-                            .get(idx)
-                            .with_context(|| Error::InvalidBrTableIndex(idx))?
-                    } else {
-                        default_idx
-                    };
+            }
+            Instruction::BrTable(label_idxs, default_idx) => {
+                let value: i32 = stack.pop1()?;
+                let idx = value as usize;
 
-                    let labels = &mut frame.labels;
-                    let pc = br(labels, stack, level)?;
-                    frame.pc = pc;
-                }
-                Instruction::Loop(block) => {
-                    let arity = block.block_type.result_count();
-                    let start_pc = frame.pc;
-                    let pc = get_end_address(insts, frame.pc)?;
-
-                    let label = Label {
-                        start: Some(start_pc),
-                        kind: LabelKind::Loop,
-                        pc,
-                        sp: stack.len(),
-                        arity,
-                    };
-                    trace!("push label '{:?}' in the loop", &label);
-                    frame.labels.push(label);
-                }
-                Instruction::If(block) => {
-                    let cond: Value = stack.pop1()?;
+                let level = if idx < label_idxs.len() {
+                    label_idxs
+                        .get(idx)
+                        .with_context(|| Error::InvalidBrTableIndex(idx))?
+                } else {
+                    default_idx
+                };
 
-                    // calc pc when the end of block
-                    let next_pc = get_end_address(insts, frame.pc)?;
+                let labels = &mut frame.labels;
+                match br(labels, stack, level)? {
+                    Some(pc) => frame.pc = pc,
+                    None => func_return(stack, &mut self.call_stack, "br_table")?,
+                }
+            }
+            Instruction::Loop(block) => {
+                let arity = block.block_type.result_count();
+                let start_pc = frame.pc;
+                let pc = get_end_address(insts, frame.pc)?;
 
-                    if !cond.is_true() {
-                        // if the condition is false, skip the if block
-                        frame.pc = get_else_or_end_address(insts, frame.pc)? as isize;
-                    }
+                let label = Label {
+                    start: Some(start_pc),
+                    kind: LabelKind::Loop,
+                    pc,
+                    sp: stack.len(),
+                    arity,
+                };
+                trace!("push label '{:?}' in the loop", &label);
+                frame.labels.push(label);
+            }
+            Instruction::If(block) => {
+                let cond: Value = stack.pop1()?;
 
-                    // NOTE: if block has no any instruction, just continue
-                    if next_pc == frame.pc as usize {
-                        continue;
-                    }
+                // calc pc when the end of block
+                let next_pc = get_end_address(insts, frame.pc)?;
 
-                    let label = Label {
-                        start: None,
-                        kind: LabelKind::If,
-                        pc: next_pc,
-                        sp: stack.len(),
-                        arity: block.block_type.result_count(),
-                    };
-                    trace!("push label '{:?}' in the if block", &label);
-                    frame.labels.push(label);
-                }
-                Instruction::Else => {
-                    let label = frame
-                        .labels
-                        .pop()
-                        .with_context(|| Error::LabelPopError("else".into()))?;
-                    let Label { pc, .. } = label;
-                    frame.pc = pc as isize;
+                if !cond.is_true()? {
+                    // if the condition is false, skip the if block
+                    frame.pc = get_else_or_end_address(insts, frame.pc)? as isize;
                 }
-                Instruction::Block(block) => {
-                    let arity = block.block_type.result_count();
-                    let pc = get_end_address(insts, frame.pc)?;
-
-                    let label = Label {
-                        start: None,
-                        kind: LabelKind::Block,
-                        pc,
-                        sp: stack.len(),
-                        arity,
-                    };
-                    trace!("push label '{:?}' in the block", &label);
-                    frame.labels.push(label);
+
+                // NOTE: if block has no any instruction, just continue
+                if next_pc == frame.pc as usize {
+                    return Ok(true);
                 }
-                Instruction::Call(idx) => {
-                    let idx = *idx as usize;
-                    let store = self.store.borrow();
-                    let func = store
-                        .funcs
-                        .get(idx)
-                        .with_context(|| Error::NotFoundFunction(idx))?;
-                    match func {
-                        FuncInst::Internal(func) => {
-                            push_frame(stack, &mut self.call_stack, func);
-                        }
-                        FuncInst::External(func) => {
-                            let result =
-                                invoke_external(Rc::clone(&self.store), stack, func.clone())?;
-                            if let Some(value) = result {
-                                stack.push(value);
-                            }
+
+                let label = Label {
+                    start: None,
+                    kind: LabelKind::If,
+                    pc: next_pc,
+                    sp: stack.len(),
+                    arity: block.block_type.result_count(),
+                };
+                trace!("push label '{:?}' in the if block", &label);
+                frame.labels.push(label);
+            }
+            Instruction::Else => {
+                let label = frame
+                    .labels
+                    .pop()
+                    .with_context(|| Error::LabelPopError("else".into()))?;
+                // NOTE: the stray else can appear in the module which is not validated
+                let Label {
+                    pc,
+                    kind: LabelKind::If,
+                    ..
+                } = label
+                else {
+                    bail!(Error::ElseWithoutIf);
+                };
+                frame.pc = pc as isize;
+            }
+            Instruction::Block(block) => {
+                let arity = block.block_type.result_count();
+                let pc = get_end_address(insts, frame.pc)?;
+
+                let label = Label {
+                    start: None,
+                    kind: LabelKind::Block,
+                    pc,
+                    sp: stack.len(),
+                    arity,
+                };
+                trace!("push label '{:?}' in the block", &label);
+                frame.labels.push(label);
+            }
+            Instruction::Call(idx) => {
+                let idx = *idx as usize;
+                // NOTE: the store must not be borrowed while calling the host function,
+                // because it may call back into the guest
+                let func = self
+                    .store
+                    .borrow()
+                    .funcs
+                    .get(idx)
+                    .cloned()
+                    .with_context(|| Error::NotFoundFunction(idx))?;
+                match func {
+                    FuncInst::Internal(func) => {
+                        check_call_depth(&self.call_stack, self.max_call_depth)?;
+                        push_frame(stack, &mut self.call_stack, &func)?;
+                    }
+                    FuncInst::External(func) => {
+                        let result = invoke_external(Rc::clone(&self.store), stack, func)?;
+                        if let Some(value) = result {
+                            stack.push(value);
                         }
                     }
                 }
-                Instruction::CallIndirect((signature_idx, table_idx)) => {
-                    let elem_idx = stack.pop1::<i32>()? as usize;
-
-                    let func = {
-                        let idx = *table_idx as usize;
-                        let tables = &self.store.borrow().tables;
-                        let table = tables
-                            .get(idx) // NOTE: table_idx is always 0 now
-                            .with_context(|| Error::NotFoundTable(idx))?;
-
-                        let table = Rc::clone(table);
-                        let table = table.borrow();
-                        let func = table
-                            .funcs
-                            .get(elem_idx)
-                            .with_context(|| Error::UndefinedElement)?
-                            .as_ref()
-                            .with_context(|| Error::UninitializedElement(elem_idx))?;
-
-                        (*func).clone()
-                    };
-
-                    // validate expect func signature and actual func signature
-                    let idx = *signature_idx as usize;
-                    let store = self.store.borrow();
-                    let expect_func_type = store
-                        .module
-                        .func_types
-                        .get(idx)
-                        .with_context(|| Error::NotFoundFuncType(idx))?;
-
-                    let func_type = match func {
-                        FuncInst::Internal(ref func) => &func.func_type,
-                        FuncInst::External(ref func) => &func.func_type,
-                    };
-
-                    if func_type.params != expect_func_type.params
-                        || func_type.results != expect_func_type.results
-                    {
-                        trace!(
-                            "expect func signature: {:?}, actual func signature: {:?}",
-                            expect_func_type,
-                            func_type
-                        );
-                        bail!(Error::TypeMismatchIndirectCall)
-                    }
+            }
+            Instruction::CallIndirect((signature_idx, table_idx)) => {
+                let elem_idx = stack.pop1::<i32>()? as u32 as usize;
+                let func = get_indirect_func(
+                    &self.store.borrow(),
+                    elem_idx,
+                    *signature_idx as usize,
+                    *table_idx as usize,
+                )?;
 
-                    match func {
-                        FuncInst::Internal(ref func) => {
-                            push_frame(stack, &mut self.call_stack, func);
+                match func {
+                    FuncInst::Internal(ref func) => {
+                        check_call_depth(&self.call_stack, self.max_call_depth)?;
+                        push_frame(stack, &mut self.call_stack, func)?;
+                        // NOTE: the table may be shared with other modules
+                        if func.store_id != self.store.borrow().id {
+                            let (store, store_stack) = (&mut self.store, &mut self.store_stack);
+                            enter_foreign_store(store, store_stack, &self.call_stack, func)?;
                         }
-                        FuncInst::External(ref func) => {
-                            let result =
-                                invoke_external(Rc::clone(&self.store), stack, func.clone())?;
-                            if let Some(value) = result {
-                                stack.push(value);
-                            }
+                    }
+                    FuncInst::External(func) => {
+                        let result = invoke_external(Rc::clone(&self.store), stack, func)?;
+                        if let Some(value) = result {
+                            stack.push(value);
                         }
-                    };
+                    }
+                };
+            }
+            Instruction::ReturnCall(idx) => {
+                let idx = *idx as usize;
+                let func = self
+                    .store
+                    .borrow()
+                    .funcs
+                    .get(idx)
+                    .cloned()
+                    .with_context(|| Error::NotFoundFunction(idx))?;
+                return_call(Rc::clone(&self.store), stack, &mut self.call_stack, func)?;
+            }
+            Instruction::ReturnCallIndirect((signature_idx, table_idx)) => {
+                let (signature_idx, table_idx) = (*signature_idx as usize, *table_idx as usize);
+                let elem_idx = stack.pop1::<i32>()? as u32 as usize;
+                let func =
+                    get_indirect_func(&self.store.borrow(), elem_idx, signature_idx, table_idx)?;
+                let foreign = match &func {
+                    FuncInst::Internal(func) if func.store_id != self.store.borrow().id => {
+                        Some(func.clone())
+                    }
+                    _ => None,
+                };
+                return_call(Rc::clone(&self.store), stack, &mut self.call_stack, func)?;
+                if let Some(func) = foreign {
+                    let (store, store_stack) = (&mut self.store, &mut self.store_stack);
+                    enter_foreign_store(store, store_stack, &self.call_stack, &func)?;
                 }
-                // NOTE: only support 1 memory now
-                Instruction::MemoryGrow(idx) => {
-                    let idx = *idx as usize;
-                    let store = self.store.borrow();
-                    let memory = store
-                        .memory
-                        .get(idx)
-                        .with_context(|| Error::NotFoundMemory(idx))?;
-                    let memory = Rc::clone(memory);
-                    let n = stack.pop1::<i32>()?;
-                    let mut memory = memory.borrow_mut();
-                    let size = memory.size();
-                    match memory.grow(n as u32) {
-                        Ok(_) => {
-                            stack.push((size as i32).into());
-                        }
-                        Err(e) => {
-                            error!("memory grow error: {}", e);
-                            stack.push((-1).into());
+            }
+            // NOTE: the function index is also the address, since the store has only one module
+            Instruction::RefFunc(idx) => stack.push(Value::FuncRef(Some(*idx))),
+            Instruction::RefNull => stack.push(Value::FuncRef(None)),
+            Instruction::RefIsNull => {
+                let value = stack.pop().with_context(|| Error::StackPopError)?;
+                let Value::FuncRef(func_idx) = value else {
+                    bail!(Error::UnexpectedStackValueType(value));
+                };
+                stack.push(Value::I32(func_idx.is_none() as i32));
+            }
+            Instruction::MemoryGrow(idx) => {
+                let idx = *idx as usize;
+                let store = self.store.borrow();
+                let memory = store
+                    .memory
+                    .get(idx)
+                    .with_context(|| Error::NotFoundMemory(idx))?;
+                let memory = Rc::clone(memory);
+                let n = stack.pop1::<i32>()? as u32;
+                let mut memory = memory.borrow_mut();
+                let size = memory.size();
+                let result = match self.max_memory_pages {
+                    Some(max) if size as u64 + n as u64 > max as u64 => {
+                        Err(Error::MemoryPageOverflow(max, (size as u32).saturating_add(n)).into())
+                    }
+                    _ => grow_memory(&mut memory, n, self.resource_limiter.as_ref()),
+                };
+                match result {
+                    Ok(_) => {
+                        if let Some(hook) = &self.memory_grow_hook {
+                            (hook.borrow_mut())(size as u32, size as u32 + n);
                         }
+                        stack.push((size as i32).into());
+                    }
+                    Err(e) => {
+                        error!("memory grow error: {}", e);
+                        stack.push((-1).into());
                     }
                 }
-                Instruction::MemorySize => {
-                    let idx = 0;
-                    let store = self.store.borrow();
-                    let memory = store
-                        .memory
-                        .get(idx)
-                        .with_context(|| Error::NotFoundMemory(idx))?;
-                    let memory = memory.borrow();
-                    let size = memory.size() as i32;
-                    stack.push(size.into());
+            }
+            Instruction::TableGrow(idx) => {
+                let idx = *idx as usize;
+                let store = self.store.borrow();
+                let table = store
+                    .tables
+                    .get(idx)
+                    .with_context(|| Error::NotFoundTable(idx))?;
+                let n = stack.pop1::<i32>()? as u32;
+                let init = stack.pop().with_context(|| Error::StackPopError)?;
+                let init = func_ref(&store, init)?;
+                let mut table = table.borrow_mut();
+                let size = table.funcs.len() as u32;
+                let result = match self.max_table_elements {
+                    Some(max) if size as u64 + n as u64 > max as u64 => {
+                        Err(Error::TableSizeOverflow(max, size.saturating_add(n)).into())
+                    }
+                    _ => table.grow(n, init),
+                };
+                match result {
+                    Ok(_) => stack.push((size as i32).into()),
+                    Err(e) => {
+                        error!("table grow error: {}", e);
+                        stack.push((-1).into());
+                    }
                 }
-                Instruction::MemoryCopy(_, _) => {
-                    let len = stack.pop1::<i32>()? as usize;
-                    let src = stack.pop1::<i32>()? as usize;
-                    let dst = stack.pop1::<i32>()? as usize;
-
-                    let store = self.store.borrow();
-                    let memory = store
-                        .memory
-                        .get(0)
-                        .with_context(|| Error::NotFoundMemory(dst))?;
-                    let mut memory = memory.borrow_mut();
+            }
+            Instruction::MemorySize(idx) => {
+                let idx = *idx as usize;
+                let store = self.store.borrow();
+                let memory = store
+                    .memory
+                    .get(idx)
+                    .with_context(|| Error::NotFoundMemory(idx))?;
+                let memory = memory.borrow();
+                let size = memory.size() as i32;
+                stack.push(size.into());
+            }
+            Instruction::MemoryCopy(dst_idx, src_idx) => {
+                let len = stack.pop1::<i32>()? as u32 as usize;
+                let src = stack.pop1::<i32>()? as u32 as usize;
+                let dst = stack.pop1::<i32>()? as u32 as usize;
+
+                let store = self.store.borrow();
+                let (dst_idx, src_idx) = (*dst_idx as usize, *src_idx as usize);
+                let dst_memory = store
+                    .memory
+                    .get(dst_idx)
+                    .with_context(|| Error::NotFoundMemory(dst_idx))?;
+                let src_memory = store
+                    .memory
+                    .get(src_idx)
+                    .with_context(|| Error::NotFoundMemory(src_idx))?;
+
+                if Rc::ptr_eq(dst_memory, src_memory) {
+                    let mut memory = dst_memory.borrow_mut();
+                    let size = memory.data.len();
+                    if src.saturating_add(len) > size || dst.saturating_add(len) > size {
+                        bail!(Error::OutOfBoundsMemoryAccess);
+                    }
                     memory.data.copy_within(src..src + len, dst);
+                } else {
+                    let src_memory = src_memory.borrow();
+                    let mut dst_memory = dst_memory.borrow_mut();
+                    let src = src
+                        .checked_add(len)
+                        .and_then(|end| src_memory.data.get(src..end))
+                        .with_context(|| Error::OutOfBoundsMemoryAccess)?;
+                    let dst = dst
+                        .checked_add(len)
+                        .and_then(|end| dst_memory.data.get_mut(dst..end))
+                        .with_context(|| Error::OutOfBoundsMemoryAccess)?;
+                    dst.copy_from_slice(src);
                 }
-                Instruction::MemoryFill(_) => {
-                    let len = stack.pop1::<i32>()? as usize;
-                    let val = stack.pop1::<i32>()? as u8;
-                    let dst = stack.pop1::<i32>()? as usize;
-
-                    let store = self.store.borrow();
-                    let memory = store
-                        .memory
-                        .get(0)
-                        .with_context(|| Error::NotFoundMemory(dst))?;
-                    let mut memory = memory.borrow_mut();
-
-                    let data: Vec<_> = vec![val; len];
-                    let dst = memory.data[dst..dst + len].as_mut();
-                    dst.copy_from_slice(data.as_slice());
-                }
-                Instruction::I32Load(arg) => load!(stack, self.store, i32, arg),
-                Instruction::I64Load(arg) => load!(stack, self.store, i64, arg),
-                Instruction::F32Load(arg) => load!(stack, self.store, f32, arg),
-                Instruction::F64Load(arg) => load!(stack, self.store, f64, arg),
-                Instruction::I32Load8S(arg) => load!(stack, self.store, i8, arg, i32),
-                Instruction::I32Load8U(arg) => load!(stack, self.store, u8, arg, i32),
-                Instruction::I32Load16S(arg) => load!(stack, self.store, i16, arg, i32),
-                Instruction::I32Load16U(arg) => load!(stack, self.store, u16, arg, i32),
-                Instruction::I64Load8S(arg) => load!(stack, self.store, i8, arg, i64),
-                Instruction::I64Load8U(arg) => load!(stack, self.store, u8, arg, i64),
-                Instruction::I64Load16S(arg) => load!(stack, self.store, i16, arg, i64),
-                Instruction::I64Load16U(arg) => load!(stack, self.store, u16, arg, i64),
-                Instruction::I64Load32S(arg) => load!(stack, self.store, i32, arg, i64),
-                Instruction::I64Load32U(arg) => load!(stack, self.store, u32, arg, i64),
-                Instruction::I32Store(arg) => store!(stack, self.store, i32, arg),
-                Instruction::I64Store(arg) => store!(stack, self.store, i64, arg),
-                Instruction::F32Store(arg) => store!(stack, self.store, f32, arg),
-                Instruction::F64Store(arg) => store!(stack, self.store, f64, arg),
-                Instruction::I32Store8(arg) => store!(stack, self.store, i32, arg, i8),
-                Instruction::I32Store16(arg) => store!(stack, self.store, i32, arg, i16),
-                Instruction::I64Store16(arg) => store!(stack, self.store, i64, arg, i16),
-                Instruction::I64Store8(arg) => store!(stack, self.store, i64, arg, i8),
-                Instruction::I64Store32(arg) => store!(stack, self.store, i64, arg, i32),
-                Instruction::Select => {
-                    let cond = stack.pop1::<i32>()?;
-                    let val2 = stack.pop1::<Value>()?;
-                    let val1 = stack.pop1::<Value>()?;
-                    stack.push(if cond != 0 { val1 } else { val2 });
-                }
-                Instruction::I32TruncF32S => i32_trunc_f32_s(stack)?,
-                Instruction::I32TruncF32U => i32_trunc_f32_u(stack)?,
-                Instruction::I32TruncF64S => i32_trunc_f64_s(stack)?,
-                Instruction::I32TruncF64U => i32_trunc_f64_u(stack)?,
-                Instruction::I64ExtendI32S => i64_extend_i32_s(stack)?,
-                Instruction::I64ExtendI32U => i64_extend_i32_u(stack)?,
-                Instruction::I64TruncF32S => i64_trunc_f32_s(stack)?,
-                Instruction::I64TruncF32U => i64_trunc_f32_u(stack)?,
-                Instruction::I64TruncF64S => i64_trunc_f64_s(stack)?,
-                Instruction::I64TruncF64U => i64_trunc_f64_u(stack)?,
-                Instruction::F32ConvertI32S => f32_convert_i32_s(stack)?,
-                Instruction::F32ConvertI32U => f32_convert_i32_u(stack)?,
-                Instruction::F32ConvertI64S => f32_convert_i64_s(stack)?,
-                Instruction::F32ConvertI64U => f32_convert_i64_u(stack)?,
-                Instruction::F32DemoteF64 => f32_demote_f64(stack)?,
-                Instruction::F64ConvertI32S => f64_convert_i32_s(stack)?,
-                Instruction::F64ConvertI32U => f64_convert_i32_u(stack)?,
-                Instruction::F64ConvertI64S => f64_convert_i64_s(stack)?,
-                Instruction::F64ConvertI64U => f64_convert_i64_u(stack)?,
-                Instruction::F64PromoteF32 => f64_demote_f32(stack)?,
-                Instruction::I32ReinterpretF32 => i32_reinterpret_f32(stack)?,
-                Instruction::I64ReinterpretF64 => i64_reinterpret_f64(stack)?,
-                Instruction::F32ReinterpretI32 => f32_reinterpret_i32(stack)?,
-                Instruction::F64ReinterpretI64 => f64_reinterpret_i64(stack)?,
-            };
+            }
+            Instruction::MemoryFill(idx) => {
+                let len = stack.pop1::<i32>()? as u32 as usize;
+                let val = stack.pop1::<i32>()? as u8;
+                let dst = stack.pop1::<i32>()? as u32 as usize;
+
+                let store = self.store.borrow();
+                let idx = *idx as usize;
+                let memory = store
+                    .memory
+                    .get(idx)
+                    .with_context(|| Error::NotFoundMemory(idx))?;
+                let mut memory = memory.borrow_mut();
+
+                let dst = dst
+                    .checked_add(len)
+                    .and_then(|end| memory.data.get_mut(dst..end))
+                    .with_context(|| Error::OutOfBoundsMemoryAccess)?;
+                dst.fill(val);
+            }
+            Instruction::MemoryInit(data_idx, mem_idx) => {
+                let len = stack.pop1::<i32>()? as u32 as usize;
+                let src = stack.pop1::<i32>()? as u32 as usize;
+                let dst = stack.pop1::<i32>()? as u32 as usize;
+
+                let store = self.store.borrow();
+                let idx = *data_idx as usize;
+                let data = store
+                    .datas
+                    .get(idx)
+                    .with_context(|| Error::NotFoundData(idx))?;
+                let idx = *mem_idx as usize;
+                let mut memory = store
+                    .memory
+                    .get(idx)
+                    .with_context(|| Error::NotFoundMemory(idx))?
+                    .borrow_mut();
+
+                let src = src
+                    .checked_add(len)
+                    .and_then(|end| data.get(src..end))
+                    .with_context(|| Error::OutOfBoundsMemoryAccess)?;
+                let dst = dst
+                    .checked_add(len)
+                    .and_then(|end| memory.data.get_mut(dst..end))
+                    .with_context(|| Error::OutOfBoundsMemoryAccess)?;
+                dst.copy_from_slice(src);
+            }
+            Instruction::DataDrop(idx) => {
+                let idx = *idx as usize;
+                let mut store = self.store.borrow_mut();
+                let data = store
+                    .datas
+                    .get_mut(idx)
+                    .with_context(|| Error::NotFoundData(idx))?;
+                *data = vec![];
+            }
+            Instruction::I32Load(arg) => load!(stack, self.store, i32, arg),
+            Instruction::I64Load(arg) => load!(stack, self.store, i64, arg),
+            Instruction::F32Load(arg) => load!(stack, self.store, f32, arg),
+            Instruction::F64Load(arg) => load!(stack, self.store, f64, arg),
+            Instruction::I32Load8S(arg) => load!(stack, self.store, i8, arg, i32),
+            Instruction::I32Load8U(arg) => load!(stack, self.store, u8, arg, i32),
+            Instruction::I32Load16S(arg) => load!(stack, self.store, i16, arg, i32),
+            Instruction::I32Load16U(arg) => load!(stack, self.store, u16, arg, i32),
+            Instruction::I64Load8S(arg) => load!(stack, self.store, i8, arg, i64),
+            Instruction::I64Load8U(arg) => load!(stack, self.store, u8, arg, i64),
+            Instruction::I64Load16S(arg) => load!(stack, self.store, i16, arg, i64),
+            Instruction::I64Load16U(arg) => load!(stack, self.store, u16, arg, i64),
+            Instruction::I64Load32S(arg) => load!(stack, self.store, i32, arg, i64),
+            Instruction::I64Load32U(arg) => load!(stack, self.store, u32, arg, i64),
+            Instruction::I32Store(arg) => store!(stack, self.store, i32, arg),
+            Instruction::I64Store(arg) => store!(stack, self.store, i64, arg),
+            Instruction::F32Store(arg) => store!(stack, self.store, f32, arg),
+            Instruction::F64Store(arg) => store!(stack, self.store, f64, arg),
+            Instruction::I32Store8(arg) => store!(stack, self.store, i32, arg, i8),
+            Instruction::I32Store16(arg) => store!(stack, self.store, i32, arg, i16),
+            Instruction::I64Store16(arg) => store!(stack, self.store, i64, arg, i16),
+            Instruction::I64Store8(arg) => store!(stack, self.store, i64, arg, i8),
+            Instruction::I64Store32(arg) => store!(stack, self.store, i64, arg, i32),
+            Instruction::Select | Instruction::TypedSelect(_) => {
+                let cond = stack.pop1::<i32>()?;
+                let val2 = stack.pop1::<Value>()?;
+                let val1 = stack.pop1::<Value>()?;
+                stack.push(if cond != 0 { val1 } else { val2 });
+            }
+            Instruction::I32TruncF32S => i32_trunc_f32_s(stack)?,
+            Instruction::I32TruncF32U => i32_trunc_f32_u(stack)?,
+            Instruction::I32TruncF64S => i32_trunc_f64_s(stack)?,
+            Instruction::I32TruncF64U => i32_trunc_f64_u(stack)?,
+            Instruction::I64ExtendI32S => i64_extend_i32_s(stack)?,
+            Instruction::I64ExtendI32U => i64_extend_i32_u(stack)?,
+            Instruction::I64TruncF32S => i64_trunc_f32_s(stack)?,
+            Instruction::I64TruncF32U => i64_trunc_f32_u(stack)?,
+            Instruction::I64TruncF64S => i64_trunc_f64_s(stack)?,
+            Instruction::I64TruncF64U => i64_trunc_f64_u(stack)?,
+            Instruction::F32ConvertI32S => f32_convert_i32_s(stack)?,
+            Instruction::F32ConvertI32U => f32_convert_i32_u(stack)?,
+            Instruction::F32ConvertI64S => f32_convert_i64_s(stack)?,
+            Instruction::F32ConvertI64U => f32_convert_i64_u(stack)?,
+            Instruction::F32DemoteF64 => f32_demote_f64(stack)?,
+            Instruction::F64ConvertI32S => f64_convert_i32_s(stack)?,
+            Instruction::F64ConvertI32U => f64_convert_i32_u(stack)?,
+            Instruction::F64ConvertI64S => f64_convert_i64_s(stack)?,
+            Instruction::F64ConvertI64U => f64_convert_i64_u(stack)?,
+            Instruction::F64PromoteF32 => f64_promote_f32(stack)?,
+            Instruction::I32ReinterpretF32 => i32_reinterpret_f32(stack)?,
+            Instruction::I64ReinterpretF64 => i64_reinterpret_f64(stack)?,
+            Instruction::F32ReinterpretI32 => f32_reinterpret_i32(stack)?,
+            Instruction::F64ReinterpretI64 => f64_reinterpret_i64(stack)?,
+        };
+        // NOTE: the foreign frames have returned, back to the stores of their callers
+        while self
+            .store_stack
+            .last()
+            .is_some_and(|(height, _)| self.call_stack.len() <= *height)
+        {
+            if let Some((_, caller)) = self.store_stack.pop() {
+                self.store = caller;
+            }
         }
-        Ok(())
+        // NOTE: each instruction pushes only a few values, so checking once per step is enough
+        let max = self.max_stack_size.unwrap_or(DEFAULT_MAX_STACK_SIZE);
+        if self.stack.len() > max {
+            bail!(Error::StackOverflow);
+        }
+        if let Some(stats) = self.stats.as_mut() {
+            stats.instructions += 1;
+            stats.max_stack_size = stats.max_stack_size.max(self.stack.len());
+            stats.max_call_depth = stats.max_call_depth.max(self.call_stack.len());
+        }
+        Ok(true)
+    }
+}
+
+fn check_call_depth(call_stack: &[Frame], max_call_depth: Option<usize>) -> Result<()> {
+    if let Some(max) = max_call_depth {
+        if call_stack.len() >= max {
+            bail!(Error::CallStackExhausted);
+        }
+    }
+    Ok(())
+}
+
+// resolve the function reference to the table element, none is null
+fn func_ref(store: &Store, value: Value) -> Result<Option<FuncInst>> {
+    let Value::FuncRef(func_idx) = value else {
+        bail!(Error::TypeMismatchTableElement(value.type_of()));
+    };
+    let Some(idx) = func_idx else {
+        return Ok(None);
+    };
+    let idx = idx as usize;
+    let func = store
+        .funcs
+        .get(idx)
+        .with_context(|| Error::NotFoundFunction(idx))?;
+    Ok(Some(func.clone()))
+}
+
+// ask the limiter before growing the memory, and tell it when the growth failed
+fn grow_memory(
+    memory: &mut InternalMemoryInst,
+    n: u32,
+    limiter: Option<&SharedResourceLimiter>,
+) -> Result<()> {
+    let Some(limiter) = limiter else {
+        return memory.grow(n);
+    };
+    let current = memory.data.len();
+    let desired = (memory.size() + n as usize) * PAGE_SIZE as usize;
+    if !limiter.borrow_mut().memory_growing(current, desired) {
+        bail!(Error::MemoryLimitExceeded(desired));
+    }
+    memory
+        .grow(n)
+        .inspect_err(|_| limiter.borrow_mut().memory_grow_failed(current, desired))
+}
+
+fn consume_fuel(fuel: &mut Option<u64>) -> Result<()> {
+    if let Some(fuel) = fuel.as_mut() {
+        if *fuel == 0 {
+            bail!(Error::OutOfFuel);
+        }
+        *fuel -= 1;
+    }
+    Ok(())
+}
+
+// check the arguments of exported function before pushing them to the stack
+fn check_args(func_type: &FuncType, args: &[Value]) -> Result<()> {
+    let join = |types: Vec<ValueType>| -> String {
+        types.iter().map(value_type).collect::<Vec<_>>().join(",")
+    };
+    let params = &func_type.params;
+    if params.len() != args.len() {
+        bail!(Error::ArgumentCountMismatch(
+            params.len(),
+            join(params.clone()),
+            args.len()
+        ));
     }
+    if params
+        .iter()
+        .zip(args)
+        .any(|(ty, arg)| arg.type_of() != *ty)
+    {
+        let actual = args.iter().map(Value::type_of).collect();
+        bail!(Error::ArgumentTypeMismatch(
+            join(params.clone()),
+            join(actual)
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Runtime, Value};
-    use anyhow::{Context, Result};
+    use super::{RunState, Runtime, Trap, TrapLocation, Trapped, Value};
+    use crate::binary::instruction::Instruction;
+    use crate::binary::module::{DecodeOptions, Decoder};
+    use crate::binary::types::{ExportDesc, FuncType, ValueType};
+    use crate::execution::error::Error;
+    use crate::execution::module::{
+        ExternalFuncInst, FuncInst, GlobalInst, InternalGlobalInst, InternalMemoryInst,
+        InternalTableInst, MAX_TABLE_ELEMENTS, PAGE_SIZE,
+    };
+    use crate::execution::value::{format_typed_values, ExternalVal};
+    use crate::validation::validate;
+    #[cfg(feature = "wasi")]
+    use crate::wasi::WasiSnapshotPreview1;
+    use crate::{Exports, HostFunc, Importer, Store};
+    use anyhow::{bail, Context, Result};
+    use std::{cell::RefCell, rc::Rc};
 
     #[test]
     fn invoke() -> Result<()> {
@@ -688,4 +1229,2585 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn void_block() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "nested-void")
+    (block
+      (block
+        (i32.const 1)
+        (drop)
+      )
+      (block
+        (block (nop))
+      )
+    )
+  )
+  (func (export "nested-void-br")
+    (block
+      (block
+        (i32.const 1)
+        (i32.const 2)
+        (br 1)
+      )
+    )
+  )
+  (func (export "nested-void-result") (result i32)
+    (block
+      (block (nop))
+    )
+    (i32.const 3)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        for name in ["nested-void", "nested-void-br"] {
+            let result = runtime.call(name.into(), vec![])?;
+            assert_eq!(result, None, "func {} fail", name);
+            assert!(runtime.stack.is_empty(), "func {} fail", name);
+            assert!(runtime.call_stack.is_empty(), "func {} fail", name);
+        }
+
+        let result = runtime.call("nested-void-result".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(3)));
+        assert!(runtime.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn br_outermost() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "br") (result i32)
+    (br 0 (i32.const 1))
+  )
+  (func (export "nested-br") (result i32)
+    (block
+      (loop
+        (br 2 (i32.const 2))
+      )
+    )
+    (i32.const 0)
+  )
+  (func (export "br_if") (param i32) (result i32)
+    (drop (br_if 0 (i32.const 3) (local.get 0)))
+    (i32.const 4)
+  )
+  (func (export "br_table") (param i32) (result i32)
+    (block (result i32)
+      (br_table 0 1 (i32.const 5) (local.get 0))
+    )
+    (i32.const 6)
+    (i32.add)
+  )
+  (func $callee (result i32 i32)
+    (i32.const 7)
+    (block (br 1 (i32.const 8) (i32.const 9)))
+    (i32.const 10)
+    (i32.const 11)
+  )
+  (func (export "caller") (result i32)
+    (call $callee)
+    (i32.add)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let tests = [
+            ("br", vec![], 1),
+            ("nested-br", vec![], 2),
+            ("br_if", vec![Value::I32(1)], 3),
+            ("br_if", vec![Value::I32(0)], 4),
+            ("br_table", vec![Value::I32(1)], 5),
+            ("br_table", vec![Value::I32(0)], 11),
+            ("caller", vec![], 17),
+        ];
+        for (name, args, expected) in tests {
+            let result = runtime.call(name.into(), args)?;
+            assert_eq!(result, Some(Value::I32(expected)), "func {} fail", name);
+            assert!(runtime.stack.is_empty(), "func {} fail", name);
+            assert!(runtime.call_stack.is_empty(), "func {} fail", name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn if_without_else() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "if-void") (param i32) (result i32)
+    (local i32)
+    (i32.const 1)
+    (if (local.get 0)
+      (then (local.set 1 (i32.const 2)))
+    )
+    (local.get 1)
+    (i32.add)
+  )
+  (func (export "if-empty") (param i32)
+    (if (local.get 0) (then))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("if-void".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(3)));
+        let result = runtime.call("if-void".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        assert!(runtime.stack.is_empty());
+
+        for cond in [0, 1] {
+            let result = runtime.call("if-empty".into(), vec![Value::I32(cond)])?;
+            assert_eq!(result, None);
+            assert!(runtime.stack.is_empty());
+            assert!(runtime.call_stack.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn deep_recursion() -> Result<()> {
+        let code = r#"
+(module
+  (func $sum (export "sum") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (i64.const 0))
+      (else
+        (i64.add
+          (local.get 0)
+          (call $sum (i64.sub (local.get 0) (i64.const 1)))
+        )
+      )
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        // NOTE: wasm calls don't consume the native stack, so a small stack is enough
+        let run = move || -> Result<()> {
+            let mut runtime = Runtime::from_bytes(wasm, None)?;
+            let n = 200_000;
+            let result = runtime.call("sum".into(), vec![Value::I64(n)])?;
+            assert_eq!(result, Some(Value::I64(n * (n + 1) / 2)));
+            assert!(runtime.call_stack.is_empty());
+            Ok(())
+        };
+        let handle = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(run)?;
+        handle.join().expect("thread must not panic")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn return_call() -> Result<()> {
+        let code = r#"
+(module
+  (type $sig (func (param i32 i64) (result i64)))
+  (table funcref (elem $count-indirect))
+  (func $count (export "count") (param i32 i64) (result i64)
+    (local f64)
+    (if (result i64) (i32.eqz (local.get 0))
+      (then (local.get 1))
+      (else
+        ;; leftover operand which must be discarded by the tail call
+        (i32.const 42)
+        (drop)
+        (f64.const 1)
+        (return_call $count
+          (i32.sub (local.get 0) (i32.const 1))
+          (i64.add (local.get 1) (i64.const 1))
+        )
+      )
+    )
+  )
+  (func $count-indirect (export "count-indirect") (param i32 i64) (result i64)
+    (if (result i64) (i32.eqz (local.get 0))
+      (then (local.get 1))
+      (else
+        (return_call_indirect (type $sig)
+          (i32.sub (local.get 0) (i32.const 1))
+          (i64.add (local.get 1) (i64.const 1))
+          (i32.const 0)
+        )
+      )
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        for name in ["count", "count-indirect"] {
+            let result = runtime.call(name.into(), vec![Value::I32(1_000_000), Value::I64(0)])?;
+            assert_eq!(result, Some(Value::I64(1_000_000)), "func {} fail", name);
+            assert!(runtime.stack.is_empty(), "func {} fail", name);
+            assert!(runtime.call_stack.is_empty(), "func {} fail", name);
+
+            // NOTE: the capacity never shrinks, so it shows the maximum size while executing
+            assert!(runtime.stack.capacity() <= 16, "func {} fail", name);
+            assert!(runtime.call_stack.capacity() <= 4, "func {} fail", name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn func_to_wat() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "sum") (param i32) (result i64)
+    (local i64 f32)
+    (block
+      (loop
+        (br_if 1 (i32.eqz (local.get 0)))
+        (if (i32.gt_u (local.get 0) (i32.const 10))
+          (then (local.set 1 (i64.add (local.get 1) (i64.const 10))))
+          (else
+            (local.set 1 (i64.add (local.get 1) (i64.extend_i32_u (local.get 0))))
+          )
+        )
+        (local.set 2 (f32.const -0x1.8p+1))
+        (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+        (br 0)
+      )
+    )
+    (block (result i64)
+      (br_table 0 0 (local.get 1) (i32.const 0))
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let runtime = Runtime::from_bytes(&wasm, None)?;
+
+        let wat = runtime.func_to_wat("sum".into())?;
+        let actual = wat::parse_str(&wat)?;
+
+        let expected = Decoder::new(wasm.as_slice()).decode()?;
+        let actual = Decoder::new(actual.as_slice()).decode()?;
+        assert_eq!(
+            format!("{:?}", actual.type_section),
+            format!("{:?}", expected.type_section)
+        );
+        assert_eq!(
+            format!("{:?}", actual.export_section),
+            format!("{:?}", expected.export_section)
+        );
+        assert_eq!(
+            format!("{:?}", actual.code_section),
+            format!("{:?}", expected.code_section)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn host_trap() -> Result<()> {
+        #[derive(Debug, thiserror::Error)]
+        #[error("host function failed with {0}")]
+        struct HostError(i32);
+
+        struct Env;
+
+        impl Importer for Env {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                _store: Rc<RefCell<Store>>,
+                _func: ExternalFuncInst,
+                args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                let value: i32 = args[0].clone().try_into()?;
+                if value == 0 {
+                    bail!(HostError(value));
+                }
+                Ok(Some(value.into()))
+            }
+        }
+
+        let code = r#"
+(module
+  (import "env" "check" (func $check (param i32) (result i32)))
+  (func $inner (param i32) (result i32)
+    (i32.add (i32.const 1) (call $check (local.get 0)))
+  )
+  (func (export "run") (param i32) (result i32)
+    (block (result i32)
+      (call $inner (local.get 0))
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(Env)];
+        let mut runtime = Runtime::from_bytes(wasm, Some(importers))?;
+
+        let result = runtime.call("run".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(2)));
+
+        let err = runtime
+            .call("run".into(), vec![Value::I32(0)])
+            .expect_err("host function must trap");
+        assert_eq!(err.to_string(), "host function failed with 0");
+        assert!(matches!(
+            err.downcast_ref::<HostError>(),
+            Some(HostError(0))
+        ));
+        assert!(runtime.stack.is_empty());
+        assert!(runtime.call_stack.is_empty());
+
+        // runtime is still available after the trap
+        let result = runtime.call("run".into(), vec![Value::I32(5)])?;
+        assert_eq!(result, Some(Value::I32(6)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_names() -> Result<()> {
+        #[rustfmt::skip]
+        let wasm = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section, () -> i32
+            0x03, 0x03, 0x02, 0x00, 0x00, // function section
+            0x07, 0x09, 0x02, 0x01, 0x66, 0x00, 0x00, 0x01, 0x66, 0x00, 0x01, // export "f" twice
+            0x0a, 0x0b, 0x02, 0x04, 0x00, 0x41, 0x01, 0x0b, 0x04, 0x00, 0x41, 0x02, 0x0b, // code section
+        ];
+        // the first defined export wins
+        for _ in 0..10 {
+            let mut runtime = Runtime::from_bytes(wasm, None)?;
+            let result = runtime.call("f".into(), vec![])?;
+            assert_eq!(result, Some(Value::I32(1)));
+        }
+
+        struct Env(i32);
+
+        impl Importer for Env {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                _store: Rc<RefCell<Store>>,
+                _func: ExternalFuncInst,
+                _args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                Ok(Some(Value::I32(self.0)))
+            }
+        }
+
+        let code = r#"
+(module
+  (import "env" "value" (func $value (result i32)))
+  (func (export "value") (result i32) (call $value))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        // the first importer wins
+        for _ in 0..10 {
+            let importers: Vec<Box<dyn Importer>> = vec![Box::new(Env(1)), Box::new(Env(2))];
+            let mut runtime = Runtime::from_bytes(&wasm, Some(importers))?;
+            let result = runtime.call("value".into(), vec![])?;
+            assert_eq!(result, Some(Value::I32(1)));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn imported_global_in_const_expr() -> Result<()> {
+        struct Env;
+
+        impl Importer for Env {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                _store: Rc<RefCell<Store>>,
+                _func: ExternalFuncInst,
+                _args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                Ok(None)
+            }
+
+            fn resolve_global(&self, _module: &str, field: &str) -> Result<Option<GlobalInst>> {
+                let global = InternalGlobalInst {
+                    value: Value::I32(16),
+                    mutability: field == "mut-offset",
+                };
+                Ok(Some(Rc::new(RefCell::new(global))))
+            }
+        }
+
+        let code = r#"
+(module
+  (import "env" "offset" (global i32))
+  (global (export "global") i32 (global.get 0))
+  (memory 1)
+  (data (global.get 0) "hello")
+  (func (export "load") (result i32)
+    (i32.load8_u (i32.const 16))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let importers: Vec<Box<dyn Importer>> = vec![Box::new(Env)];
+        let mut runtime = Runtime::from_bytes(wasm, Some(importers))?;
+
+        let result = runtime.call("load".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(b'h' as i32)));
+        let Exports::Global(global) = runtime.exports("global".into())? else {
+            panic!("global must be exported");
+        };
+        assert_eq!(global.borrow().value, Value::I32(16));
+
+        // only imported immutable globals can be referenced
+        let invalid_codes = [
+            r#"(module (import "env" "mut-offset" (global (mut i32))) (memory 1) (data (global.get 0) "hello"))"#,
+            r#"(module (global i32 (i32.const 16)) (memory 1) (data (global.get 0) "hello"))"#,
+        ];
+        for code in invalid_codes {
+            let wasm = wat::parse_str(code)?;
+            let importers: Vec<Box<dyn Importer>> = vec![Box::new(Env)];
+            let result = Runtime::from_bytes(wasm, Some(importers));
+            assert!(result.is_err(), "{} must be rejected", code);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_type_compatibility() -> Result<()> {
+        struct Env;
+
+        impl Importer for Env {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                _store: Rc<RefCell<Store>>,
+                _func: ExternalFuncInst,
+                args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                Ok(args.into_iter().next())
+            }
+
+            fn resolve_func(&self, module: &str, field: &str) -> Result<Option<FuncInst>> {
+                let func = ExternalFuncInst {
+                    module: module.into(),
+                    field: field.into(),
+                    func_type: FuncType {
+                        params: vec![ValueType::I32],
+                        results: vec![ValueType::I32],
+                    },
+                };
+                Ok(Some(FuncInst::External(func)))
+            }
+
+            fn resolve_memory(
+                &self,
+                _module: &str,
+                _field: &str,
+            ) -> Result<Option<Rc<RefCell<InternalMemoryInst>>>> {
+                let memory = InternalMemoryInst {
+                    data: vec![0; PAGE_SIZE as usize],
+                    max: Some(2),
+                };
+                Ok(Some(Rc::new(RefCell::new(memory))))
+            }
+
+            fn resolve_global(&self, _module: &str, _field: &str) -> Result<Option<GlobalInst>> {
+                let global = InternalGlobalInst {
+                    value: Value::I32(0),
+                    mutability: true,
+                };
+                Ok(Some(Rc::new(RefCell::new(global))))
+            }
+        }
+
+        let code = r#"
+(module
+  (import "env" "id" (func $id (param i32) (result i32)))
+  (import "env" "memory" (memory 1 3))
+  (import "env" "global" (global (mut i32)))
+  (func (export "call") (param i32) (result i32)
+    (call $id (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, Some(vec![Box::new(Env)]))?;
+        let result = runtime.call("call".into(), vec![Value::I32(7)])?;
+        assert_eq!(result, Some(Value::I32(7)));
+
+        let invalid_codes = [
+            (
+                r#"(module (import "env" "id" (func (param i64) (result i32))))"#,
+                "id",
+            ),
+            (r#"(module (import "env" "id" (func (param i32))))"#, "id"),
+            (r#"(module (import "env" "memory" (memory 2)))"#, "memory"),
+            (r#"(module (import "env" "memory" (memory 1 1)))"#, "memory"),
+            (r#"(module (import "env" "global" (global i32)))"#, "global"),
+            (
+                r#"(module (import "env" "global" (global (mut i64))))"#,
+                "global",
+            ),
+        ];
+        for (code, field) in invalid_codes {
+            let wasm = wat::parse_str(code)?;
+            let result = Runtime::from_bytes(wasm, Some(vec![Box::new(Env)]));
+            assert_eq!(
+                result.err().map(|e| e.to_string()),
+                Some(format!("incompatible import type: env.{field}")),
+                "{} must be rejected",
+                code
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_imports_and_exports() -> Result<()> {
+        struct Env;
+
+        impl Importer for Env {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                _store: Rc<RefCell<Store>>,
+                func: ExternalFuncInst,
+                args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                let value: i32 = args[0].clone().try_into()?;
+                Ok(Some(Value::I32(value + func.func_type.params.len() as i32)))
+            }
+        }
+
+        // each import occupies its own index even if the module and field are the same
+        let code = r#"
+(module
+  (import "env" "inc" (func $inc1 (param i32) (result i32)))
+  (import "env" "inc" (func $inc2 (param i32 i32) (result i32)))
+  (func $add (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+  (func (export "call_imports") (result i32)
+    (i32.add
+      (call $inc1 (i32.const 10))
+      (call $inc2 (i32.const 20) (i32.const 0))
+    )
+  )
+  (export "add" (func $add))
+  (export "plus" (func $add))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, Some(vec![Box::new(Env)]))?;
+
+        let result = runtime.call("call_imports".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(11 + 22)));
+
+        for name in ["add", "plus"] {
+            let args = vec![Value::I32(1), Value::I32(2)];
+            let result = runtime.call(name.into(), args)?;
+            assert_eq!(result, Some(Value::I32(3)));
+        }
+        let store = runtime.store.borrow();
+        let idxs: Vec<_> = ["add", "plus"]
+            .iter()
+            .map(|name| store.module.export(name).map(|e| e.desc.clone()))
+            .collect();
+        assert!(matches!(
+            idxs.as_slice(),
+            [Some(ExternalVal::Func(2)), Some(ExternalVal::Func(2))]
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_grow() -> Result<()> {
+        let code = r#"
+(module
+  (memory (export "memory") 1 3)
+  (func (export "size") (result i32) (memory.size))
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+
+        let prev = runtime.call("size".into(), vec![])?;
+        assert_eq!(prev, Some(Value::I32(1)));
+
+        // returns the previous size in pages
+        let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, prev);
+        let result = runtime.call("size".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        assert_eq!(memory.borrow().data.len(), 2 * PAGE_SIZE as usize);
+
+        // grow by 0 returns the current size
+        let result = runtime.call("grow".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        assert_eq!(memory.borrow().data.len(), 2 * PAGE_SIZE as usize);
+
+        // fails when exceeding the maximum
+        let result = runtime.call("grow".into(), vec![Value::I32(2)])?;
+        assert_eq!(result, Some(Value::I32(-1)));
+        let result = runtime.call("grow".into(), vec![Value::I32(-1)])?;
+        assert_eq!(result, Some(Value::I32(-1)));
+        let result = runtime.call("size".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(2)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_grow_zeroed() -> Result<()> {
+        let code = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+  (func (export "load") (param i32) (result i64) (i64.load (local.get 0)))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+
+        // leave dirty bytes in the spare capacity of the buffer
+        {
+            let mut memory = memory.borrow_mut();
+            memory.data.resize(2 * PAGE_SIZE as usize, 0xff);
+            memory.data.truncate(PAGE_SIZE as usize);
+            memory.data.fill(0xff);
+        }
+
+        let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        for addr in [PAGE_SIZE, PAGE_SIZE + 4096, 2 * PAGE_SIZE - 8] {
+            let result = runtime.call("load".into(), vec![Value::I32(addr as i32)])?;
+            assert_eq!(result, Some(Value::I64(0)), "addr: {addr}");
+        }
+        assert!(memory.borrow().data[PAGE_SIZE as usize..]
+            .iter()
+            .all(|b| *b == 0));
+
+        // the old region is kept as is
+        let result = runtime.call("load".into(), vec![Value::I32(PAGE_SIZE as i32 - 8)])?;
+        assert_eq!(result, Some(Value::I64(-1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn table_set() -> Result<()> {
+        let code = r#"
+(module
+  (type $t (func (result i32)))
+  (table 2 funcref)
+  (func (export "forty-two") (result i32) (i32.const 42))
+  (func (export "dispatch") (param i32) (result i32)
+    (call_indirect (type $t) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let Some(ExternalVal::Func(idx)) = runtime
+            .store
+            .borrow()
+            .module
+            .export("forty-two")
+            .map(|export| export.desc.clone())
+        else {
+            panic!("function must be exported");
+        };
+
+        runtime.table_set(0, 1, Value::FuncRef(Some(idx)))?;
+        let result = runtime.call("dispatch".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(42)));
+
+        // the null reference clears the entry
+        runtime.table_set(0, 1, Value::FuncRef(None))?;
+        let result = runtime.call("dispatch".into(), vec![Value::I32(1)]);
+        assert_eq!(result.unwrap_err().to_string(), "uninitialized element 1");
+
+        let tests = [
+            (
+                0,
+                2,
+                Value::FuncRef(Some(idx)),
+                "out of bounds table access",
+            ),
+            (
+                1,
+                0,
+                Value::FuncRef(Some(idx)),
+                "not found table by index: 1",
+            ),
+            (
+                0,
+                0,
+                Value::FuncRef(Some(2)),
+                "not found function by index: 2",
+            ),
+            (
+                0,
+                0,
+                Value::I32(0),
+                "table element type mismatch, expected FuncRef, got I32",
+            ),
+        ];
+        for (table_idx, entry_idx, value, expected) in tests {
+            let result = runtime.table_set(table_idx, entry_idx, value);
+            assert_eq!(result.unwrap_err().to_string(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_arity() -> Result<()> {
+        let code = r#"
+(module
+  ;; the locals must be in the order of the arguments
+  (func $f (param i32 i64 f32) (result i64 i32)
+    (i64.sub (local.get 1) (i64.extend_i32_s (local.get 0)))
+    (i32.trunc_f32_s (local.get 2))
+  )
+  (func (export "call") (result i32 i64 i32 i32)
+    ;; the operand below the arguments must be kept
+    (i32.const 99)
+    (call $f (i32.const 3) (i64.const 10) (f32.const 7.5))
+    (i32.const 1)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call_multi("call".into(), vec![])?;
+        assert_eq!(
+            result,
+            vec![Value::I32(99), Value::I64(7), Value::I32(7), Value::I32(1)]
+        );
+        assert!(runtime.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_value() -> Result<()> {
+        let code = r#"
+(module
+  (func $pair (result i32 f64)
+    (i32.const 42)
+    (f64.const 2.5)
+  )
+  (func (export "pair") (result i32 f64)
+    (call $pair)
+  )
+  (func (export "swap") (param i32 i64) (result i64 i32)
+    (i32.const 0)
+    (drop)
+    (return (local.get 1) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let results = runtime.call_multi("pair".into(), vec![])?;
+        assert_eq!(results, vec![Value::I32(42), Value::F64(2.5)]);
+        assert_eq!(
+            format_typed_values(&results, Value::to_string),
+            "[i32:42, f64:2.5]"
+        );
+        assert!(runtime.stack.is_empty());
+
+        let results = runtime.call_multi("swap".into(), vec![Value::I32(1), Value::I64(2)])?;
+        assert_eq!(
+            format_typed_values(&results, Value::to_string),
+            "[i64:2, i32:1]"
+        );
+        assert!(runtime.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn zero_locals() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "locals") (param i32) (result i32 i64 f32 f64 i32)
+    (local i64 f32)
+    (local f64 i32)
+    (local.get 1)
+    (drop)
+    (local.get 0)
+    (local.get 1)
+    (local.get 2)
+    (local.get 3)
+    (local.get 4)
+  )
+  (func (export "set") (result i64)
+    (local i64)
+    (local.set 0 (i64.const 1))
+    (local.get 0)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let results = runtime.call_multi("locals".into(), vec![Value::I32(7)])?;
+        assert_eq!(
+            results,
+            vec![
+                Value::I32(7),
+                Value::I64(0),
+                Value::F32(0.0),
+                Value::F64(0.0),
+                Value::I32(0),
+            ]
+        );
+
+        // locals are initialized per call
+        runtime.call("set".into(), vec![])?;
+        let result = runtime.call("set".into(), vec![])?;
+        assert_eq!(result, Some(Value::I64(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn demote_and_promote() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "f32.demote_f64") (param f64) (result f32)
+    (f32.demote_f64 (local.get 0))
+  )
+  (func (export "f64.promote_f32") (param f32) (result f64)
+    (f64.promote_f32 (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        // rounds to nearest, ties to even
+        let tests = [
+            (1.0 + 2f64.powi(-28), 1.0),
+            (1.0 + 2f64.powi(-24), 1.0),
+            (1.0 + 3.0 * 2f64.powi(-24), 1.0 + 2f32.powi(-22)),
+            (f64::MAX, f32::INFINITY),
+            (f64::INFINITY, f32::INFINITY),
+            (f64::NEG_INFINITY, f32::NEG_INFINITY),
+        ];
+        for (arg, expected) in tests {
+            let result = runtime.call("f32.demote_f64".into(), vec![Value::F64(arg)])?;
+            assert_eq!(result, Some(Value::F32(expected)), "demote {arg}");
+        }
+        let nan = f64::from_bits(0x7FF4000000000001);
+        let result = runtime.call("f32.demote_f64".into(), vec![Value::F64(nan)])?;
+        let Some(Value::F32(f)) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert_eq!(f.to_bits(), 0x7FC00000);
+
+        // promotion is exact
+        let tests = [
+            (0.1f32, 0.1f32 as f64),
+            (f32::MIN_POSITIVE, f32::MIN_POSITIVE as f64),
+            (f32::NEG_INFINITY, f64::NEG_INFINITY),
+        ];
+        for (arg, expected) in tests {
+            let result = runtime.call("f64.promote_f32".into(), vec![Value::F32(arg)])?;
+            assert_eq!(result, Some(Value::F64(expected)), "promote {arg}");
+        }
+        let result = runtime.call("f64.promote_f32".into(), vec![Value::F32(0.1)])?;
+        assert_eq!(result, Some(Value::F64(0.10000000149011612)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reinterpret() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "i32.reinterpret_f32") (param f32) (result i32)
+    (i32.reinterpret_f32 (local.get 0))
+  )
+  (func (export "f32.reinterpret_i32") (param i32) (result f32)
+    (f32.reinterpret_i32 (local.get 0))
+  )
+  (func (export "i64.reinterpret_f64") (param f64) (result i64)
+    (i64.reinterpret_f64 (local.get 0))
+  )
+  (func (export "f64.reinterpret_i64") (param i64) (result f64)
+    (f64.reinterpret_i64 (local.get 0))
+  )
+  (func (export "f32.nan_roundtrip") (result i32)
+    (i32.reinterpret_f32 (f32.const nan:0x200001))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("i32.reinterpret_f32".into(), vec![Value::F32(1.0)])?;
+        assert_eq!(result, Some(Value::I32(0x3F800000)));
+        let result = runtime.call("f32.reinterpret_i32".into(), vec![Value::I32(0x3F800000)])?;
+        assert_eq!(result, Some(Value::F32(1.0)));
+
+        let result = runtime.call("i64.reinterpret_f64".into(), vec![Value::F64(1.0)])?;
+        assert_eq!(result, Some(Value::I64(0x3FF0000000000000)));
+        let args = vec![Value::I64(0x3FF0000000000000)];
+        let result = runtime.call("f64.reinterpret_i64".into(), args)?;
+        assert_eq!(result, Some(Value::F64(1.0)));
+
+        // NaN bit patterns are preserved
+        let result = runtime.call("f32.nan_roundtrip".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(0x7FA00001)));
+        for bits in [0x7FA00001_u32, 0xFFC00000] {
+            let result =
+                runtime.call("f32.reinterpret_i32".into(), vec![Value::I32(bits as i32)])?;
+            let Some(Value::F32(f)) = result else {
+                panic!("unexpected result: {result:?}");
+            };
+            assert_eq!(f.to_bits(), bits);
+            let result = runtime.call("i32.reinterpret_f32".into(), vec![Value::F32(f)])?;
+            assert_eq!(result, Some(Value::I32(bits as i32)));
+        }
+        let bits = 0x7FF4000000000001_u64;
+        let result = runtime.call("f64.reinterpret_i64".into(), vec![Value::I64(bits as i64)])?;
+        let Some(Value::F64(f)) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert_eq!(f.to_bits(), bits);
+
+        Ok(())
+    }
+
+    #[test]
+    fn step() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "add") (result i32)
+    (i32.const 1)
+    (i32.const 2)
+    (i32.add)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        runtime.prepare_call("add".into(), vec![])?;
+        assert!(runtime.stack_values().is_empty());
+        assert!(runtime.step()?);
+        assert_eq!(runtime.stack_values(), &[Value::I32(1)]);
+        assert!(runtime.step()?);
+        assert_eq!(runtime.stack_values(), &[Value::I32(1), Value::I32(2)]);
+        assert!(runtime.step()?);
+        assert_eq!(runtime.stack_values(), &[Value::I32(3)]);
+
+        // end of function
+        assert!(runtime.step()?);
+        assert!(runtime.call_stack.is_empty());
+        assert!(!runtime.step()?);
+        assert_eq!(runtime.stack_values(), &[Value::I32(3)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_init() -> Result<()> {
+        let code = r#"
+(module
+  (memory (export "memory") 1)
+  (data "hello")
+  (func (export "init") (param i32 i32 i32)
+    (memory.init 0 (local.get 0) (local.get 1) (local.get 2))
+  )
+  (func (export "drop")
+    (data.drop 0)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+
+        let args = vec![Value::I32(10), Value::I32(1), Value::I32(3)];
+        runtime.call("init".into(), args)?;
+        assert_eq!(&memory.borrow().data[10..13], b"ell");
+
+        // out of bounds of the data segment
+        let args = vec![Value::I32(0), Value::I32(3), Value::I32(3)];
+        assert!(runtime.call("init".into(), args).is_err());
+
+        // the data segment is empty after dropped
+        runtime.call("drop".into(), vec![])?;
+        let args = vec![Value::I32(0), Value::I32(0), Value::I32(1)];
+        assert!(runtime.call("init".into(), args).is_err());
+        let args = vec![Value::I32(0), Value::I32(0), Value::I32(0)];
+        assert!(runtime.call("init".into(), args).is_ok());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "wasi")]
+    #[test]
+    fn builder() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "args_sizes_get"
+    (func $args_sizes_get (param i32 i32) (result i32)))
+  (memory 1 4)
+  (func (export "args") (result i32)
+    (call $args_sizes_get (i32.const 0) (i32.const 4))
+  )
+  (func (export "loop")
+    (loop $l (br $l))
+  )
+  (func $rec (export "rec") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else (call $rec (i32.sub (local.get 0) (i32.const 1))))
+    )
+  )
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::builder()
+            .wasi(WasiSnapshotPreview1::default())
+            .fuel(10_000)
+            .max_call_depth(100)
+            .max_memory_pages(2)
+            .build_from_bytes(&wasm)?;
+
+        let result = runtime.call("args".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(0)));
+
+        let result = runtime.call("rec".into(), vec![Value::I32(50)])?;
+        assert_eq!(result, Some(Value::I32(0)));
+        let result = runtime.call("rec".into(), vec![Value::I32(200)]);
+        assert_eq!(result.unwrap_err().to_string(), "call stack exhausted");
+        runtime.call_stack.clear();
+        runtime.stack.clear();
+
+        let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(-1)));
+
+        let result = runtime.call("loop".into(), vec![]);
+        assert_eq!(result.unwrap_err().to_string(), "all fuel consumed");
+        assert_eq!(runtime.fuel, Some(0));
+
+        // the initial memory must not exceed the limit
+        let result = Runtime::builder()
+            .max_memory_pages(0)
+            .build_from_bytes(&wasm);
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_memory() -> Result<()> {
+        let code = r#"
+(module
+  (memory $m0 1)
+  (memory $m1 2)
+  (func (export "store1") (param i32 i32)
+    (i32.store $m1 (local.get 0) (local.get 1))
+  )
+  (func (export "load0") (param i32) (result i32)
+    (i32.load $m0 (local.get 0))
+  )
+  (func (export "load1") (param i32) (result i32)
+    (i32.load $m1 (local.get 0))
+  )
+  (func (export "size1") (result i32)
+    (memory.size $m1)
+  )
+  (func (export "grow1") (param i32) (result i32)
+    (memory.grow $m1 (local.get 0))
+  )
+  (func (export "copy") (param i32 i32 i32)
+    (memory.copy $m0 $m1 (local.get 0) (local.get 1) (local.get 2))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        let args = vec![Value::I32(8), Value::I32(42)];
+        runtime.call("store1".into(), args)?;
+        let result = runtime.call("load1".into(), vec![Value::I32(8)])?;
+        assert_eq!(result, Some(Value::I32(42)));
+        let result = runtime.call("load0".into(), vec![Value::I32(8)])?;
+        assert_eq!(result, Some(Value::I32(0)));
+
+        let result = runtime.call("size1".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        let result = runtime.call("grow1".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        let result = runtime.call("size1".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(3)));
+
+        let args = vec![Value::I32(0), Value::I32(8), Value::I32(4)];
+        runtime.call("copy".into(), args)?;
+        let result = runtime.call("load0".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I32(42)));
+
+        // memory 0 has only one page
+        let args = vec![Value::I32(65535), Value::I32(8), Value::I32(4)];
+        let result = runtime.call("copy".into(), args);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "out of bounds memory access"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn end_at_function_boundary() -> Result<()> {
+        let code = r#"
+(module
+  (func $inner (result i32)
+    (block (result i32)
+      (block (result i32)
+        (i32.const 1)
+      )
+    )
+  )
+  (func (export "nested") (result i32)
+    (block (result i32)
+      (i32.add (call $inner) (i32.const 1))
+    )
+  )
+  (func (export "if_else") (param i32) (result i32)
+    (if (result i32) (local.get 0)
+      (then (i32.const 1))
+      (else (i32.const 2))
+    )
+  )
+  (func (export "loop") (param i32) (result i32)
+    (loop $l (result i32)
+      (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+      (br_if $l (local.get 0))
+      (local.get 0)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        let result = runtime.call("nested".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        let result = runtime.call("if_else".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        let result = runtime.call("if_else".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        let result = runtime.call("loop".into(), vec![Value::I32(3)])?;
+        assert_eq!(result, Some(Value::I32(0)));
+
+        assert!(runtime.call_stack.is_empty());
+        assert!(runtime.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn on_memory_grow() -> Result<()> {
+        let code = r#"
+(module
+  (memory 1 4)
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        let events = Rc::new(RefCell::new(vec![]));
+        let recorded = Rc::clone(&events);
+        runtime.on_memory_grow(Box::new(move |old, new| {
+            recorded.borrow_mut().push((old, new));
+        }));
+
+        let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        let result = runtime.call("grow".into(), vec![Value::I32(2)])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        // failed grow must not fire the hook
+        let result = runtime.call("grow".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(-1)));
+
+        assert_eq!(*events.borrow(), vec![(1, 2), (2, 4)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reentrant_call() -> Result<()> {
+        struct Callback;
+
+        impl Importer for Callback {
+            fn name(&self) -> &str {
+                "env"
+            }
+
+            fn invoke(
+                &self,
+                store: Rc<RefCell<Store>>,
+                func: ExternalFuncInst,
+                args: Vec<Value>,
+            ) -> Result<Option<Value>> {
+                assert_eq!(func.field, "callback");
+                let mut runtime = Runtime::with_store(store);
+                runtime.call("inc".into(), args)
+            }
+        }
+
+        let code = r#"
+(module
+  (import "env" "callback" (func $callback (param i32) (result i32)))
+  (global $g (mut i32) (i32.const 0))
+  (func (export "inc") (param i32) (result i32)
+    (global.set $g (i32.add (global.get $g) (local.get 0)))
+    (global.get $g)
+  )
+  (func (export "run") (result i32)
+    (local $x i32)
+    (local.set $x (i32.const 100))
+    (global.set $g (i32.const 10))
+    (i32.add
+      (i32.add (call $callback (i32.const 5)) (global.get $g))
+      (local.get $x)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, Some(vec![Box::new(Callback)]))?;
+
+        let result = runtime.call("run".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(130)));
+        assert!(runtime.call_stack.is_empty());
+        assert!(runtime.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn br_out_of_loop_with_extra_values() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "br") (result i32 i32)
+    (i32.const 7)
+    (block $out (result i32)
+      (loop $l
+        (i32.const 1)
+        (i32.const 2)
+        (block
+          (i64.const 3)
+          (br $out (i32.const 42))
+        )
+        (br $l)
+      )
+      (i32.const 0)
+    )
+  )
+  (func (export "br_if") (param i32) (result i32 i32)
+    (i32.const 7)
+    (block $out (result i32)
+      (loop $l
+        (f32.const 1)
+        (f64.const 2)
+        (drop (br_if $out (i32.const 42) (local.get 0)))
+        (drop)
+        (drop)
+      )
+      (i32.const 0)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        let result = runtime.call_multi("br".into(), vec![])?;
+        assert_eq!(result, vec![Value::I32(7), Value::I32(42)]);
+        assert!(runtime.stack.is_empty());
+
+        let result = runtime.call_multi("br_if".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, vec![Value::I32(7), Value::I32(42)]);
+        let result = runtime.call_multi("br_if".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, vec![Value::I32(7), Value::I32(0)]);
+        assert!(runtime.stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn global_init() -> Result<()> {
+        let code = r#"
+(module
+  (global $a i32 (i32.const -1))
+  (global $b i64 (i64.const 0x1_0000_0000))
+  (global $c f32 (f32.const 1.5))
+  (global $d (mut f64) (f64.const 3.14))
+  (func (export "get") (result i32 i64 f32 f64)
+    (global.get $a)
+    (global.get $b)
+    (global.get $c)
+    (global.get $d)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        let result = runtime.call_multi("get".into(), vec![])?;
+        assert_eq!(
+            result,
+            vec![
+                Value::I32(-1),
+                Value::I64(1 << 32),
+                Value::F32(1.5),
+                Value::F64(3.14)
+            ]
+        );
+
+        #[rustfmt::skip]
+        let wasm = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x06, 0x06, 0x01, 0x7c, 0x00, 0x41, 0x01, 0x0b, // global section, f64 initialized by i32.const
+        ];
+        let result = Runtime::from_bytes(wasm, None);
+        assert_eq!(
+            result.err().map(|err| err.to_string()),
+            Some("global initializer type mismatch, expected F64, got I32".into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_global() -> Result<()> {
+        let code = r#"
+(module
+  (global $g (export "g") (mut i32) (i32.const 1))
+  (global $c (export "c") i32 (i32.const 2))
+  (func (export "get") (result i32)
+    (global.get $g)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        runtime.set_global("g".into(), Value::I32(42))?;
+        let result = runtime.call("get".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(42)));
+
+        let result = runtime.set_global("g".into(), Value::I64(42));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "global type mismatch, expected I32, got I64"
+        );
+        let result = runtime.set_global("c".into(), Value::I32(42));
+        assert_eq!(result.unwrap_err().to_string(), "global is immutable: c");
+        let result = runtime.set_global("get".into(), Value::I32(42));
+        assert!(result.is_err());
+
+        let result = runtime.call("get".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(42)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_tables() -> Result<()> {
+        let code = r#"
+(module
+  (type $t (func (result i32)))
+  (table $t0 2 funcref)
+  (table $t1 2 funcref)
+  (elem (table $t0) (i32.const 0) func $one $two)
+  (elem (table $t1) (i32.const 0) func $three $four)
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func $three (result i32) (i32.const 3))
+  (func $four (result i32) (i32.const 4))
+  (func (export "call0") (param i32) (result i32)
+    (call_indirect $t0 (type $t) (local.get 0))
+  )
+  (func (export "call1") (param i32) (result i32)
+    (call_indirect $t1 (type $t) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        for (name, idx, expected) in [
+            ("call0", 0, 1),
+            ("call0", 1, 2),
+            ("call1", 0, 3),
+            ("call1", 1, 4),
+        ] {
+            let result = runtime.call(name.into(), vec![Value::I32(idx)])?;
+            assert_eq!(result, Some(Value::I32(expected)), "{name}({idx})");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn extend_i32() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "i64.extend_i32_s") (param i32) (result i64)
+    (i64.extend_i32_s (local.get 0))
+  )
+  (func (export "i64.extend_i32_u") (param i32) (result i64)
+    (i64.extend_i32_u (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        for (arg, signed, unsigned) in [
+            (-1, -1, 0xFFFFFFFF),
+            (i32::MIN, i32::MIN as i64, 0x80000000),
+            (i32::MAX, i32::MAX as i64, i32::MAX as i64),
+            (0, 0, 0),
+        ] {
+            let result = runtime.call("i64.extend_i32_s".into(), vec![Value::I32(arg)])?;
+            assert_eq!(result, Some(Value::I64(signed)), "extend_i32_s {arg}");
+            let result = runtime.call("i64.extend_i32_u".into(), vec![Value::I32(arg)])?;
+            assert_eq!(result, Some(Value::I64(unsigned)), "extend_i32_u {arg}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn i64_const_range() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "min") (result i64) (i64.const -9223372036854775808))
+  (func (export "max") (result i64) (i64.const 9223372036854775807))
+  (func (export "minus_one") (result i64) (i64.const -1))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        for (name, expected) in [("min", i64::MIN), ("max", i64::MAX), ("minus_one", -1)] {
+            let result = runtime.call(name.into(), vec![])?;
+            assert_eq!(result, Some(Value::I64(expected)), "{name}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_with_invalid_args() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("add".into(), vec![Value::I32(1)]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "expected 2 args of [i32,i32], got 1"
+        );
+        let result = runtime.call("add".into(), vec![Value::I32(1), Value::F32(2.0)]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "expected args of [i32,i32], got [i32,f32]"
+        );
+        assert!(runtime.stack.is_empty());
+
+        let result = runtime.call("add".into(), vec![Value::I32(1), Value::I32(2)])?;
+        assert_eq!(result, Some(Value::I32(3)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_with_limit() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "count") (param i32) (result i32)
+    (local i32)
+    (loop $l
+      (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+      (br_if $l (i32.lt_u (local.get 1) (local.get 0)))
+    )
+    (local.get 1)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::builder().fuel(10_000).build_from_bytes(&wasm)?;
+
+        let result = runtime.call_with_limit("count".into(), vec![Value::I32(10)], 1_000)?;
+        assert_eq!(result, Some(Value::I32(10)));
+        assert_eq!(runtime.call_fuel, None);
+
+        let result = runtime.call_with_limit("count".into(), vec![Value::I32(1_000)], 1_000);
+        assert_eq!(result.unwrap_err().to_string(), "all fuel consumed");
+        assert_eq!(runtime.call_fuel, None);
+
+        // the global fuel is still available after the limited call is trapped
+        assert!(runtime.fuel.unwrap() > 0);
+        let result = runtime.call("count".into(), vec![Value::I32(100)])?;
+        assert_eq!(result, Some(Value::I32(100)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_little_endian() -> Result<()> {
+        let code = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "store")
+    (i32.store (i32.const 0) (i32.const 0x01020304))
+    (i64.store (i32.const 8) (i64.const 0x0102030405060708))
+    (f32.store (i32.const 16) (f32.const 1))
+  )
+  (func (export "load8") (param i32) (result i32)
+    (i32.load8_u (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+
+        // the byte layout must not depend on the endianness of the host
+        runtime.call("store".into(), vec![])?;
+        let data = memory.borrow().data[..20].to_vec();
+        assert_eq!(&data[0..4], &[0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(
+            &data[8..16],
+            &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+        );
+        assert_eq!(&data[16..20], &[0x00, 0x00, 0x80, 0x3f]);
+
+        let result = runtime.call("load8".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I32(0x04)));
+        let result = runtime.call("load8".into(), vec![Value::I32(15)])?;
+        assert_eq!(result, Some(Value::I32(0x01)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_of_each_kind() -> Result<()> {
+        let code = r#"
+(module
+  (func (result i32) (i32.const 0))
+  (func (result i32) (i32.const 1))
+  (table 1 funcref)
+  (table 2 funcref)
+  (memory 1)
+  (global i32 (i32.const 2))
+  (global i32 (i32.const 3))
+  (export "func" (func 1))
+  (export "table" (table 1))
+  (export "memory" (memory 0))
+  (export "global" (global 1))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let descs: Vec<_> = module
+            .export_section
+            .iter()
+            .flatten()
+            .map(|export| (export.name.as_str(), export.desc.clone()))
+            .collect();
+        assert_eq!(
+            descs,
+            vec![
+                ("func", ExportDesc::Func(1)),
+                ("table", ExportDesc::Table(1)),
+                ("memory", ExportDesc::Memory(0)),
+                ("global", ExportDesc::Global(1)),
+            ]
+        );
+
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let Exports::Func(FuncInst::Internal(func)) = runtime.exports("func".into())? else {
+            panic!("func must be exported");
+        };
+        assert!(matches!(
+            func.code.body.insts()?[0],
+            Instruction::I32Const(1)
+        ));
+        let Exports::Table(table) = runtime.exports("table".into())? else {
+            panic!("table must be exported");
+        };
+        assert_eq!(table.borrow().funcs.len(), 2);
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+        assert_eq!(memory.borrow().data.len(), PAGE_SIZE as usize);
+        let Exports::Global(global) = runtime.exports("global".into())? else {
+            panic!("global must be exported");
+        };
+        assert_eq!(global.borrow().value, Value::I32(3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn import_resolver() -> Result<()> {
+        let code = r#"
+(module
+  (import "env" "double" (func $double (param i32) (result i32)))
+  (func (export "call") (param i32) (result i32)
+    (call $double (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let resolved = Rc::new(RefCell::new(vec![]));
+        let log = Rc::clone(&resolved);
+        let mut runtime = Runtime::builder()
+            .resolver(move |module, field, func_type| {
+                log.borrow_mut().push(format!("{module}.{field}"));
+                assert_eq!(func_type.params, vec![ValueType::I32]);
+                let double: HostFunc = Rc::new(|_store, args| {
+                    let value: i32 = args[0].clone().try_into()?;
+                    Ok(Some(Value::I32(value * 2)))
+                });
+                (field == "double").then_some(double)
+            })
+            .build_from_bytes(&wasm)?;
+        assert_eq!(*resolved.borrow(), vec!["env.double"]);
+
+        let result = runtime.call("call".into(), vec![Value::I32(21)])?;
+        assert_eq!(result, Some(Value::I32(42)));
+
+        // unlinkable if the resolver doesn't provide the function
+        let result = Runtime::builder()
+            .resolver(|_, _, _| None)
+            .build_from_bytes(&wasm);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "unknown import: env.double"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn trap_location() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "ok") (result i32) (i32.const 0))
+  (func $div (param i32) (result i32)
+    (i32.div_s (i32.const 1) (local.get 0))
+  )
+  (func (export "div") (param i32) (result i32)
+    (call $div (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let err = runtime.call("div".into(), vec![Value::I32(0)]).unwrap_err();
+        assert_eq!(err.to_string(), "integer divide by zero");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::IntegerDivideByZero)
+        ));
+        let location = TrapLocation {
+            func_idx: 1,
+            offset: 2,
+        };
+        assert_eq!(
+            err.downcast_ref::<Trapped>()
+                .map(|trapped| trapped.location),
+            Some(location)
+        );
+        assert_eq!(runtime.last_trap, Some(location));
+
+        runtime.call("ok".into(), vec![])?;
+        assert_eq!(runtime.last_trap, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn on_trap() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "div") (param i32) (result i32)
+    (i32.div_s (i32.const 1) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let traps = Rc::new(RefCell::new(vec![]));
+        let recorded = Rc::clone(&traps);
+        runtime.on_trap(Box::new(move |trap: &Trap| {
+            let kind = trap.kind().map(|e| matches!(e, Error::IntegerDivideByZero));
+            recorded.borrow_mut().push((kind, trap.location));
+        }));
+
+        let result = runtime.call("div".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        assert!(traps.borrow().is_empty());
+
+        let result = runtime.call("div".into(), vec![Value::I32(0)]);
+        assert!(result.is_err());
+        let location = TrapLocation {
+            func_idx: 0,
+            offset: 2,
+        };
+        assert_eq!(*traps.borrow(), vec![(Some(true), Some(location))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_for() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "count") (param i32) (result i32)
+    (local i32)
+    (loop $l
+      (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+      (br_if $l (i32.lt_u (local.get 1) (local.get 0)))
+    )
+    (local.get 1)
+  )
+  (func (export "div") (param i32) (result i32)
+    (i32.div_s (i32.const 1) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        runtime.prepare_call("count".into(), vec![Value::I32(1000)])?;
+        let mut slices = 1;
+        let results = loop {
+            match runtime.run_for(100)? {
+                RunState::Paused => slices += 1,
+                RunState::Done(results) => break results,
+            }
+        };
+        assert_eq!(results, vec![Value::I32(1000)]);
+        assert!(slices > 1);
+        assert!(runtime.call_stack.is_empty());
+
+        runtime.prepare_call("div".into(), vec![Value::I32(0)])?;
+        let result = runtime.run_for(100);
+        assert_eq!(result.unwrap_err().to_string(), "integer divide by zero");
+        assert!(runtime.call_stack.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_with_stats() -> Result<()> {
+        let code = r#"
+(module
+  (func $sum (export "sum") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else
+        (i32.add
+          (local.get 0)
+          (call $sum (i32.sub (local.get 0) (i32.const 1)))
+        )
+      )
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let (result, stats) = runtime.call_with_stats("sum".into(), vec![Value::I32(10)])?;
+        assert_eq!(result, Some(Value::I32(55)));
+        // sum(10) calls itself down to sum(0)
+        assert_eq!(stats.max_call_depth, 11);
+        assert!(stats.max_stack_size >= 11);
+        assert!(stats.instructions > 0);
+        assert_eq!(runtime.stats, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_all_exports_with_zeros() -> Result<()> {
+        let code = r#"
+(module
+  (memory 1)
+  (func (export "add") (param i32 i64) (result i64)
+    (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1))
+  )
+  (func (export "div") (param i32) (result i32)
+    (i32.div_s (i32.const 1) (local.get 0))
+  )
+  (export "memory" (memory 0))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let results = runtime.call_all_exports_with_zeros();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results["add"].as_ref().unwrap(), &vec![Value::I64(0)]);
+        assert_eq!(
+            results["div"].as_ref().unwrap_err().to_string(),
+            "integer divide by zero"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_access_with_offset() -> Result<()> {
+        let code = r#"
+(module
+  (memory 1)
+  (func (export "load") (param i32) (result i32)
+    (i32.load offset=4294967295 (local.get 0))
+  )
+  (func (export "load_last") (param i32) (result i64)
+    (i64.load offset=65528 (local.get 0))
+  )
+  (func (export "store") (param i32)
+    (i32.store offset=4294967295 (local.get 0) (i32.const 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        // NOTE: the effective address is base + offset, which can exceed u32
+        for base in [0, 1, -1] {
+            let result = runtime.call("load".into(), vec![Value::I32(base)]);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "out of bounds memory access"
+            );
+            let result = runtime.call("store".into(), vec![Value::I32(base)]);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "out of bounds memory access"
+            );
+        }
+
+        // the whole width of the access must be in bounds
+        let result = runtime.call("load_last".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I64(0)));
+        let result = runtime.call("load_last".into(), vec![Value::I32(1)]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "out of bounds memory access"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip_unreachable_code() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "return") (result i32)
+    i32.const 1
+    return
+    unreachable
+  )
+  (func (export "br") (result i32)
+    (block (result i32)
+      i32.const 2
+      br 0
+      unreachable
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("return".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        let result = runtime.call("br".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(2)));
+
+        Ok(())
+    }
+
+    // NOTE: run with `cargo test --no-default-features` to check the core works without wasi
+    #[cfg(not(feature = "wasi"))]
+    #[test]
+    fn without_wasi() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        let result = runtime.call("add".into(), vec![Value::I32(1), Value::I32(2)])?;
+        assert_eq!(result, Some(Value::I32(3)));
+
+        // modules importing wasi can't be instantiated without importers
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        assert!(Runtime::from_bytes(wasm, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_funcref() -> Result<()> {
+        let code = r#"
+(module
+  (func $a)
+  (func $b)
+  (elem declare func $a $b)
+  (func (export "select") (param i32) (result funcref)
+    (select (result funcref) (ref.func $a) (ref.func $b) (local.get 0))
+  )
+  (func (export "is_null") (param i32) (result i32)
+    (ref.is_null (select (result funcref) (ref.null func) (ref.func $a) (local.get 0)))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("select".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::FuncRef(Some(0))));
+        let result = runtime.call("select".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::FuncRef(Some(1))));
+
+        let result = runtime.call("is_null".into(), vec![Value::I32(1)])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        let result = runtime.call("is_null".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Value::I32(0)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn max_table_elements() -> Result<()> {
+        let code = r#"
+(module
+  (table 1 10 funcref)
+  (elem (i32.const 0) $f)
+  (func $f (result i32) (i32.const 42))
+  (func (export "grow") (param i32) (result i32)
+    (table.grow (ref.func $f) (local.get 0))
+  )
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (result i32) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+        runtime.set_max_table_elements(4);
+
+        // NOTE: the module declares max 10, but the host limit is applied
+        for (n, expected) in [(2, 1), (2, -1), (1, 3), (1, -1), (0, 4)] {
+            let result = runtime.call("grow".into(), vec![Value::I32(n)])?;
+            assert_eq!(result, Some(Value::I32(expected)), "grow {n}");
+        }
+        let result = runtime.call("call".into(), vec![Value::I32(3)])?;
+        assert_eq!(result, Some(Value::I32(42)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn table_grow_without_max() -> Result<()> {
+        let code = r#"
+(module
+  (table 0 funcref)
+  (func (export "grow") (param i32) (result i32)
+    (table.grow (ref.null func) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        // NOTE: the table without max is capped, instead of growing up to 2^32-1 elements
+        let max = MAX_TABLE_ELEMENTS as i32;
+        for (n, expected) in [(2, 0), (-1, -1), (max - 1, -1), (1, 2)] {
+            let result = runtime.call("grow".into(), vec![Value::I32(n)])?;
+            assert_eq!(result, Some(Value::I32(expected)), "grow {n}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_as_function_body() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "block") (param i32) (result i32)
+    (block (result i32)
+      (i32.mul (local.get 0) (i32.const 3))
+    )
+  )
+  (func (export "br") (param i32) (result i32)
+    (block (result i32)
+      (i32.const 1)
+      (i32.const 2)
+      (br 0 (local.get 0))
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let results = runtime.call_multi("block".into(), vec![Value::I32(5)])?;
+        assert_eq!(results, vec![Value::I32(15)]);
+        // NOTE: the operands under the branch value are discarded
+        let results = runtime.call_multi("br".into(), vec![Value::I32(7)])?;
+        assert_eq!(results, vec![Value::I32(7)]);
+        assert!(runtime.stack_values().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn block_result_fallthrough() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "fallthrough") (param i32) (result i32)
+    (i32.const 100)
+    (block (result i32)
+      (local.set 0 (i32.mul (local.get 0) (i32.const 2)))
+      (i32.add (local.get 0) (i32.const 1))
+    )
+    i32.add
+  )
+  (func (export "nested") (result i32)
+    (block (result i32)
+      (block (result i32)
+        (i32.const 7)
+      )
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("fallthrough".into(), vec![Value::I32(5)])?;
+        assert_eq!(result, Some(Value::I32(111)));
+        let result = runtime.call("nested".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(7)));
+        assert!(runtime.stack_values().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ref_func() -> Result<()> {
+        let code = r#"
+(module
+  (func $f (result i32) (i32.const 1))
+  (elem declare func $f)
+  (func (export "ref") (result funcref)
+    (ref.func $f)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call("ref".into(), vec![])?;
+        assert_eq!(result, Some(Value::FuncRef(Some(0))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn funcref_operand_type_mismatch() -> Result<()> {
+        // NOTE: this module is invalid, but it is not validated before execution
+        let code = r#"
+(module
+  (memory 1)
+  (func $f)
+  (elem declare func $f)
+  (func (export "br_if")
+    (br_if 0 (ref.func $f))
+  )
+  (func (export "add") (result i32)
+    (i32.add (i32.const 1) (ref.func $f))
+  )
+  (func (export "store")
+    (i32.store (ref.func $f) (i32.const 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let err = runtime.call("br_if".into(), vec![]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::TypeMismatch(ValueType::I32, Value::FuncRef(Some(0))))
+        ));
+        let err = runtime.call("add".into(), vec![]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::TypeMismatchOperands(
+                Value::I32(1),
+                Value::FuncRef(Some(0))
+            ))
+        ));
+        let err = runtime.call("store".into(), vec![]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::TypeMismatch(ValueType::I32, Value::FuncRef(Some(0))))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn drop_ref() -> Result<()> {
+        let code = r#"
+(module
+  (func $f)
+  (elem declare func $f)
+  (func (export "drop") (result i32)
+    (drop (ref.func $f))
+    (i32.const 1)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        crate::validation::validate(&module)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        let result = runtime.call_multi("drop".into(), vec![])?;
+        assert_eq!(result, vec![Value::I32(1)]);
+        assert!(runtime.stack_values().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_indirect_empty_table() -> Result<()> {
+        let code = r#"
+(module
+  (type $t (func (result i32)))
+  (table 0 funcref)
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $t) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(wasm, None)?;
+
+        for idx in [0, 1, -1] {
+            let result = runtime.call("call".into(), vec![Value::I32(idx)]);
+            assert_eq!(result.unwrap_err().to_string(), "undefined element");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn overlapping_element_segments() -> Result<()> {
+        let code = r#"
+(module
+  (type $t (func (result i32)))
+  (table 4 funcref)
+  (elem (i32.const 0) func $one $two $three)
+  (elem (i32.const 1) func $four)
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func $three (result i32) (i32.const 3))
+  (func $four (result i32) (i32.const 4))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $t) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+
+        for (idx, expected) in [(0, 1), (1, 4), (2, 3)] {
+            let result = runtime.call("call".into(), vec![Value::I32(idx)])?;
+            assert_eq!(result, Some(Value::I32(expected)), "call({idx})");
+        }
+        let result = runtime.call("call".into(), vec![Value::I32(3)]);
+        assert_eq!(result.unwrap_err().to_string(), "uninitialized element 3");
+
+        let code = r#"
+(module
+  (table 2 funcref)
+  (elem (i32.const 1) func $f $f)
+  (func $f)
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let result = Runtime::from_bytes(&wasm, None);
+        assert_eq!(
+            result.err().map(|e| e.to_string()),
+            Some("out of bounds table access".into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn operand_stack_overflow() -> Result<()> {
+        // NOTE: these modules are invalid, but they are not validated before execution
+        let consts = "(i32.const 1)\n".repeat(200);
+        let code = format!(
+            r#"
+(module
+  (func (export "consts")
+    {consts}
+  )
+  (func $rec (export "rec")
+    (i32.const 1)
+    (call $rec)
+  )
+)
+            "#
+        );
+        let wasm = wat::parse_str(code)?;
+        let mut runtime = Runtime::builder()
+            .max_stack_size(100)
+            .build_from_bytes(&wasm)?;
+
+        for name in ["consts", "rec"] {
+            let result = runtime.call(name.into(), vec![]);
+            assert_eq!(result.unwrap_err().to_string(), "operand stack overflow");
+            assert!(runtime.stack.is_empty());
+        }
+
+        Ok(())
+    }
+
+    // exports the tables of the module "a" to the other modules
+    struct Exporter(Rc<RefCell<Store>>);
+
+    impl Importer for Exporter {
+        fn name(&self) -> &str {
+            "a"
+        }
+
+        fn invoke(
+            &self,
+            _store: Rc<RefCell<Store>>,
+            _func: ExternalFuncInst,
+            _args: Vec<Value>,
+        ) -> Result<Option<Value>> {
+            bail!("no functions are imported")
+        }
+
+        fn resolve_table(
+            &self,
+            _module: &str,
+            field: &str,
+        ) -> Result<Option<Rc<RefCell<InternalTableInst>>>> {
+            let Exports::Table(table) =
+                Runtime::with_store(Rc::clone(&self.0)).exports(field.into())?
+            else {
+                return Ok(None);
+            };
+            Ok(Some(table))
+        }
+    }
+
+    #[test]
+    fn shared_table() -> Result<()> {
+        let code = r#"
+(module
+  (type $t (func (result i32)))
+  (global $g (mut i32) (i32.const 40))
+  (table (export "tab") 2 funcref)
+  (elem (i32.const 0) $incr)
+  (func $incr (result i32)
+    (global.set $g (i32.add (global.get $g) (i32.const 2)))
+    (global.get $g)
+  )
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $t) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let mut a = Runtime::from_bytes(wasm, None)?;
+
+        let code = r#"
+(module
+  (type $t (func (result i32)))
+  (import "a" "tab" (table 2 funcref))
+  (global $g i32 (i32.const 7))
+  (elem (i32.const 1) $get)
+  (func $get (result i32) (global.get $g))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $t) (local.get 0))
+  )
+  (func (export "return-call") (param i32) (result i32)
+    (return_call_indirect (type $t) (local.get 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let exporter = Exporter(Rc::clone(&a.store));
+        let mut b = Runtime::from_bytes(wasm, Some(vec![Box::new(exporter)]))?;
+
+        // the function of a is executed with the global of a
+        let tests = [
+            ("b", "call", 0, 42),
+            ("b", "return-call", 0, 44),
+            ("a", "call", 0, 46),
+            // the element written by b is visible from a, and executed with the global of b
+            ("a", "call", 1, 7),
+            ("b", "call", 1, 7),
+        ];
+        for (module, name, idx, expected) in tests {
+            let runtime = if module == "a" { &mut a } else { &mut b };
+            let result = runtime.call(name.into(), vec![Value::I32(idx)])?;
+            assert_eq!(result, Some(Value::I32(expected)), "{module}.{name} {idx}");
+        }
+
+        // the table doesn't keep the store of b alive
+        drop(b);
+        let err = a.call("call".into(), vec![Value::I32(1)]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NotFoundStore(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_table_limits() -> Result<()> {
+        // each module counts down through the function of the other module
+        let code = r#"
+(module
+  (type $t (func (param i32) (result i32)))
+  (table (export "tab") 2 funcref)
+  (elem (i32.const 0) $down)
+  (func $down (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else
+        (i32.add
+          (call_indirect (type $t) (i32.sub (local.get 0) (i32.const 1)) (i32.const 1))
+          (i32.const 1)
+        )
+      )
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let a = Runtime::from_bytes(wasm, None)?;
+
+        let code = r#"
+(module
+  (type $t (func (param i32) (result i32)))
+  (import "a" "tab" (table 2 funcref))
+  (elem (i32.const 1) $down)
+  (func $down (export "down") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else
+        (i32.add
+          (call_indirect (type $t) (i32.sub (local.get 0) (i32.const 1)) (i32.const 0))
+          (i32.const 1)
+        )
+      )
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let exporter = Exporter(Rc::clone(&a.store));
+        let mut b = Runtime::from_bytes(wasm, Some(vec![Box::new(exporter)]))?;
+        let store = Rc::clone(&b.store);
+
+        let result = b.call("down".into(), vec![Value::I32(5)])?;
+        assert_eq!(result, Some(Value::I32(5)));
+
+        // NOTE: the frames of both modules are on the same call stack
+        b.max_call_depth = Some(10);
+        let err = b
+            .call("down".into(), vec![Value::I32(100)])
+            .expect_err("call depth must be limited");
+        assert!(
+            matches!(err.downcast_ref::<Error>(), Some(Error::CallStackExhausted)),
+            "{err}"
+        );
+        assert!(Rc::ptr_eq(&b.store, &store));
+        assert!(b.store_stack.is_empty());
+        let result = b.call("down".into(), vec![Value::I32(9)])?;
+        assert_eq!(result, Some(Value::I32(9)));
+
+        // the fuel is also consumed by the functions of the other module
+        b.max_call_depth = None;
+        b.fuel = Some(50);
+        let err = b
+            .call("down".into(), vec![Value::I32(100)])
+            .expect_err("fuel must be limited");
+        assert!(
+            matches!(err.downcast_ref::<Error>(), Some(Error::OutOfFuel)),
+            "{err}"
+        );
+        assert!(Rc::ptr_eq(&b.store, &store));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lazy_decode() -> Result<()> {
+        let code = r#"
+(module
+  (func (export "one") (result i32) (i32.const 1))
+  (func (export "two") (result i32) (i32.const 2))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let options = DecodeOptions {
+            lazy: true,
+            ..DecodeOptions::default()
+        };
+        let module = Decoder::with_options(wasm.as_slice(), options).decode()?;
+        validate(&module)?;
+        let mut runtime = Runtime::instantiate(Rc::new(RefCell::new(Store::new(&module, None)?)))?;
+
+        let decoded = |runtime: &Runtime| -> Vec<bool> {
+            let store = runtime.store.borrow();
+            store
+                .funcs
+                .iter()
+                .map(|func| match func {
+                    FuncInst::Internal(func) => func.code.body.decoded().is_some(),
+                    FuncInst::External(_) => false,
+                })
+                .collect()
+        };
+        assert_eq!(decoded(&runtime), vec![false, false]);
+
+        let result = runtime.call("one".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(1)));
+        assert_eq!(decoded(&runtime), vec![true, false]);
+
+        let result = runtime.call("two".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(2)));
+        assert_eq!(decoded(&runtime), vec![true, true]);
+
+        // the lazily decoded module is validated as well
+        let code = r#"(module (func (result i32) (i64.const 0)))"#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::with_options(wasm.as_slice(), options).decode()?;
+        assert!(validate(&module).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn else_without_if() -> Result<()> {
+        let code = r#"(module (func (export "f") (block (nop))))"#;
+        let mut wasm = wat::parse_str(code)?;
+        // replace nop with else
+        let pos = wasm
+            .windows(4)
+            .position(|w| w == [0x02, 0x40, 0x01, 0x0b])
+            .context("block must be found")?;
+        wasm[pos + 2] = 0x05;
+
+        let mut runtime = Runtime::from_bytes(&wasm, None)?;
+        let result = runtime.call("f".into(), vec![]);
+        assert_eq!(result.unwrap_err().to_string(), "else without if");
+
+        Ok(())
+    }
 }
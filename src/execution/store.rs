@@ -2,19 +2,22 @@ use super::{error::Error, module::*, value::Value};
 use crate::{
     binary::{
         module::{Decoder, Module},
-        types::{Expr, ExprValue, FuncType, Mutability},
+        types::{DataMode, ElementMode, Expr, FuncType, Limits, Mutability},
     },
     Importer,
 };
 use anyhow::{bail, Context, Result};
 use std::{
     cell::RefCell,
-    collections::HashMap,
     fs,
     io::{Cursor, Read},
-    rc::Rc,
+    rc::{Rc, Weak},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+// NOTE: 0 is the id of the default store
+static NEXT_STORE_ID: AtomicUsize = AtomicUsize::new(1);
+
 #[derive(Debug)]
 pub enum Exports {
     Func(FuncInst),
@@ -25,11 +28,13 @@ pub enum Exports {
 
 #[derive(Default)]
 pub struct Store {
+    pub id: usize, // unique in the process
     pub funcs: Vec<FuncInst>,
     pub tables: Vec<TableInst>,
     pub memory: Vec<MemoryInst>,
     pub globals: Vec<GlobalInst>,
-    pub imports: Option<HashMap<String, Box<dyn Importer>>>,
+    pub datas: Vec<Vec<u8>>,
+    pub imports: Option<Vec<Rc<dyn Importer>>>,
     pub module: ModuleInst,
     pub start: Option<u32>,
 }
@@ -62,11 +67,15 @@ impl Store {
     }
 
     pub fn new(module: &Module, importers: Option<Vec<Box<dyn Importer>>>) -> Result<Self> {
-        let func_type_idxs = match module.function_section {
-            Some(ref functions) => functions.clone(),
-            _ => vec![],
-        };
+        Self::with_funcs(module, internal_funcs(module)?, importers)
+    }
 
+    // instantiate with the functions which are already built from the module
+    pub(crate) fn with_funcs(
+        module: &Module,
+        internal_funcs: Vec<InternalFuncInst>,
+        importers: Option<Vec<Box<dyn Importer>>>,
+    ) -> Result<Self> {
         let mut funcs = vec![];
         let mut tables = vec![];
         let mut globals = vec![];
@@ -81,14 +90,13 @@ impl Store {
                 let module_name = import_info.module.as_str();
                 let field = import_info.field.as_str();
 
-                let importers: Vec<_> = importers
+                // NOTE: the first importer wins when the names are duplicated
+                let importer = importers
                     .iter()
-                    .filter(|importer| importer.name() == module_name)
-                    .collect();
-                if importers.is_empty() {
-                    bail!("not found import module: {}", module_name);
-                }
-                let importer = importers.get(0).unwrap();
+                    .find(|importer| importer.name() == module_name)
+                    .with_context(|| format!("not found import module: {}", module_name))?;
+                let incompatible =
+                    || Error::IncompatibleImportType(module_name.to_string(), field.to_string());
 
                 match import_info.kind {
                     crate::binary::types::ImportKind::Func(typeidx) => {
@@ -104,6 +112,12 @@ impl Store {
                             params: func_type.params.clone(),
                             results: func_type.results.clone(),
                         };
+                        // NOTE: checked only if the importer provides the function
+                        if let Some(provided) = importer.resolve_func(module_name, field)? {
+                            if *provided.func_type() != func_type {
+                                bail!(incompatible());
+                            }
+                        }
                         let func = FuncInst::External(ExternalFuncInst {
                             module: module_name.to_string(),
                             field: field.to_string(),
@@ -111,36 +125,80 @@ impl Store {
                         });
                         funcs.push(func);
                     }
-                    crate::binary::types::ImportKind::Table(_) => {
+                    crate::binary::types::ImportKind::Table(ref ty) => {
                         let table = importer
                             .resolve_table(module_name, field)?
                             .with_context(|| Error::NoImports)?; // TODO: define error enum
+                        {
+                            let table = table.borrow();
+                            if !limits_match(table.funcs.len(), table.max, &ty.limits) {
+                                bail!(incompatible());
+                            }
+                        }
                         tables.push(table);
                     }
-                    crate::binary::types::ImportKind::Global(_) => {
+                    crate::binary::types::ImportKind::Global(ref ty) => {
                         let global = importer
                             .resolve_global(module_name, field)?
                             .with_context(|| Error::NoImports)?;
+                        {
+                            let global = global.borrow();
+                            if global.value.type_of() != ty.value_type
+                                || global.mutability != (ty.mutability == Mutability::Var)
+                            {
+                                bail!(incompatible());
+                            }
+                        }
                         globals.push(global);
                     }
-                    crate::binary::types::ImportKind::Memory(_) => {
+                    crate::binary::types::ImportKind::Memory(ref ty) => {
                         let memory = importer
                             .resolve_memory(module_name, field)?
                             .with_context(|| Error::NoImports)?;
+                        {
+                            let memory = memory.borrow();
+                            if !limits_match(memory.size(), memory.max, &ty.limits) {
+                                bail!(incompatible());
+                            }
+                        }
                         memories.push(memory);
                     }
                 }
             }
         }
 
+        // https://webassembly.github.io/spec/core/valid/instructions.html#constant-expressions
+        // NOTE: global.get can only refer to the imported immutable globals
+        let imported_globals = globals.len();
+        let eval_const = |globals: &Vec<GlobalInst>, expr: &Expr| -> Result<Value> {
+            match expr {
+                Expr::Value(value) => Ok(value.clone().into()),
+                Expr::GlobalIndex(idx) => {
+                    if *idx >= imported_globals {
+                        bail!(Error::NotImportedGlobalInConstExpr(*idx));
+                    }
+                    let global = globals
+                        .get(*idx)
+                        .with_context(|| Error::NotFoundGlobalVariable(*idx))?
+                        .borrow();
+                    if global.mutability {
+                        bail!(Error::MutableGlobalInConstExpr(*idx));
+                    }
+                    Ok(global.value.clone())
+                }
+            }
+        };
+
         if let Some(ref section) = module.global_section {
             for global in section {
-                let value = match global.init_expr {
-                    ExprValue::I32(v) => Value::I32(v),
-                    ExprValue::I64(v) => Value::I64(v),
-                    ExprValue::F32(v) => Value::F32(v),
-                    ExprValue::F64(v) => Value::F64(v),
-                };
+                let value = eval_const(&globals, &global.init_expr)?;
+                let value_type = &global.global_type.value_type;
+                if value.type_of() != *value_type {
+                    bail!(Error::TypeMismatchGlobalInit(
+                        value_type.clone(),
+                        value.type_of()
+                    ));
+                }
                 let global = InternalGlobalInst {
                     value,
                     mutability: global.global_type.mutability == Mutability::Var,
@@ -149,40 +207,14 @@ impl Store {
             }
         }
 
-        if let Some(ref code_section) = module.code_section {
-            if code_section.len() != func_type_idxs.len() {
-                bail!("code section length must be equal to function section length");
-            }
-            for (func_body, typeidx) in code_section.iter().zip(func_type_idxs.iter()) {
-                let func_type = module
-                    .type_section
-                    .as_ref()
-                    .with_context(|| "cannot get type section")?
-                    .get(*typeidx as usize)
-                    .with_context(|| "cannot get func type from type section")?
-                    .clone();
-
-                let mut locals = Vec::with_capacity(func_body.locals.len());
-                for local in func_body.locals.iter() {
-                    for _ in 0..local.type_count {
-                        locals.push(local.value_type.clone());
-                    }
-                }
-
-                // NOTE: locals length must be func_type.params + func_body.locals
-                let func = InternalFuncInst {
-                    func_type,
-                    code: Func {
-                        type_idx: *typeidx,
-                        locals,
-                        body: func_body.code.clone(),
-                    },
-                };
-                funcs.push(FuncInst::Internal(func));
-            }
-        }
+        let id = NEXT_STORE_ID.fetch_add(1, Ordering::Relaxed);
+        funcs.extend(internal_funcs.into_iter().map(|func| {
+            FuncInst::Internal(InternalFuncInst {
+                store_id: id,
+                ..func
+            })
+        }));
 
-        // NOTE: only support one memory now
         if let Some(ref section) = module.memory_section {
             for memory in section {
                 let min = memory.limits.min * PAGE_SIZE;
@@ -196,103 +228,191 @@ impl Store {
 
         // eval for offset in the table
         let eval = |globals: &Vec<GlobalInst>, offset: Expr| -> Result<usize> {
-            match offset {
-                Expr::Value(value) => Ok(i32::from(value) as usize),
-                Expr::GlobalIndex(idx) => {
-                    let global = globals
-                        .get(idx)
-                        .with_context(|| "not found offset from globals")?
-                        .borrow();
-                    Ok(i32::from(global.value.clone()) as usize)
-                }
-            }
-        };
-
-        // table will be shared by all module instance
-        // so if element is exists in the same index, overwrite the table
-        let update_funcs_in_table = |entries: &mut Vec<Option<FuncInst>>| -> Result<()> {
-            if let Some(elems) = module.element_section.as_ref() {
-                for elem in elems {
-                    let offset = eval(&globals, elem.offset.clone())?;
-                    if entries.len() <= offset {
-                        entries.resize(entries.len() + offset + elem.init.len(), None);
-                    }
-                    for (i, func_idx) in elem.init.iter().enumerate() {
-                        let func = funcs
-                            .get(*func_idx as usize)
-                            .with_context(|| format!("not found function by {func_idx}"))?;
-                        entries[offset + i] = Some(func.clone());
-                    }
-                }
-            };
-            Ok(())
+            let value = eval_const(globals, &offset)?;
+            Ok(i32::try_from(value)? as usize)
         };
 
         // table
         if let Some(ref table_section) = module.table_section {
-            let table = table_section
-                .get(0) // NOTE: only support one table now
-                .with_context(|| "cannot get table from table section")?; // NOTE: only support one table now
-            let min = table.limits.min as usize;
+            for table in table_section {
+                let table_inst = InternalTableInst {
+                    funcs: vec![None; table.limits.min as usize],
+                    max: table.limits.max,
+                };
+                tables.push(Rc::new(RefCell::new(table_inst)));
+            }
+        }
 
-            let mut entries = vec![None; min];
-            update_funcs_in_table(&mut entries)?;
+        // table will be shared by all module instance
+        // so if element is exists in the same index, overwrite the table
+        // NOTE: declarative element segments are not applied to any table
+        if let Some(elems) = module.element_section.as_ref() {
+            for elem in elems {
+                let ElementMode::Active {
+                    table_index,
+                    ref offset,
+                } = elem.mode
+                else {
+                    continue;
+                };
+                let table_idx = table_index as usize;
+                let table = tables
+                    .get(table_idx)
+                    .with_context(|| Error::NotFoundTable(table_idx))?;
+                let entries = &mut table.borrow_mut().funcs;
 
-            let table_inst = InternalTableInst {
-                funcs: entries,
-                max: table.limits.max,
-            };
-            tables.push(Rc::new(RefCell::new(table_inst)));
-        } else {
-            // update table if element section exists
-            if !tables.is_empty() {
-                let entries = &mut tables
-                    .first()
-                    .with_context(|| "not found table")?
-                    .borrow_mut()
-                    .funcs;
-                update_funcs_in_table(entries)?;
+                // NOTE: segments are applied in order, so later ones overwrite earlier ones
+                let offset = eval(&globals, offset.clone())?;
+                let entries = offset
+                    .checked_add(elem.init.len())
+                    .and_then(|end| entries.get_mut(offset..end))
+                    .with_context(|| Error::OutOfBoundsTableAccess)?;
+                for (entry, func_idx) in entries.iter_mut().zip(&elem.init) {
+                    let func = funcs
+                        .get(*func_idx as usize)
+                        .with_context(|| format!("not found function by {func_idx}"))?;
+                    *entry = Some(func.clone());
+                }
             }
         }
 
         // copy data to memory
+        // NOTE: active data segments are dropped after the initialization,
+        // passive data segments are kept for memory.init
+        let mut datas = vec![];
         if let Some(ref data_list) = module.data {
             for data in data_list {
-                let offset = eval(&globals, data.offset.clone())?;
+                let DataMode::Active {
+                    memory_index,
+                    ref offset,
+                } = data.mode
+                else {
+                    datas.push(data.init.clone());
+                    continue;
+                };
+
+                let offset = eval(&globals, offset.clone())?;
                 let init_data = &data.init;
                 let mut memory = memories
-                    .get(data.memory_index as usize)
+                    .get(memory_index as usize)
                     .with_context(|| "not found memory")?
                     .borrow_mut();
                 if offset + init_data.len() > memory.data.len() {
                     bail!("data is too large to fit in memory");
                 }
                 memory.data[offset..offset + init_data.len()].copy_from_slice(init_data);
+                datas.push(vec![]);
             }
         }
 
         let module_inst = ModuleInst::allocate(module);
 
-        let imports = if let Some(imports) = importers {
-            let mut map = HashMap::new();
-            for importer in imports {
-                map.insert(importer.name().to_string(), importer);
-            }
-            Some(map)
-        } else {
-            None
-        };
-
         let store = Self {
+            id,
             funcs,
             tables,
             memory: memories,
             globals,
-            imports,
+            datas,
+            imports: importers.map(|importers| importers.into_iter().map(Rc::from).collect()),
             module: module_inst,
             start: module.start_section,
         };
 
         Ok(store)
     }
+
+    // NOTE: the first importer wins when the names are duplicated
+    pub fn importer(&self, name: &str) -> Result<Rc<dyn Importer>> {
+        let importer = self
+            .imports
+            .as_ref()
+            .with_context(|| Error::NoImports)?
+            .iter()
+            .find(|importer| importer.name() == name)
+            .with_context(|| Error::NotFoundImportModule(name.to_string()))?;
+        Ok(Rc::clone(importer))
+    }
+}
+
+// bind the functions defined by the store to it, so that they can be called from other stores
+// through the shared tables
+// NOTE: the functions are copied into the tables before the store is wrapped in Rc
+pub(crate) fn bind_store(store: &Rc<RefCell<Store>>) {
+    let weak = Rc::downgrade(store);
+    let mut store = store.borrow_mut();
+    let id = store.id;
+    let bind = |func: &mut FuncInst| {
+        if let FuncInst::Internal(func) = func {
+            if func.store_id == id {
+                func.store = weak.clone();
+            }
+        }
+    };
+    store.funcs.iter_mut().for_each(bind);
+    for table in store.tables.iter() {
+        table.borrow_mut().funcs.iter_mut().flatten().for_each(bind);
+    }
+}
+
+// https://webassembly.github.io/spec/core/valid/types.html#limits
+// NOTE: the provided size must be at least the imported min, and the max must not be larger
+fn limits_match(size: usize, max: Option<u32>, limits: &Limits) -> bool {
+    let max_match = match (max, limits.max) {
+        (_, None) => true,
+        (Some(max), Some(limit)) => max <= limit,
+        (None, Some(_)) => false,
+    };
+    size as u64 >= limits.min as u64 && max_match
+}
+
+// build the internal functions from the code section, the bodies can be shared by many stores
+pub(crate) fn internal_funcs(module: &Module) -> Result<Vec<InternalFuncInst>> {
+    let func_type_idxs = match module.function_section {
+        Some(ref functions) => functions.clone(),
+        _ => vec![],
+    };
+
+    let mut funcs = vec![];
+    if let Some(ref code_section) = module.code_section {
+        if code_section.len() != func_type_idxs.len() {
+            bail!("code section length must be equal to function section length");
+        }
+        for (func_body, typeidx) in code_section.iter().zip(func_type_idxs.iter()) {
+            let func_type = module
+                .type_section
+                .as_ref()
+                .with_context(|| "cannot get type section")?
+                .get(*typeidx as usize)
+                .with_context(|| "cannot get func type from type section")?
+                .clone();
+
+            let mut locals = Vec::with_capacity(func_body.locals.len());
+            for local in func_body.locals.iter() {
+                for _ in 0..local.type_count {
+                    locals.push(local.value_type.clone());
+                }
+            }
+
+            // NOTE: locals length must be func_type.params + func_body.locals
+            let func = InternalFuncInst {
+                func_type,
+                code: Func {
+                    type_idx: *typeidx,
+                    locals,
+                    body: match func_body.bytes {
+                        Some(ref bytes) => Body::Lazy(Rc::new(LazyBody {
+                            bytes: bytes.clone(),
+                            ..LazyBody::default()
+                        })),
+                        None => Body::Decoded(func_body.code.clone().into()),
+                    },
+                },
+                store_id: 0, // set on instantiation
+                store: Weak::new(),
+            };
+            funcs.push(func);
+        }
+    }
+    Ok(funcs)
 }
@@ -4,8 +4,9 @@ use super::indices::*;
 use super::module::ModuleInst;
 use super::{float::*, integer::*};
 use crate::binary::instruction::*;
-use crate::binary::types::ExportDesc;
-use crate::binary::types::FuncType;
+use crate::binary::types::{ExportDesc, ExprValue};
+use crate::binary::types::{FuncType, ValueType};
+use crate::binary::wat::value_type;
 use crate::execution::error::Error;
 use anyhow::{bail, Context as _, Result};
 use log::trace;
@@ -22,22 +23,47 @@ pub enum Value {
     I64(i64),
     F32(f32),
     F64(f64),
+    FuncRef(Option<u32>), // index of function, none is null
 }
 
 impl Value {
-    pub fn is_true(&self) -> bool {
+    // https://webassembly.github.io/spec/core/exec/runtime.html#default-val
+    pub fn zero(value_type: &ValueType) -> Self {
+        match value_type {
+            ValueType::I32 => Value::I32(0),
+            ValueType::I64 => Value::I64(0),
+            ValueType::F32 => Value::F32(0.0),
+            ValueType::F64 => Value::F64(0.0),
+            ValueType::FuncRef => Value::FuncRef(None),
+        }
+    }
+
+    // NOTE: v128 and externref are rejected by the decoder, so there are no such values or types yet
+    pub fn type_of(&self) -> ValueType {
+        match self {
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+            Value::FuncRef(_) => ValueType::FuncRef,
+        }
+    }
+
+    pub fn is_true(&self) -> Result<bool> {
         match *self {
-            Value::I32(v) => 0 != v,
-            Value::I64(v) => 0 != v,
-            _ => {
-                panic!("cannot call is_true() when value is f32 or f64");
-            }
+            Value::I32(v) => Ok(0 != v),
+            Value::I64(v) => Ok(0 != v),
+            _ => bail!(Error::TypeMismatch(ValueType::I32, self.clone())),
         }
     }
 }
 
+// NOTE: alternate format `{:#}` includes the type, like `i32:42`
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            write!(f, "{}:", value_type(&self.type_of()))?;
+        }
         match self {
             Self::I32(n) => {
                 write!(f, "{n}")
@@ -51,10 +77,26 @@ impl Display for Value {
             Self::F64(n) => {
                 write!(f, "{n}")
             }
+            Self::FuncRef(Some(idx)) => {
+                write!(f, "{idx}")
+            }
+            Self::FuncRef(None) => {
+                write!(f, "null")
+            }
         }
     }
 }
 
+// format values as a typed list, like `[i32:42, f64:3.14]`
+// NOTE: each value is formatted by `format`, e.g. `Value::to_string`
+pub fn format_typed_values(values: &[Value], format: impl Fn(&Value) -> String) -> String {
+    let values: Vec<_> = values
+        .iter()
+        .map(|value| format!("{}:{}", value_type(&value.type_of()), format(value)))
+        .collect();
+    format!("[{}]", values.join(", "))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LabelKind {
     If,
@@ -73,32 +115,43 @@ pub struct Label {
 
 #[derive(Clone, Debug, Default)]
 pub struct Frame {
-    pub pc: isize,               // next pc
-    pub sp: usize,               // stack pointer when frame created
-    pub insts: Vec<Instruction>, // function instructions
-    pub arity: usize,            // result arity
-    pub locals: Vec<Value>,      // local variables
-    pub labels: Vec<Label>,      // labels for if, loop, block
+    pub pc: isize,                // next pc
+    pub sp: usize,                // stack pointer when frame created
+    pub insts: Rc<[Instruction]>, // function instructions
+    pub arity: usize,             // result arity
+    pub locals: Vec<Value>,       // local variables
+    pub labels: Vec<Label>,       // labels for if, loop, block
 }
 
 // trait for stack access
+// NOTE: popping a value fails on a type mismatch, except when popping it as `Value`
 pub trait StackAccess {
     fn push<T: Into<Value>>(&mut self, value: T);
-    fn pop1<T: From<Value>>(&mut self) -> Result<T>;
-    fn pop_rl<T: From<Value>>(&mut self) -> Result<(T, T)>;
+    fn pop1<T: TryFrom<Value>>(&mut self) -> Result<T>
+    where
+        anyhow::Error: From<T::Error>;
+    fn pop_rl<T: TryFrom<Value>>(&mut self) -> Result<(T, T)>
+    where
+        anyhow::Error: From<T::Error>;
 }
 
 impl StackAccess for Vec<Value> {
     fn push<T: Into<Value>>(&mut self, value: T) {
         self.push(value.into());
     }
-    fn pop1<T: From<Value>>(&mut self) -> Result<T> {
+    fn pop1<T: TryFrom<Value>>(&mut self) -> Result<T>
+    where
+        anyhow::Error: From<T::Error>,
+    {
         trace!("pop value from stack. stack: {:#?}", self);
-        let value: T = self.pop().with_context(|| Error::StackPopError)?.into();
-        Ok(value)
+        let value = self.pop().with_context(|| Error::StackPopError)?;
+        Ok(value.try_into()?)
     }
 
-    fn pop_rl<T: From<Value>>(&mut self) -> Result<(T, T)> {
+    fn pop_rl<T: TryFrom<Value>>(&mut self) -> Result<(T, T)>
+    where
+        anyhow::Error: From<T::Error>,
+    {
         let r = self.pop1()?;
         let l = self.pop1()?;
         Ok((r, l))
@@ -122,11 +175,13 @@ into_into_value!(i32 => I32, i64 => I64, f32 => F32, f64 => F64);
 macro_rules! into_from_value {
     ($($ty: ty => $variant: ident),*) => {
         $(
-            impl From<Value> for $ty {
-                fn from(value: Value) -> Self {
+            impl TryFrom<Value> for $ty {
+                type Error = Error;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
                     match value {
-                        Value::$variant(v) => v,
-                        _ => panic!("unexpected value: {value:?}"),
+                        Value::$variant(v) => Ok(v),
+                        _ => Err(Error::TypeMismatch(ValueType::$variant, value)),
                     }
                 }
             }
@@ -143,6 +198,17 @@ impl From<u64> for Value {
     }
 }
 
+impl From<ExprValue> for Value {
+    fn from(value: ExprValue) -> Self {
+        match value {
+            ExprValue::I32(v) => Self::I32(v),
+            ExprValue::I64(v) => Self::I64(v),
+            ExprValue::F32(v) => Self::F32(v),
+            ExprValue::F64(v) => Self::F64(v),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ExternalVal {
     Func(FuncIdx),
@@ -171,7 +237,7 @@ macro_rules! binop {
                     (Value::I64(l), Value::I64(r)) => Ok(Value::I64(l.$op(*r)?)),
                     (Value::F32(l), Value::F32(r)) => Ok(Value::F32(l.$op(*r)?)),
                     (Value::F64(l), Value::F64(r)) => Ok(Value::F64(l.$op(*r)?)),
-                    _ => panic!("unexpected value. left: {self} right: {rhs}")
+                    _ => bail!(Error::TypeMismatchOperands(self.clone(), rhs.clone()))
                 }
             }
         )*
@@ -185,7 +251,7 @@ macro_rules! ibinop {
                 match (self, rhs) {
                     (Value::I32(l), Value::I32(r)) => Ok(Value::I32(l.$op(*r)?)),
                     (Value::I64(l), Value::I64(r)) => Ok(Value::I64(l.$op(*r)?)),
-                    _ => panic!("unexpected value. left: {self} right: {rhs}")
+                    _ => bail!(Error::TypeMismatchOperands(self.clone(), rhs.clone()))
                 }
             }
         )*
@@ -199,7 +265,7 @@ macro_rules! fbinop {
                 match (self, rhs) {
                     (Value::F32(l), Value::F32(r)) => Ok(Value::F32(l.$op(*r)?)),
                     (Value::F64(l), Value::F64(r)) => Ok(Value::F64(l.$op(*r)?)),
-                    _ => panic!("unexpected value. left: {self} right: {rhs}")
+                    _ => bail!(Error::TypeMismatchOperands(self.clone(), rhs.clone()))
                 }
             }
         )*
@@ -215,7 +281,7 @@ macro_rules! relop {
                     (Value::I64(l), Value::I64(r)) => Ok(Value::I32(l.$op(*r)? as i32)),
                     (Value::F32(l), Value::F32(r)) => Ok(Value::I32(l.$op(*r)? as i32)),
                     (Value::F64(l), Value::F64(r)) => Ok(Value::I32(l.$op(*r)? as i32)),
-                    _ => panic!("unexpected value. left: {self} right: {rhs}")
+                    _ => bail!(Error::TypeMismatchOperands(self.clone(), rhs.clone()))
                 }
             }
         )*
@@ -229,7 +295,7 @@ macro_rules! irelop {
                 match (self, rhs) {
                     (Value::I32(l), Value::I32(r)) => Ok(Value::I32(l.$op(*r)?)),
                     (Value::I64(l), Value::I64(r)) => Ok(Value::I32(l.$op(*r)? as i32)),
-                    _ => panic!("unexpected value. left: {self} right: {rhs}")
+                    _ => bail!(Error::TypeMismatchOperands(self.clone(), rhs.clone()))
                 }
             }
         )*
@@ -243,7 +309,7 @@ macro_rules! frelop {
                 match (self, rhs) {
                     (Value::F32(l), Value::F32(r)) => Ok(Value::I32(l.$op(*r)? as i32)),
                     (Value::F64(l), Value::F64(r)) => Ok(Value::I32(l.$op(*r)? as i32)),
-                    _ => panic!("unexpected value. left: {self} right: {rhs}")
+                    _ => bail!(Error::TypeMismatchOperands(self.clone(), rhs.clone()))
                 }
             }
         )*
@@ -257,7 +323,7 @@ macro_rules! iunop {
                 match self {
                     Value::I32(l) => Ok(Value::I32(l.$op()?)),
                     Value::I64(l) => Ok(Value::I64(l.$op()?)),
-                    _ => panic!("unexpected value. {self}")
+                    _ => bail!(Error::UnexpectedStackValueType(self.clone()))
                 }
             }
         )*
@@ -271,7 +337,7 @@ macro_rules! itestop {
                 match self {
                     Value::I32(l) => Ok(Value::I32(l.$op()?)),
                     Value::I64(l) => Ok(Value::I32(l.$op()? as i32)),
-                    _ => panic!("unexpected value. {self}")
+                    _ => bail!(Error::UnexpectedStackValueType(self.clone()))
                 }
             }
         )*
@@ -285,7 +351,7 @@ macro_rules! funop {
                 match self {
                     Value::F32(l) => Ok(Value::F32(l.$op()?)),
                     Value::F64(l) => Ok(Value::F64(l.$op()?)),
-                    _ => panic!("unexpected value. {self}")
+                    _ => bail!(Error::UnexpectedStackValueType(self.clone()))
                 }
             }
         )*
@@ -331,7 +397,7 @@ impl Value {
                 validate!(*f, i32);
                 Ok(Value::I32((*f).trunc() as i32))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -343,7 +409,7 @@ impl Value {
 
                 Ok(Value::I32(x))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -353,7 +419,7 @@ impl Value {
                 validate!(*f, i32);
                 Ok(Value::I32(*f as i32))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -363,14 +429,14 @@ impl Value {
                 validate!(*f, u32);
                 Ok(Value::I32(*f as u32 as i32))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn i32_wrap_i64(&self) -> Result<Self> {
         match self {
             Value::I64(l) => Ok(Value::I32(*l as i32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -380,7 +446,7 @@ impl Value {
                 validate!(*f, i64);
                 Ok(Value::I64(*f as i64))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -390,7 +456,7 @@ impl Value {
                 validate!(*f, u64);
                 Ok(Value::I64(*f as u64 as i64))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -400,7 +466,7 @@ impl Value {
                 validate!(*f, i64);
                 Ok(Value::I64(*f as i64))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
@@ -410,119 +476,124 @@ impl Value {
                 validate!(*f, u64);
                 Ok(Value::I64(*f as u64 as i64))
             }
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn i64_extend_i32_s(&self) -> Result<Self> {
         match self {
             Value::I32(l) => Ok(Value::I64(*l as i64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn i64_extend_i32_u(&self) -> Result<Self> {
         match self {
             Value::I32(l) => Ok(Value::I64(*l as u32 as i64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f32_convert_i32_s(&self) -> Result<Self> {
         match self {
             Value::I32(l) => Ok(Value::F32(*l as f32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f32_convert_i32_u(&self) -> Result<Self> {
         match self {
             Value::I32(l) => Ok(Value::F32(*l as u32 as f32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f32_convert_i64_s(&self) -> Result<Self> {
         match self {
             Value::I64(l) => Ok(Value::F32(*l as f32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f32_convert_i64_u(&self) -> Result<Self> {
         match self {
             Value::I64(l) => Ok(Value::F32(*l as u64 as f32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
+    // NOTE: `as` rounds to nearest-even and keeps infinities, NaN is canonicalized
+    // https://webassembly.github.io/spec/core/exec/numerics.html#op-demote
     pub fn f32_demote_f64(&self) -> Result<Self> {
         match self {
+            Value::F64(f) if f.is_nan() => Ok(Value::F32(f32::NAN)),
             Value::F64(f) => Ok(Value::F32(*f as f32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f64_convert_i32_s(&self) -> Result<Self> {
         match self {
             Value::I32(l) => Ok(Value::F64(*l as f64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f64_convert_i32_u(&self) -> Result<Self> {
         match self {
             Value::I32(l) => Ok(Value::F64(*l as u32 as f64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f64_convert_i64_s(&self) -> Result<Self> {
         match self {
             Value::I64(l) => Ok(Value::F64(*l as f64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f64_convert_i64_u(&self) -> Result<Self> {
         match self {
             Value::I64(i) => Ok(Value::F64(*i as u64 as f64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
-    pub fn f64_demote_f32(&self) -> Result<Self> {
+    // https://webassembly.github.io/spec/core/exec/numerics.html#op-promote
+    pub fn f64_promote_f32(&self) -> Result<Self> {
         match self {
+            Value::F32(f) if f.is_nan() => Ok(Value::F64(f64::NAN)),
             Value::F32(f) => Ok(Value::F64(*f as f64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn i32_reinterpret_f32(&self) -> Result<Self> {
         match self {
             Value::F32(f) => Ok(Value::I32((*f).to_bits() as i32)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn i64_reinterpret_f64(&self) -> Result<Self> {
         match self {
             Value::F64(f) => Ok(Value::I64((*f).to_bits() as i64)),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f32_reinterpret_i32(&self) -> Result<Self> {
         match self {
             Value::I32(i) => Ok(Value::F32(f32::from_bits(*i as u32))),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 
     pub fn f64_reinterpret_i64(&self) -> Result<Self> {
         match self {
             Value::I64(i) => Ok(Value::F64(f64::from_bits(*i as u64))),
-            _ => panic!("unexpected value. {self}"),
+            _ => bail!(Error::UnexpectedStackValueType(self.clone())),
         }
     }
 }
@@ -539,19 +610,21 @@ macro_rules! impl_numeric {
         $(
             impl Numeric for $ty {
                 fn read(buf: &[u8], addr: usize) -> Result<$ty> {
-                    if addr + size_of::<$ty>() > buf.len() {
-                        bail!("out of bounds memory access");
-                    }
-                    let end = addr + size_of::<$ty>();
+                    // NOTE: the whole width of the value must be in bounds
+                    let end = match addr.checked_add(size_of::<$ty>()) {
+                        Some(end) if end <= buf.len() => end,
+                        _ => bail!(Error::OutOfBoundsMemoryAccess),
+                    };
                     Ok(<$ty>::from_le_bytes(buf[addr..end].try_into()?))
                 }
 
                 fn write(buf: &mut [u8], addr: usize, value: Self) -> Result<()> {
                     let bytes = value.to_le_bytes();
-                    if addr + size_of::<$ty>() > buf.len() {
-                        bail!("out of bounds memory access");
-                    }
-                    buf[addr..addr + size_of::<$ty>()].copy_from_slice(&bytes);
+                    let end = match addr.checked_add(size_of::<$ty>()) {
+                        Some(end) if end <= buf.len() => end,
+                        _ => bail!(Error::OutOfBoundsMemoryAccess),
+                    };
+                    buf[addr..end].copy_from_slice(&bytes);
                     Ok(())
                 }
             }
@@ -560,3 +633,39 @@ macro_rules! impl_numeric {
 }
 
 impl_numeric!(i8, i16, i32, i64, f32, f64, u8, u16, u32);
+
+#[cfg(test)]
+mod test {
+    use super::Value;
+    use crate::binary::types::ValueType;
+    use crate::execution::error::Error;
+
+    #[test]
+    fn type_of() {
+        assert_eq!(Value::I32(0).type_of(), ValueType::I32);
+        assert_eq!(Value::I64(0).type_of(), ValueType::I64);
+        assert_eq!(Value::F32(0.0).type_of(), ValueType::F32);
+        assert_eq!(Value::F64(0.0).type_of(), ValueType::F64);
+        assert_eq!(Value::FuncRef(None).type_of(), ValueType::FuncRef);
+        for ty in [ValueType::I32, ValueType::F64, ValueType::FuncRef] {
+            assert_eq!(Value::zero(&ty).type_of(), ty);
+        }
+    }
+
+    #[test]
+    fn unop_type_mismatch() {
+        let value = Value::FuncRef(Some(0));
+        for result in [
+            value.eqz(),
+            value.clz(),
+            value.neg(),
+            value.i64_extend_i32_s(),
+        ] {
+            let err = result.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::UnexpectedStackValueType(Value::FuncRef(Some(0))))
+            ));
+        }
+    }
+}
@@ -1,4 +1,6 @@
-mod binary;
+pub mod binary;
 pub mod execution;
+pub mod validation;
+#[cfg(feature = "wasi")]
 pub mod wasi;
 pub use execution::*;
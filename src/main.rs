@@ -1,37 +1,142 @@
-use anyhow::Result;
-use chibiwasm::{execution::Runtime, wasi::WasiSnapshotPreview1};
-use clap::Parser;
+use anyhow::{Context as _, Result};
+use chibiwasm::{
+    binary::{extern_type_to_wat, Decoder},
+    execution::{format_typed_values, Runtime, Value},
+    wasi::WasiSnapshotPreview1,
+};
+use clap::{Parser, ValueEnum};
+use std::{fs::File, io::BufReader};
 
 #[derive(Debug, Parser)]
 #[clap(author, about, version)]
 struct Args {
+    /// Make WASI time and random sources reproducible
+    #[clap(long)]
+    deterministic: bool,
+    /// Print the exports and imports of the module without running it
+    #[clap(long)]
+    list: bool,
+    /// Format of the results
+    #[clap(long, value_enum, default_value_t = Format::Dec)]
+    format: Format,
+    /// Treat FUNC as the index of the function instead of the exported name
+    #[clap(long)]
+    index: bool,
     file: String,
-    func: String,
-    func_args: Option<Vec<i32>>,
+    #[clap(required_unless_present = "list")]
+    func: Option<String>,
+    func_args: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Decimal
+    Dec,
+    /// Hexadecimal integers, floats are printed in decimal
+    Hex,
+    /// Hexadecimal integers and IEEE-754 bits of floats
+    Bits,
+}
+
+fn format_value(value: &Value, format: Format) -> String {
+    match (value, format) {
+        (Value::I32(n), Format::Hex | Format::Bits) => format!("{n:#x}"),
+        (Value::I64(n), Format::Hex | Format::Bits) => format!("{n:#x}"),
+        (Value::F32(n), Format::Bits) => format!("{:#x}", n.to_bits()),
+        (Value::F64(n), Format::Bits) => format!("{:#x}", n.to_bits()),
+        (value, _) => value.to_string(),
+    }
+}
+
+// NOTE: multiple results are printed as a typed list, like `[i32:0x2a, f64:1.5]`
+fn format_results(values: &[Value], format: Format) -> String {
+    match values {
+        [value] => format_value(value, format),
+        values => format_typed_values(values, |value| format_value(value, format)),
+    }
+}
+
+fn list(file: &str) -> Result<()> {
+    let reader = BufReader::new(File::open(file)?);
+    let module = Decoder::new(reader).decode()?;
+
+    println!("exports:");
+    for export in module.exports()? {
+        println!("  {:?} {}", export.name, extern_type_to_wat(&export.ty));
+    }
+    println!("imports:");
+    for import in module.imports()? {
+        let ty = extern_type_to_wat(&import.ty);
+        println!("  {:?} {:?} {}", import.module, import.field, ty);
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     pretty_env_logger::init();
 
     let Args {
+        deterministic,
+        list: list_only,
+        format,
+        index,
         file,
         func,
         func_args,
     } = Args::parse();
 
-    let args = match func_args {
-        Some(args) => args.into_iter().map(Into::into).collect(),
-        None => {
-            vec![]
-        }
-    };
+    if list_only {
+        return list(&file);
+    }
+    let func = func.context("function name is required")?;
+    let args = func_args.into_iter().map(Into::into).collect();
 
-    let mut runtime =
-        Runtime::from_file(&file, Some(vec![Box::<WasiSnapshotPreview1>::default()]))?;
-    let result = runtime.call(func, args).expect("failed to call function");
+    let mut wasi = WasiSnapshotPreview1::default();
+    if deterministic {
+        wasi = wasi.deterministic();
+    }
+    let mut runtime = Runtime::from_file(&file, Some(vec![Box::new(wasi)]))?;
+    let results = if index {
+        let idx = func
+            .parse::<usize>()
+            .with_context(|| format!("invalid function index: {func}"))?;
+        runtime.invoke_by_index(idx, args)
+    } else {
+        runtime.call_multi(func.clone(), args)
+    }
+    .with_context(|| format!("failed to call function: {func}"))?;
 
-    if let Some(output) = result {
-        println!("{}", output);
+    // NOTE: nothing is printed when the function has no results
+    if !results.is_empty() {
+        println!("{}", format_results(&results, format));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{format_results, Format};
+    use chibiwasm::Value;
+
+    #[test]
+    fn format() {
+        let values = [Value::I32(42)];
+        assert_eq!(format_results(&values, Format::Dec), "42");
+        assert_eq!(format_results(&values, Format::Hex), "0x2a");
+        assert_eq!(format_results(&[Value::I32(-1)], Format::Hex), "0xffffffff");
+
+        let values = [Value::I64(255), Value::F32(1.0), Value::F64(-0.5)];
+        assert_eq!(
+            format_results(&values, Format::Dec),
+            "[i64:255, f32:1, f64:-0.5]"
+        );
+        assert_eq!(
+            format_results(&values, Format::Hex),
+            "[i64:0xff, f32:1, f64:-0.5]"
+        );
+        assert_eq!(
+            format_results(&values, Format::Bits),
+            "[i64:0xff, f32:0x3f800000, f64:0xbfe0000000000000]"
+        );
+    }
+}
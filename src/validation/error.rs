@@ -0,0 +1,84 @@
+use crate::binary::types::ValueType;
+use std::fmt::Display;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("type mismatch, expected {0:?}, got {1:?}")]
+    TypeMismatch(ValueType, ValueType),
+    #[error("type mismatch, operand stack is empty")]
+    EmptyOperandStack,
+    #[error("type mismatch, {0} values remain at the end of block")]
+    UnconsumedValues(usize),
+    #[error("unknown label: {0}")]
+    UnknownLabel(u32),
+    #[error("unknown local: {0}")]
+    UnknownLocal(u32),
+    #[error("unknown global: {0}")]
+    UnknownGlobal(u32),
+    #[error("unknown function: {0}")]
+    UnknownFunction(u32),
+    #[error("unknown type: {0}")]
+    UnknownType(u32),
+    #[error("unknown table: {0}")]
+    UnknownTable(u32),
+    #[error("unknown memory: {0}")]
+    UnknownMemory(u32),
+    #[error("undeclared function reference: {0}")]
+    UndeclaredFunctionReference(u32),
+    #[error("unknown data segment: {0}")]
+    UnknownData(u32),
+    #[error("data count section is required")]
+    DataCountRequired,
+    #[error("data count and data section have inconsistent lengths, {0} != {1}")]
+    DataCountMismatch(u32, usize),
+    #[error("function and code section have inconsistent lengths, {0} != {1}")]
+    FunctionCountMismatch(usize, usize),
+    #[error("duplicate export name: {0}")]
+    DuplicateExportName(String),
+    #[error("global is immutable: {0}")]
+    ImmutableGlobal(u32),
+    #[error("alignment must not be larger than natural")]
+    InvalidAlignment,
+    #[error("malformed function body: {0}")]
+    MalformedBody(String),
+    #[error("type mismatch, br_table targets have different arity")]
+    BrTableArityMismatch,
+    #[error("type mismatch, tail call results must be the same as the caller")]
+    TailCallResultMismatch,
+    #[error("type mismatch, select without type must have numeric operands")]
+    NonNumericSelect,
+    #[error("invalid result arity of select, expected 1, got {0}")]
+    InvalidSelectArity(usize),
+    #[error("type mismatch, if without else must not produce results")]
+    MissingElse,
+    #[error("else without matching if")]
+    ElseWithoutIf,
+    #[error("unexpected end of function body")]
+    UnexpectedEnd,
+    #[error("instructions after the end of function body")]
+    TrailingInstructions,
+}
+
+// where the validation error occurred
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    Module,
+    Func { idx: u32, offset: usize }, // index of function and instruction
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Module => write!(f, "module"),
+            Self::Func { idx, offset } => write!(f, "function {idx}, offset {offset}"),
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{error} at {location}")]
+pub struct ValidationError {
+    pub location: Location,
+    pub error: Error,
+}
@@ -0,0 +1,5 @@
+pub mod error;
+pub mod validator;
+
+pub use error::*;
+pub use validator::*;
@@ -0,0 +1,1353 @@
+use super::error::{Error, Location, ValidationError};
+use crate::binary::{
+    instruction::{Instruction, MemoryArg},
+    module::Module,
+    types::{BlockType, ExportDesc, FuncType, FunctionBody, ImportKind, Mutability, ValueType},
+};
+use anyhow::bail;
+use std::collections::HashSet;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    // stop at the first validation error
+    #[default]
+    FailFast,
+    // keep going and report all validation errors
+    Collect,
+}
+
+// validate module with the fail-fast mode
+pub fn validate(module: &Module) -> anyhow::Result<()> {
+    if let Err(errors) = Validator::new(module).validate() {
+        if let Some(error) = errors.into_iter().next() {
+            bail!(error);
+        }
+    }
+    Ok(())
+}
+
+// https://webassembly.github.io/spec/core/appendix/algorithm.html
+pub struct Validator<'a> {
+    module: &'a Module,
+    mode: ValidationMode,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        Self::with_mode(module, ValidationMode::default())
+    }
+
+    pub fn with_mode(module: &'a Module, mode: ValidationMode) -> Self {
+        Self { module, mode }
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), Vec<ValidationError>> {
+        let context = match Context::new(self.module) {
+            Ok(context) => context,
+            Err(error) => {
+                return Err(vec![ValidationError {
+                    location: Location::Module,
+                    error,
+                }])
+            }
+        };
+
+        let mut errors = vec![];
+
+        // https://webassembly.github.io/spec/core/binary/modules.html#data-count-section
+        if let Some(count) = self.module.data_count_section {
+            let len = self.module.data.as_ref().map_or(0, Vec::len);
+            if count as usize != len {
+                errors.push(ValidationError {
+                    location: Location::Module,
+                    error: Error::DataCountMismatch(count, len),
+                });
+                if self.mode == ValidationMode::FailFast {
+                    return Err(errors);
+                }
+            }
+        }
+
+        // https://webassembly.github.io/spec/core/binary/modules.html#binary-module
+        let funcs = self.module.function_section.as_ref().map_or(0, Vec::len);
+        let bodies = self.module.code_section.as_ref().map_or(0, Vec::len);
+        if funcs != bodies {
+            errors.push(ValidationError {
+                location: Location::Module,
+                error: Error::FunctionCountMismatch(funcs, bodies),
+            });
+            if self.mode == ValidationMode::FailFast {
+                return Err(errors);
+            }
+        }
+
+        // https://webassembly.github.io/spec/core/valid/modules.html#valid-module
+        let mut names = HashSet::new();
+        for export in self.module.export_section.iter().flatten() {
+            if !names.insert(export.name.as_str()) {
+                errors.push(ValidationError {
+                    location: Location::Module,
+                    error: Error::DuplicateExportName(export.name.clone()),
+                });
+                if self.mode == ValidationMode::FailFast {
+                    return Err(errors);
+                }
+            }
+        }
+
+        let bodies = self.module.code_section.iter().flatten();
+        let type_idxs = self.module.function_section.iter().flatten();
+        for (i, (body, type_idx)) in bodies.zip(type_idxs).enumerate() {
+            let idx = (context.imported_funcs + i) as u32;
+            let Some(func_type) = context.types.get(*type_idx as usize) else {
+                errors.push(ValidationError {
+                    location: Location::Module,
+                    error: Error::UnknownType(*type_idx),
+                });
+                break;
+            };
+
+            // NOTE: the lazily decoded body is decoded here, but it is not kept in the module
+            let code = match body.instructions() {
+                Ok(code) => code,
+                Err(e) => {
+                    errors.push(ValidationError {
+                        location: Location::Func { idx, offset: 0 },
+                        error: Error::MalformedBody(e.to_string()),
+                    });
+                    if self.mode == ValidationMode::FailFast {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            let mut validator = FuncValidator::new(&context, func_type, body, &code);
+            validator.validate(idx, self.mode, &mut errors);
+
+            if self.mode == ValidationMode::FailFast && !errors.is_empty() {
+                break;
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // validate all functions, and report the first error of each invalid function
+    // NOTE: the errors of the module itself are not reported, use validate for them
+    pub fn validate_funcs(&self) -> Vec<(u32, ValidationError)> {
+        let Err(errors) = Self::with_mode(self.module, ValidationMode::Collect).validate() else {
+            return vec![];
+        };
+        let mut funcs: Vec<(u32, ValidationError)> = vec![];
+        for error in errors {
+            let Location::Func { idx, .. } = error.location else {
+                continue;
+            };
+            if funcs.last().is_some_and(|(last, _)| *last == idx) {
+                continue;
+            }
+            funcs.push((idx, error));
+        }
+        funcs
+    }
+}
+
+// https://webassembly.github.io/spec/core/valid/conventions.html#contexts
+struct Context<'a> {
+    types: &'a [FuncType],
+    funcs: Vec<FuncType>,
+    tables: usize,
+    memories: usize,
+    globals: Vec<(ValueType, bool)>, // value type and mutability
+    datas: Option<u32>,
+    imported_funcs: usize,
+    refs: HashSet<u32>, // functions declared in elements or exports, used by ref.func
+}
+
+impl<'a> Context<'a> {
+    fn new(module: &'a Module) -> Result<Self> {
+        let types = module.type_section.as_deref().unwrap_or_default();
+
+        let mut funcs = vec![];
+        let mut tables = 0;
+        let mut memories = 0;
+        let mut globals = vec![];
+        for import in module.import_section.iter().flatten() {
+            match import.kind {
+                ImportKind::Func(idx) => {
+                    let func_type = types.get(idx as usize).ok_or(Error::UnknownType(idx))?;
+                    funcs.push(func_type.clone());
+                }
+                ImportKind::Table(_) => tables += 1,
+                ImportKind::Memory(_) => memories += 1,
+                ImportKind::Global(ref global_type) => globals.push((
+                    global_type.value_type.clone(),
+                    global_type.mutability == Mutability::Var,
+                )),
+            }
+        }
+        let imported_funcs = funcs.len();
+
+        for idx in module.function_section.iter().flatten() {
+            let func_type = types.get(*idx as usize).ok_or(Error::UnknownType(*idx))?;
+            funcs.push(func_type.clone());
+        }
+        tables += module.table_section.as_ref().map_or(0, Vec::len);
+        memories += module.memory_section.as_ref().map_or(0, Vec::len);
+        for global in module.global_section.iter().flatten() {
+            globals.push((
+                global.global_type.value_type.clone(),
+                global.global_type.mutability == Mutability::Var,
+            ));
+        }
+
+        // https://webassembly.github.io/spec/core/valid/modules.html#valid-module
+        let mut refs: HashSet<u32> = module
+            .element_section
+            .iter()
+            .flatten()
+            .flat_map(|elem| elem.init.iter().copied())
+            .collect();
+        for export in module.export_section.iter().flatten() {
+            if let ExportDesc::Func(idx) = export.desc {
+                refs.insert(idx);
+            }
+        }
+
+        Ok(Self {
+            types,
+            funcs,
+            tables,
+            memories,
+            globals,
+            datas: module.data_count_section,
+            imported_funcs,
+            refs,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FrameKind {
+    Func,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+#[derive(Debug, Clone)]
+struct ControlFrame {
+    kind: FrameKind,
+    start_types: Vec<ValueType>,
+    end_types: Vec<ValueType>,
+    height: usize,
+    unreachable: bool,
+}
+
+impl ControlFrame {
+    fn label_types(&self) -> &[ValueType] {
+        if self.kind == FrameKind::Loop {
+            &self.start_types
+        } else {
+            &self.end_types
+        }
+    }
+}
+
+// None means the unknown type, it appears in the unreachable code
+type Operand = Option<ValueType>;
+
+struct FuncValidator<'a> {
+    context: &'a Context<'a>,
+    func_type: &'a FuncType,
+    code: &'a [Instruction],
+    locals: Vec<ValueType>,
+    vals: Vec<Operand>,
+    ctrls: Vec<ControlFrame>,
+}
+
+impl<'a> FuncValidator<'a> {
+    fn new(
+        context: &'a Context<'a>,
+        func_type: &'a FuncType,
+        body: &FunctionBody,
+        code: &'a [Instruction],
+    ) -> Self {
+        let mut locals = func_type.params.clone();
+        for local in body.locals.iter() {
+            for _ in 0..local.type_count {
+                locals.push(local.value_type.clone());
+            }
+        }
+
+        Self {
+            context,
+            func_type,
+            code,
+            locals,
+            vals: vec![],
+            ctrls: vec![],
+        }
+    }
+
+    fn validate(&mut self, idx: u32, mode: ValidationMode, errors: &mut Vec<ValidationError>) {
+        self.push_ctrl(FrameKind::Func, vec![], self.func_type.results.clone());
+
+        for (offset, inst) in self.code.iter().enumerate() {
+            let result = if self.ctrls.is_empty() {
+                Err(Error::TrailingInstructions)
+            } else {
+                self.validate_instruction(inst)
+            };
+
+            if let Err(error) = result {
+                errors.push(ValidationError {
+                    location: Location::Func { idx, offset },
+                    error,
+                });
+                if mode == ValidationMode::FailFast || self.ctrls.is_empty() {
+                    return;
+                }
+                // NOTE: treat the rest of the block as unreachable code
+                // so that one error doesn't cause the following errors
+                self.unreachable();
+            }
+        }
+
+        if !self.ctrls.is_empty() {
+            errors.push(ValidationError {
+                location: Location::Func {
+                    idx,
+                    offset: self.code.len(),
+                },
+                error: Error::UnexpectedEnd,
+            });
+        }
+    }
+
+    fn push_val(&mut self, val: Operand) {
+        self.vals.push(val);
+    }
+
+    fn pop_val(&mut self) -> Result<Operand> {
+        let frame = self.ctrls.last().ok_or(Error::TrailingInstructions)?;
+        if self.vals.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err(Error::EmptyOperandStack);
+        }
+        Ok(self.vals.pop().flatten())
+    }
+
+    fn pop_expect(&mut self, expect: &ValueType) -> Result<Operand> {
+        match self.pop_val()? {
+            Some(actual) if actual != *expect => Err(Error::TypeMismatch(expect.clone(), actual)),
+            _ => Ok(Some(expect.clone())),
+        }
+    }
+
+    fn push_vals(&mut self, types: &[ValueType]) {
+        for ty in types {
+            self.push_val(Some(ty.clone()));
+        }
+    }
+
+    fn pop_vals(&mut self, types: &[ValueType]) -> Result<Vec<Operand>> {
+        let mut popped = vec![];
+        for ty in types.iter().rev() {
+            popped.insert(0, self.pop_expect(ty)?);
+        }
+        Ok(popped)
+    }
+
+    fn push_ctrl(
+        &mut self,
+        kind: FrameKind,
+        start_types: Vec<ValueType>,
+        end_types: Vec<ValueType>,
+    ) {
+        let frame = ControlFrame {
+            kind,
+            height: self.vals.len(),
+            unreachable: false,
+            end_types,
+            start_types: start_types.clone(),
+        };
+        self.ctrls.push(frame);
+        self.push_vals(&start_types);
+    }
+
+    // NOTE: the frame is popped even if the validation fails
+    fn pop_ctrl(&mut self) -> Result<ControlFrame> {
+        let frame = self.ctrls.last().cloned().ok_or(Error::UnexpectedEnd)?;
+        let result = self.pop_vals(&frame.end_types).and_then(|_| {
+            let remains = self.vals.len() - frame.height;
+            if remains > 0 {
+                return Err(Error::UnconsumedValues(remains));
+            }
+            Ok(())
+        });
+        self.vals.truncate(frame.height);
+        self.ctrls.pop();
+        result.map(|_| frame)
+    }
+
+    fn label(&self, level: u32) -> Result<&ControlFrame> {
+        let idx = (self.ctrls.len() as u32)
+            .checked_sub(level + 1)
+            .ok_or(Error::UnknownLabel(level))?;
+        Ok(&self.ctrls[idx as usize])
+    }
+
+    fn unreachable(&mut self) {
+        if let Some(frame) = self.ctrls.last_mut() {
+            self.vals.truncate(frame.height);
+            frame.unreachable = true;
+        }
+    }
+
+    fn unop(&mut self, ty: ValueType) -> Result<()> {
+        self.pop_expect(&ty)?;
+        self.push_val(Some(ty));
+        Ok(())
+    }
+
+    fn binop(&mut self, ty: ValueType) -> Result<()> {
+        self.pop_expect(&ty)?;
+        self.pop_expect(&ty)?;
+        self.push_val(Some(ty));
+        Ok(())
+    }
+
+    fn testop(&mut self, ty: ValueType) -> Result<()> {
+        self.pop_expect(&ty)?;
+        self.push_val(Some(ValueType::I32));
+        Ok(())
+    }
+
+    fn relop(&mut self, ty: ValueType) -> Result<()> {
+        self.pop_expect(&ty)?;
+        self.pop_expect(&ty)?;
+        self.push_val(Some(ValueType::I32));
+        Ok(())
+    }
+
+    fn cvtop(&mut self, from: ValueType, to: ValueType) -> Result<()> {
+        self.pop_expect(&from)?;
+        self.push_val(Some(to));
+        Ok(())
+    }
+
+    fn return_call(&mut self, func_type: &FuncType) -> Result<()> {
+        if func_type.results != self.func_type.results {
+            return Err(Error::TailCallResultMismatch);
+        }
+        self.pop_vals(&func_type.params)?;
+        self.unreachable();
+        Ok(())
+    }
+
+    fn check_memory(&self, idx: u32) -> Result<()> {
+        if idx as usize >= self.context.memories {
+            return Err(Error::UnknownMemory(idx));
+        }
+        Ok(())
+    }
+
+    // NOTE: data count section is required to refer data segments in the code
+    fn check_data(&self, idx: u32) -> Result<()> {
+        let count = self.context.datas.ok_or(Error::DataCountRequired)?;
+        if idx >= count {
+            return Err(Error::UnknownData(idx));
+        }
+        Ok(())
+    }
+
+    // https://webassembly.github.io/spec/core/valid/instructions.html#memory-instructions
+    fn check_memarg(&self, arg: &MemoryArg, width: u32) -> Result<()> {
+        self.check_memory(arg.memory)?;
+        if arg.align >= 32 || (1 << arg.align) > width {
+            return Err(Error::InvalidAlignment);
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, arg: &MemoryArg, ty: ValueType, width: u32) -> Result<()> {
+        self.check_memarg(arg, width)?;
+        self.pop_expect(&ValueType::I32)?;
+        self.push_val(Some(ty));
+        Ok(())
+    }
+
+    fn store(&mut self, arg: &MemoryArg, ty: ValueType, width: u32) -> Result<()> {
+        self.check_memarg(arg, width)?;
+        self.pop_expect(&ty)?;
+        self.pop_expect(&ValueType::I32)?;
+        Ok(())
+    }
+
+    fn block_types(block_type: &BlockType) -> Vec<ValueType> {
+        match block_type {
+            BlockType::Empty => vec![],
+            BlockType::Value(types) => types.clone(),
+        }
+    }
+
+    fn validate_instruction(&mut self, inst: &Instruction) -> Result<()> {
+        use ValueType::*;
+
+        match inst {
+            Instruction::Unreachable => self.unreachable(),
+            Instruction::Nop => {}
+            Instruction::Block(block) => {
+                let end_types = Self::block_types(&block.block_type);
+                self.push_ctrl(FrameKind::Block, vec![], end_types);
+            }
+            Instruction::Loop(block) => {
+                let end_types = Self::block_types(&block.block_type);
+                self.push_ctrl(FrameKind::Loop, vec![], end_types);
+            }
+            Instruction::If(block) => {
+                self.pop_expect(&I32)?;
+                let end_types = Self::block_types(&block.block_type);
+                self.push_ctrl(FrameKind::If, vec![], end_types);
+            }
+            Instruction::Else => {
+                let frame = self.ctrls.last().cloned().ok_or(Error::ElseWithoutIf)?;
+                if frame.kind != FrameKind::If {
+                    return Err(Error::ElseWithoutIf);
+                }
+                let result = self.pop_ctrl();
+                self.push_ctrl(FrameKind::Else, frame.start_types, frame.end_types);
+                result?;
+            }
+            Instruction::End => {
+                let frame = self.ctrls.last().cloned().ok_or(Error::UnexpectedEnd)?;
+                let result = self.pop_ctrl();
+                if !self.ctrls.is_empty() {
+                    self.push_vals(&frame.end_types);
+                }
+                result?;
+                // NOTE: the missing else is an empty block which passes the params through
+                if frame.kind == FrameKind::If && frame.start_types != frame.end_types {
+                    return Err(Error::MissingElse);
+                }
+            }
+            Instruction::Br(level) => {
+                let types = self.label(*level)?.label_types().to_vec();
+                self.pop_vals(&types)?;
+                self.unreachable();
+            }
+            Instruction::BrIf(level) => {
+                self.pop_expect(&I32)?;
+                let types = self.label(*level)?.label_types().to_vec();
+                self.pop_vals(&types)?;
+                self.push_vals(&types);
+            }
+            Instruction::BrTable(levels, default) => {
+                self.pop_expect(&I32)?;
+                let default_types = self.label(*default)?.label_types().to_vec();
+                for level in levels {
+                    let types = self.label(*level)?.label_types().to_vec();
+                    if types.len() != default_types.len() {
+                        return Err(Error::BrTableArityMismatch);
+                    }
+                    let vals = self.pop_vals(&types)?;
+                    for val in vals {
+                        self.push_val(val);
+                    }
+                }
+                self.pop_vals(&default_types)?;
+                self.unreachable();
+            }
+            Instruction::Return => {
+                let results = self.func_type.results.clone();
+                self.pop_vals(&results)?;
+                self.unreachable();
+            }
+            Instruction::Call(idx) => {
+                let func_type = self
+                    .context
+                    .funcs
+                    .get(*idx as usize)
+                    .ok_or(Error::UnknownFunction(*idx))?;
+                self.pop_vals(&func_type.params)?;
+                self.push_vals(&func_type.results);
+            }
+            Instruction::CallIndirect((type_idx, table_idx)) => {
+                if *table_idx as usize >= self.context.tables {
+                    return Err(Error::UnknownTable(*table_idx));
+                }
+                let func_type = self
+                    .context
+                    .types
+                    .get(*type_idx as usize)
+                    .ok_or(Error::UnknownType(*type_idx))?;
+                self.pop_expect(&I32)?;
+                self.pop_vals(&func_type.params)?;
+                self.push_vals(&func_type.results);
+            }
+            Instruction::ReturnCall(idx) => {
+                let func_type = self
+                    .context
+                    .funcs
+                    .get(*idx as usize)
+                    .ok_or(Error::UnknownFunction(*idx))?;
+                self.return_call(func_type)?;
+            }
+            Instruction::ReturnCallIndirect((type_idx, table_idx)) => {
+                if *table_idx as usize >= self.context.tables {
+                    return Err(Error::UnknownTable(*table_idx));
+                }
+                let func_type = self
+                    .context
+                    .types
+                    .get(*type_idx as usize)
+                    .ok_or(Error::UnknownType(*type_idx))?;
+                self.pop_expect(&I32)?;
+                self.return_call(func_type)?;
+            }
+            Instruction::RefFunc(idx) => {
+                if *idx as usize >= self.context.funcs.len() {
+                    return Err(Error::UnknownFunction(*idx));
+                }
+                if !self.context.refs.contains(idx) {
+                    return Err(Error::UndeclaredFunctionReference(*idx));
+                }
+                self.push_val(Some(FuncRef));
+            }
+            Instruction::RefNull => self.push_val(Some(FuncRef)),
+            Instruction::RefIsNull => {
+                self.pop_expect(&FuncRef)?;
+                self.push_val(Some(I32));
+            }
+            Instruction::Drop => {
+                self.pop_val()?;
+            }
+            // NOTE: select without a type annotation must have numeric operands,
+            // reference operands are selected by the typed select
+            Instruction::Select => {
+                self.pop_expect(&I32)?;
+                let t1 = self.pop_val()?;
+                let t2 = match t1 {
+                    Some(ref ty) => self.pop_expect(ty)?,
+                    None => self.pop_val()?,
+                };
+                if t2 == Some(FuncRef) {
+                    return Err(Error::NonNumericSelect);
+                }
+                self.push_val(t2);
+            }
+            Instruction::TypedSelect(value_types) => {
+                let [ty] = value_types.as_slice() else {
+                    return Err(Error::InvalidSelectArity(value_types.len()));
+                };
+                self.pop_expect(&I32)?;
+                self.pop_expect(ty)?;
+                self.pop_expect(ty)?;
+                self.push_val(Some(ty.clone()));
+            }
+            Instruction::LocalGet(idx) => {
+                let ty = self.local(*idx)?;
+                self.push_val(Some(ty));
+            }
+            Instruction::LocalSet(idx) => {
+                let ty = self.local(*idx)?;
+                self.pop_expect(&ty)?;
+            }
+            Instruction::LocalTee(idx) => {
+                let ty = self.local(*idx)?;
+                self.pop_expect(&ty)?;
+                self.push_val(Some(ty));
+            }
+            Instruction::GlobalGet(idx) => {
+                let (ty, _) = self.global(*idx)?;
+                self.push_val(Some(ty));
+            }
+            Instruction::GlobalSet(idx) => {
+                let (ty, mutable) = self.global(*idx)?;
+                if !mutable {
+                    return Err(Error::ImmutableGlobal(*idx));
+                }
+                self.pop_expect(&ty)?;
+            }
+            Instruction::I32Load(arg) => self.load(arg, I32, 4)?,
+            Instruction::I64Load(arg) => self.load(arg, I64, 8)?,
+            Instruction::F32Load(arg) => self.load(arg, F32, 4)?,
+            Instruction::F64Load(arg) => self.load(arg, F64, 8)?,
+            Instruction::I32Load8S(arg) | Instruction::I32Load8U(arg) => self.load(arg, I32, 1)?,
+            Instruction::I32Load16S(arg) | Instruction::I32Load16U(arg) => {
+                self.load(arg, I32, 2)?
+            }
+            Instruction::I64Load8S(arg) | Instruction::I64Load8U(arg) => self.load(arg, I64, 1)?,
+            Instruction::I64Load16S(arg) | Instruction::I64Load16U(arg) => {
+                self.load(arg, I64, 2)?
+            }
+            Instruction::I64Load32S(arg) | Instruction::I64Load32U(arg) => {
+                self.load(arg, I64, 4)?
+            }
+            Instruction::I32Store(arg) => self.store(arg, I32, 4)?,
+            Instruction::I64Store(arg) => self.store(arg, I64, 8)?,
+            Instruction::F32Store(arg) => self.store(arg, F32, 4)?,
+            Instruction::F64Store(arg) => self.store(arg, F64, 8)?,
+            Instruction::I32Store8(arg) => self.store(arg, I32, 1)?,
+            Instruction::I32Store16(arg) => self.store(arg, I32, 2)?,
+            Instruction::I64Store8(arg) => self.store(arg, I64, 1)?,
+            Instruction::I64Store16(arg) => self.store(arg, I64, 2)?,
+            Instruction::I64Store32(arg) => self.store(arg, I64, 4)?,
+            Instruction::MemorySize(idx) => {
+                self.check_memory(*idx)?;
+                self.push_val(Some(I32));
+            }
+            Instruction::MemoryGrow(idx) => {
+                self.check_memory(*idx)?;
+                self.unop(I32)?;
+            }
+            Instruction::MemoryCopy(dst, src) => {
+                self.check_memory(*dst)?;
+                self.check_memory(*src)?;
+                self.pop_expect(&I32)?;
+                self.pop_expect(&I32)?;
+                self.pop_expect(&I32)?;
+            }
+            Instruction::MemoryFill(idx) => {
+                self.check_memory(*idx)?;
+                self.pop_expect(&I32)?;
+                self.pop_expect(&I32)?;
+                self.pop_expect(&I32)?;
+            }
+            Instruction::MemoryInit(data_idx, mem_idx) => {
+                self.check_memory(*mem_idx)?;
+                self.check_data(*data_idx)?;
+                self.pop_expect(&I32)?;
+                self.pop_expect(&I32)?;
+                self.pop_expect(&I32)?;
+            }
+            Instruction::DataDrop(data_idx) => self.check_data(*data_idx)?,
+            Instruction::TableGrow(table_idx) => {
+                if *table_idx as usize >= self.context.tables {
+                    return Err(Error::UnknownTable(*table_idx));
+                }
+                self.pop_expect(&I32)?;
+                self.pop_expect(&FuncRef)?;
+                self.push_val(Some(I32));
+            }
+            Instruction::I32Const(_) => self.push_val(Some(I32)),
+            Instruction::I64Const(_) => self.push_val(Some(I64)),
+            Instruction::F32Const(_) => self.push_val(Some(F32)),
+            Instruction::F64Const(_) => self.push_val(Some(F64)),
+            Instruction::I32Eqz => self.testop(I32)?,
+            Instruction::I64Eqz => self.testop(I64)?,
+            Instruction::I32Clz
+            | Instruction::I32Ctz
+            | Instruction::I32Popcnt
+            | Instruction::I32Extend8S
+            | Instruction::I32Extend16S => self.unop(I32)?,
+            Instruction::I64Clz
+            | Instruction::I64Ctz
+            | Instruction::I64Popcnt
+            | Instruction::I64Extend8S
+            | Instruction::I64Extend16S
+            | Instruction::I64Extend32S => self.unop(I64)?,
+            Instruction::F32Abs
+            | Instruction::F32Neg
+            | Instruction::F32Ceil
+            | Instruction::F32Floor
+            | Instruction::F32Trunc
+            | Instruction::F32Nearest
+            | Instruction::F32Sqrt => self.unop(F32)?,
+            Instruction::F64Abs
+            | Instruction::F64Neg
+            | Instruction::F64Ceil
+            | Instruction::F64Floor
+            | Instruction::F64Trunc
+            | Instruction::F64Nearest
+            | Instruction::F64Sqrt => self.unop(F64)?,
+            Instruction::I32Add
+            | Instruction::I32Sub
+            | Instruction::I32Mul
+            | Instruction::I32DivS
+            | Instruction::I32DivU
+            | Instruction::I32RemS
+            | Instruction::I32RemU
+            | Instruction::I32And
+            | Instruction::I32Or
+            | Instruction::I32Xor
+            | Instruction::I32ShL
+            | Instruction::I32ShrS
+            | Instruction::I32ShrU
+            | Instruction::I32RtoL
+            | Instruction::I32RtoR => self.binop(I32)?,
+            Instruction::I64Add
+            | Instruction::I64Sub
+            | Instruction::I64Mul
+            | Instruction::I64DivS
+            | Instruction::I64DivU
+            | Instruction::I64RemS
+            | Instruction::I64RemU
+            | Instruction::I64And
+            | Instruction::I64Or
+            | Instruction::I64Xor
+            | Instruction::I64ShL
+            | Instruction::I64ShrS
+            | Instruction::I64ShrU
+            | Instruction::I64RtoL
+            | Instruction::I64RtoR => self.binop(I64)?,
+            Instruction::F32Add
+            | Instruction::F32Sub
+            | Instruction::F32Mul
+            | Instruction::F32Div
+            | Instruction::F32Min
+            | Instruction::F32Max
+            | Instruction::F32Copysign => self.binop(F32)?,
+            Instruction::F64Add
+            | Instruction::F64Sub
+            | Instruction::F64Mul
+            | Instruction::F64Div
+            | Instruction::F64Min
+            | Instruction::F64Max
+            | Instruction::F64Copysign => self.binop(F64)?,
+            Instruction::I32Eq
+            | Instruction::I32Ne
+            | Instruction::I32LtS
+            | Instruction::I32LtU
+            | Instruction::I32GtS
+            | Instruction::I32GtU
+            | Instruction::I32LeS
+            | Instruction::I32LeU
+            | Instruction::I32GeS
+            | Instruction::I32GeU => self.relop(I32)?,
+            Instruction::I64Eq
+            | Instruction::I64Ne
+            | Instruction::I64LtS
+            | Instruction::I64LtU
+            | Instruction::I64GtS
+            | Instruction::I64GtU
+            | Instruction::I64LeS
+            | Instruction::I64LeU
+            | Instruction::I64GeS
+            | Instruction::I64GeU => self.relop(I64)?,
+            Instruction::F32Eq
+            | Instruction::F32Ne
+            | Instruction::F32Lt
+            | Instruction::F32Gt
+            | Instruction::F32Le
+            | Instruction::F32Ge => self.relop(F32)?,
+            Instruction::F64Eq
+            | Instruction::F64Ne
+            | Instruction::F64Lt
+            | Instruction::F64Gt
+            | Instruction::F64Le
+            | Instruction::F64Ge => self.relop(F64)?,
+            Instruction::I32WrapI64 => self.cvtop(I64, I32)?,
+            Instruction::I32TruncF32S | Instruction::I32TruncF32U => self.cvtop(F32, I32)?,
+            Instruction::I32TruncF64S | Instruction::I32TruncF64U => self.cvtop(F64, I32)?,
+            Instruction::I64ExtendI32S | Instruction::I64ExtendI32U => self.cvtop(I32, I64)?,
+            Instruction::I64TruncF32S | Instruction::I64TruncF32U => self.cvtop(F32, I64)?,
+            Instruction::I64TruncF64S | Instruction::I64TruncF64U => self.cvtop(F64, I64)?,
+            Instruction::F32ConvertI32S | Instruction::F32ConvertI32U => self.cvtop(I32, F32)?,
+            Instruction::F32ConvertI64S | Instruction::F32ConvertI64U => self.cvtop(I64, F32)?,
+            Instruction::F32DemoteF64 => self.cvtop(F64, F32)?,
+            Instruction::F64ConvertI32S | Instruction::F64ConvertI32U => self.cvtop(I32, F64)?,
+            Instruction::F64ConvertI64S | Instruction::F64ConvertI64U => self.cvtop(I64, F64)?,
+            Instruction::F64PromoteF32 => self.cvtop(F32, F64)?,
+            Instruction::I32ReinterpretF32 => self.cvtop(F32, I32)?,
+            Instruction::I64ReinterpretF64 => self.cvtop(F64, I64)?,
+            Instruction::F32ReinterpretI32 => self.cvtop(I32, F32)?,
+            Instruction::F64ReinterpretI64 => self.cvtop(I64, F64)?,
+        };
+        Ok(())
+    }
+
+    fn local(&self, idx: u32) -> Result<ValueType> {
+        self.locals
+            .get(idx as usize)
+            .cloned()
+            .ok_or(Error::UnknownLocal(idx))
+    }
+
+    fn global(&self, idx: u32) -> Result<(ValueType, bool)> {
+        self.context
+            .globals
+            .get(idx as usize)
+            .cloned()
+            .ok_or(Error::UnknownGlobal(idx))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ValidationMode, Validator};
+    use crate::binary::module::Decoder;
+    use crate::binary::types::ValueType;
+    use crate::validation::error::{Error, Location, ValidationError};
+    use anyhow::Result;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn collect_all_errors() -> Result<()> {
+        let code = r#"
+(module
+  (func (result i32)
+    (i32.add (i32.const 1) (i64.const 2))
+  )
+  (func
+    (drop (f32.neg (i32.const 1)))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let errors = Validator::with_mode(&module, ValidationMode::Collect)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError {
+                    location: Location::Func { idx: 0, offset: 2 },
+                    error: Error::TypeMismatch(ValueType::I32, ValueType::I64),
+                },
+                ValidationError {
+                    location: Location::Func { idx: 1, offset: 1 },
+                    error: Error::TypeMismatch(ValueType::F32, ValueType::I32),
+                },
+            ]
+        );
+
+        // fail-fast mode reports only the first error
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(errors.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate_funcs() -> Result<()> {
+        let code = r#"
+(module
+  (func (result i32)
+    (i32.add (i32.const 1) (i32.const 2))
+  )
+  (func (result i32)
+    (drop (f32.neg (i32.const 1)))
+    (i64.const 0)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        // NOTE: only the first error of the function is reported
+        let errors = Validator::new(&module).validate_funcs();
+        assert_eq!(
+            errors,
+            vec![(
+                1,
+                ValidationError {
+                    location: Location::Func { idx: 1, offset: 1 },
+                    error: Error::TypeMismatch(ValueType::F32, ValueType::I32),
+                }
+            )]
+        );
+
+        let wasm = wat::parse_str("(module (func (result i32) (i32.const 0)))")?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert!(Validator::new(&module).validate_funcs().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn else_without_if() -> Result<()> {
+        let code = r#"(module (func (block (nop))) (func (nop)))"#;
+        let mut wasm = wat::parse_str(code)?;
+        // replace each nop with else, in the block and in the function
+        for bytes in [[0x02, 0x40, 0x01, 0x0b], [0x03, 0x00, 0x01, 0x0b]] {
+            let pos = wasm
+                .windows(4)
+                .position(|w| w == bytes)
+                .expect("instructions must be found");
+            wasm[pos + 2] = 0x05;
+        }
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let errors = Validator::with_mode(&module, ValidationMode::Collect)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError {
+                    location: Location::Func { idx: 0, offset: 1 },
+                    error: Error::ElseWithoutIf,
+                },
+                ValidationError {
+                    location: Location::Func { idx: 1, offset: 0 },
+                    error: Error::ElseWithoutIf,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_local() -> Result<()> {
+        // NOTE: the index space of locals consists of params and declared locals
+        let code = r#"
+(module
+  (func (param i32) (local i64)
+    (drop (local.get 1))
+    (drop (local.get 2))
+  )
+  (func (param i32)
+    (local.set 1 (i32.const 0))
+  )
+  (func (local f32)
+    (drop (local.tee 1 (f32.const 0)))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let errors = Validator::with_mode(&module, ValidationMode::Collect)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError {
+                    location: Location::Func { idx: 0, offset: 2 },
+                    error: Error::UnknownLocal(2),
+                },
+                ValidationError {
+                    location: Location::Func { idx: 1, offset: 1 },
+                    error: Error::UnknownLocal(1),
+                },
+                ValidationError {
+                    location: Location::Func { idx: 2, offset: 1 },
+                    error: Error::UnknownLocal(1),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn if_without_else() -> Result<()> {
+        let code = r#"
+(module
+  (func (param i32)
+    (if (local.get 0) (then (nop)))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(Validator::new(&module).validate(), Ok(()));
+
+        let code = r#"
+(module
+  (func (param i32) (result i32)
+    (if (result i32) (local.get 0) (then (i32.const 1)))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("if with result must have else");
+        assert_eq!(errors[0].error, Error::MissingElse);
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_operand_types() -> Result<()> {
+        let code = r#"
+(module
+  (func (result i32)
+    (select (i32.const 1) (i32.const 2) (i32.const 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(Validator::new(&module).validate(), Ok(()));
+
+        let code = r#"
+(module
+  (func (result i32)
+    (select (i32.const 1) (f32.const 2) (i32.const 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("operands of select must have the same type");
+        assert_eq!(
+            errors[0].error,
+            Error::TypeMismatch(ValueType::F32, ValueType::I32)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unreachable_code() -> Result<()> {
+        // NOTE: the operand stack is polymorphic after br, return and unreachable
+        let code = r#"
+(module
+  (func (result i32)
+    i32.const 1
+    return
+    i32.add
+  )
+  (func (result i64)
+    unreachable
+    f32.neg
+    drop
+  )
+  (func (param i32) (result i32)
+    (block (result f64)
+      br 0
+      i64.eqz
+      drop
+    )
+    drop
+    local.get 0
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(Validator::new(&module).validate(), Ok(()));
+
+        // values pushed after return still have their types
+        let code = r#"
+(module
+  (func (result i32)
+    return
+    i64.const 1
+    i32.eqz
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("known operands must be typed");
+        assert_eq!(
+            errors[0].error,
+            Error::TypeMismatch(ValueType::I32, ValueType::I64)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn ref_func_declaration() -> Result<()> {
+        let code = r#"
+(module
+  (func $declared)
+  (func $exported (export "exported"))
+  (elem declare func $declared)
+  (func (result funcref)
+    (drop (ref.func $exported))
+    (ref.func $declared)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(Validator::new(&module).validate(), Ok(()));
+
+        let code = r#"
+(module
+  (func $undeclared)
+  (func (result funcref)
+    (ref.func $undeclared)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("ref.func must refer to a declared function");
+        assert_eq!(errors[0].error, Error::UndeclaredFunctionReference(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_export_name() -> Result<()> {
+        #[rustfmt::skip]
+        let wasm = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type section, () -> i32
+            0x03, 0x03, 0x02, 0x00, 0x00, // function section
+            0x07, 0x09, 0x02, 0x01, 0x66, 0x00, 0x00, 0x01, 0x66, 0x00, 0x01, // export "f" twice
+            0x0a, 0x0b, 0x02, 0x04, 0x00, 0x41, 0x01, 0x0b, 0x04, 0x00, 0x41, 0x02, 0x0b, // code section
+        ];
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("export names must be unique");
+        assert_eq!(
+            errors,
+            vec![ValidationError {
+                location: Location::Module,
+                error: Error::DuplicateExportName("f".into()),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn select_reference_operands() -> Result<()> {
+        let code = r#"
+(module
+  (func $f)
+  (elem declare func $f)
+  (func (result funcref)
+    (select (result funcref) (ref.func $f) (ref.null func) (i32.const 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(Validator::new(&module).validate(), Ok(()));
+
+        let code = r#"
+(module
+  (func $f)
+  (elem declare func $f)
+  (func (result i32)
+    (select (i32.const 1) (ref.func $f) (i32.const 0))
+  )
+  (func (result funcref)
+    (select (result funcref) (ref.func $f) (i32.const 1) (i32.const 0))
+  )
+  (func (result funcref)
+    (select (ref.func $f) (ref.func $f) (i32.const 0))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let errors = Validator::with_mode(&module, ValidationMode::Collect)
+            .validate()
+            .expect_err("operands of select must have the same kind");
+        let errors: Vec<_> = errors.into_iter().map(|e| (e.location, e.error)).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    Location::Func { idx: 1, offset: 3 },
+                    Error::TypeMismatch(ValueType::FuncRef, ValueType::I32),
+                ),
+                (
+                    Location::Func { idx: 2, offset: 3 },
+                    Error::TypeMismatch(ValueType::FuncRef, ValueType::I32),
+                ),
+                (
+                    Location::Func { idx: 3, offset: 3 },
+                    Error::NonNumericSelect
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn data_count() -> Result<()> {
+        #[rustfmt::skip]
+        let wasm = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+            0x0c, 0x01, 0x02, // data count section, count is 2
+            0x0b, 0x04, 0x01, 0x01, 0x01, 0x61, // data section, only 1 passive segment
+        ];
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        assert_eq!(module.data_count_section, Some(2));
+
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(
+            errors,
+            vec![ValidationError {
+                location: Location::Module,
+                error: Error::DataCountMismatch(2, 1),
+            }]
+        );
+
+        let code = r#"
+(module
+  (memory 1)
+  (data "a")
+  (func
+    (memory.init 1 (i32.const 0) (i32.const 0) (i32.const 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(errors[0].error, Error::UnknownData(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn function_count() -> Result<()> {
+        #[rustfmt::skip]
+        let wasm = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section, () -> ()
+            0x03, 0x03, 0x02, 0x00, 0x00, // function section, 2 functions
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section, only 1 body
+        ];
+        let module = Decoder::new(wasm.as_slice()).decode()?;
+
+        let expected = ValidationError {
+            location: Location::Module,
+            error: Error::FunctionCountMismatch(2, 1),
+        };
+        let errors = Validator::new(&module)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(errors, vec![expected.clone()]);
+        let errors = Validator::with_mode(&module, ValidationMode::Collect)
+            .validate()
+            .expect_err("module must be invalid");
+        assert_eq!(errors, vec![expected]);
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ macro_rules! memory_load {
             &MemoryArg {
                 align: $align,
                 offset: $offset as u32,
+                memory: 0,
             },
         )?
     }};
@@ -19,6 +20,7 @@ macro_rules! memory_write {
             &MemoryArg {
                 align: $align,
                 offset: $offset as u32,
+                memory: 0,
             },
             $size as i32,
         )?;
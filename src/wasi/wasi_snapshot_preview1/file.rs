@@ -1,17 +1,25 @@
-use anyhow::Result;
-use std::io::{Read, Seek, Write};
+use anyhow::{bail, Result};
+use std::{
+    io::{self, Read, Seek, Write},
+    path::Path,
+};
 
 pub trait ReadWrite: Read + Write + Seek + Send + Sync + 'static {}
 
 impl<IO: Read + Write + Seek + Send + Sync + 'static> ReadWrite for IO {}
 
+// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fdflags-flagsu16
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum FdFlags {
     Append = 0b1,
     Dsync = 0b10,
-    Nonblock = 0b1000,
-    Rsync = 0b10000,
-    Sync = 0b100000,
+    Nonblock = 0b100,
+    Rsync = 0b1000,
+    Sync = 0b10000,
+}
+
+impl FdFlags {
+    pub const ALL: u16 = 0b11111;
 }
 
 #[derive(Debug, Clone)]
@@ -31,8 +39,9 @@ pub enum FileCaps {
     PollReadwrite = 0b1000000000000,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum FileType {
+    #[default]
     Unknown = 0,
     BlockDevice = 1,
     CharacterDevice = 2,
@@ -44,37 +53,220 @@ pub enum FileType {
     Pipe = 8,
 }
 
+impl From<std::fs::FileType> for FileType {
+    fn from(ty: std::fs::FileType) -> Self {
+        if ty.is_file() {
+            FileType::RegularFile
+        } else if ty.is_dir() {
+            FileType::Directory
+        } else if ty.is_symlink() {
+            FileType::SymbolicLink
+        } else {
+            special_file_type(&ty)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn special_file_type(ty: &std::fs::FileType) -> FileType {
+    use std::os::unix::fs::FileTypeExt;
+    if ty.is_block_device() {
+        FileType::BlockDevice
+    } else if ty.is_char_device() {
+        FileType::CharacterDevice
+    } else if ty.is_fifo() {
+        FileType::Pipe
+    } else if ty.is_socket() {
+        FileType::SocketStream
+    } else {
+        FileType::Unknown
+    }
+}
+
+#[cfg(not(unix))]
+fn special_file_type(_ty: &std::fs::FileType) -> FileType {
+    FileType::Unknown
+}
+
+// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-filestat-record
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub dev: u64,
+    pub ino: u64,
+    pub filetype: FileType,
+    pub nlink: u64,
+    pub size: u64,
+    pub atim: u64, // timestamps in nanoseconds
+    pub mtim: u64,
+    pub ctim: u64,
+}
+
+// NOTE: timestamps before the epoch are clamped to 0
+fn nanos(sec: i64, nsec: i64) -> u64 {
+    if sec < 0 {
+        return 0;
+    }
+    (sec as u64)
+        .saturating_mul(1_000_000_000)
+        .saturating_add(nsec as u64)
+}
+
+#[cfg(unix)]
+impl From<&std::fs::Metadata> for FileStat {
+    fn from(m: &std::fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            dev: m.dev(),
+            ino: m.ino(),
+            filetype: m.file_type().into(),
+            nlink: m.nlink(),
+            size: m.size(),
+            atim: nanos(m.atime(), m.atime_nsec()),
+            mtim: nanos(m.mtime(), m.mtime_nsec()),
+            ctim: nanos(m.ctime(), m.ctime_nsec()),
+        }
+    }
+}
+
+// NOTE: device and inode are not available on non-unix platforms
+#[cfg(not(unix))]
+impl From<&std::fs::Metadata> for FileStat {
+    fn from(m: &std::fs::Metadata) -> Self {
+        let time = |time: io::Result<std::time::SystemTime>| match time {
+            Ok(time) => match time.duration_since(std::time::UNIX_EPOCH) {
+                Ok(d) => nanos(d.as_secs() as i64, d.subsec_nanos() as i64),
+                Err(_) => 0,
+            },
+            Err(_) => 0,
+        };
+        Self {
+            filetype: m.file_type().into(),
+            nlink: 1,
+            size: m.len(),
+            atim: time(m.accessed()),
+            mtim: time(m.modified()),
+            ctim: time(m.created()),
+            ..Default::default()
+        }
+    }
+}
+
+impl FileStat {
+    // NOTE: the memory layout of filestat is 64 bytes
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut buf = [0u8; 64];
+        buf[0..8].copy_from_slice(&self.dev.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.ino.to_le_bytes());
+        buf[16] = self.filetype as u8;
+        buf[24..32].copy_from_slice(&self.nlink.to_le_bytes());
+        buf[32..40].copy_from_slice(&self.size.to_le_bytes());
+        buf[40..48].copy_from_slice(&self.atim.to_le_bytes());
+        buf[48..56].copy_from_slice(&self.mtim.to_le_bytes());
+        buf[56..64].copy_from_slice(&self.ctim.to_le_bytes());
+        buf
+    }
+}
+
+// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-dirent-record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dirent {
+    pub next: u64, // cookie of the next entry
+    pub ino: u64,
+    pub filetype: FileType,
+    pub name: String,
+}
+
+impl Dirent {
+    // NOTE: the header of dirent is 24 bytes, followed by the name without null terminator
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; 24];
+        buf[0..8].copy_from_slice(&self.next.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.ino.to_le_bytes());
+        buf[16..20].copy_from_slice(&(self.name.len() as u32).to_le_bytes());
+        buf[20] = self.filetype as u8;
+        buf.extend_from_slice(self.name.as_bytes());
+        buf
+    }
+}
+
+fn is_interrupted(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::Interrupted)
+}
+
 pub trait File: Send + Sync {
     fn write(&mut self, data: &[u8]) -> Result<usize>;
     fn read(&mut self, data: &mut [u8]) -> Result<usize>;
     fn seek(&mut self, pos: u64) -> Result<u64>;
+    fn seek_end(&mut self) -> Result<u64>;
     fn filetype(&self) -> Result<FileType>;
-    fn fdflags(&self) -> Result<FdFlags>;
     fn read_string(&mut self) -> Result<String>;
+    fn filestat(&mut self) -> Result<FileStat>;
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    // write the whole data, retrying on partial writes and interrupts
+    fn write_all(&mut self, mut data: &[u8]) -> Result<usize> {
+        let len = data.len();
+        while !data.is_empty() {
+            match self.write(data) {
+                Ok(0) => bail!(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => data = &data[n..],
+                Err(e) if is_interrupted(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(len)
+    }
+
+    // host path of the preopened directory
+    fn dir_path(&self) -> Option<&Path> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FdStat {
     pub filetype: FileType,
     pub caps: FileCaps,
-    pub flags: FdFlags,
+    pub flags: u16, // bits of FdFlags
 }
 
 pub struct FileEntry {
     caps: FileCaps,
+    flags: u16, // bits of FdFlags, set by path_open and fd_fdstat_set_flags
     file: Box<dyn File>,
 }
 
 impl FileEntry {
     pub fn new(file: Box<dyn File>, caps: FileCaps) -> Self {
-        Self { caps, file }
+        Self {
+            caps,
+            flags: 0,
+            file,
+        }
+    }
+
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn set_flags(&mut self, flags: u16) {
+        self.flags = flags;
+    }
+
+    pub fn has_flag(&self, flag: FdFlags) -> bool {
+        self.flags & flag as u16 != 0
     }
 
     pub fn get_fdstat(&self) -> Result<FdStat> {
         Ok(FdStat {
             filetype: self.file.filetype()?,
             caps: self.caps.clone(),
-            flags: self.file.fdflags()?,
+            flags: self.flags,
         })
     }
 
@@ -84,3 +276,17 @@ impl FileEntry {
         Ok(file)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::nanos;
+
+    #[test]
+    fn test_nanos() {
+        assert_eq!(nanos(1, 5), 1_000_000_005);
+        assert_eq!(nanos(0, 0), 0);
+        // before the epoch
+        assert_eq!(nanos(-1, 999_999_999), 0);
+        assert_eq!(nanos(i64::MAX, 0), u64::MAX);
+    }
+}
@@ -1,43 +1,51 @@
 use super::{
-    file::{FileCaps, FileEntry},
+    file::{FdFlags, FileCaps, FileEntry},
     wasi_file::WasiFile,
 };
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex},
+};
 
-pub struct FileTable(Vec<Arc<Mutex<FileEntry>>>);
+// NOTE: files are opened while the importer is borrowed, so the table is mutable inside
+pub struct FileTable(RefCell<Vec<Arc<Mutex<FileEntry>>>>);
 
+// NOTE: stdio is in append mode, as the host stdio usually is
 impl Default for FileTable {
     fn default() -> Self {
-        Self(vec![
+        Self(RefCell::new(vec![
             // stdin
-            Arc::new(Mutex::new(FileEntry::new(
-                Box::new(WasiFile::from_raw_fd(0)),
-                FileCaps::Sync,
-            ))),
+            Arc::new(Mutex::new(
+                FileEntry::new(Box::new(WasiFile::from_raw_fd(0)), FileCaps::Sync)
+                    .with_flags(FdFlags::Append as u16),
+            )),
             // stdout
-            Arc::new(Mutex::new(FileEntry::new(
-                Box::new(WasiFile::from_raw_fd(1)),
-                FileCaps::Sync,
-            ))),
+            Arc::new(Mutex::new(
+                FileEntry::new(Box::new(WasiFile::from_raw_fd(1)), FileCaps::Sync)
+                    .with_flags(FdFlags::Append as u16),
+            )),
             // stderr
-            Arc::new(Mutex::new(FileEntry::new(
-                Box::new(WasiFile::from_raw_fd(2)),
-                FileCaps::Sync,
-            ))),
-        ])
+            Arc::new(Mutex::new(
+                FileEntry::new(Box::new(WasiFile::from_raw_fd(2)), FileCaps::Sync)
+                    .with_flags(FdFlags::Append as u16),
+            )),
+        ]))
     }
 }
 
 impl FileTable {
     pub fn with_io(files: Vec<Arc<Mutex<FileEntry>>>) -> Self {
-        FileTable(files)
+        FileTable(RefCell::new(files))
     }
 
-    pub fn get(&self, idx: usize) -> Option<&Arc<Mutex<FileEntry>>> {
-        self.0.get(idx)
+    pub fn get(&self, idx: usize) -> Option<Arc<Mutex<FileEntry>>> {
+        self.0.borrow().get(idx).cloned()
     }
 
-    pub fn add(&mut self, file: Arc<Mutex<FileEntry>>) {
-        self.0.push(file);
+    // returns the fd of the added file
+    pub fn add(&self, file: Arc<Mutex<FileEntry>>) -> usize {
+        let mut files = self.0.borrow_mut();
+        files.push(file);
+        files.len() - 1
     }
 }
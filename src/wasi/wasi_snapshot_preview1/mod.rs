@@ -3,6 +3,8 @@ pub mod file_table;
 pub mod preview1;
 pub mod types;
 pub mod virtual_file;
+pub mod wasi_dir;
 pub mod wasi_file;
 
 pub use preview1::*;
+pub use types::Errno;
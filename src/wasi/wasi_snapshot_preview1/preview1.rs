@@ -1,19 +1,44 @@
-use super::{file::FileEntry, file_table::FileTable};
+use super::{
+    file::{FdFlags, FileEntry},
+    file_table::FileTable,
+    types::Errno,
+    wasi_dir::{read_dir, resolve_path, WasiDir},
+    wasi_file::WasiFile,
+};
 use crate::{
-    binary::instruction::MemoryArg, memory_load, memory_write, module::ExternalFuncInst,
-    wasi::file::FileCaps, Importer, Store, Value,
+    binary::instruction::MemoryArg,
+    execution::error::Error,
+    memory_load, memory_write,
+    module::ExternalFuncInst,
+    wasi::file::{Dirent, FileCaps, FileStat, FileType},
+    Importer, Store, Value,
 };
-use anyhow::{Context as _, Result};
+use anyhow::{bail, Context as _, Result};
 use rand::prelude::*;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    io::ErrorKind,
+    path::Path,
     rc::Rc,
     sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+// seed of the random source in deterministic mode
+pub const DETERMINISTIC_SEED: u64 = 0;
+// nanoseconds advanced by each clock_time_get in deterministic mode
+pub const DETERMINISTIC_TICK: u64 = 1_000_000;
+
+// fake time and seeded random source for reproducible runs
+struct Deterministic {
+    time: Cell<u64>,
+    rng: RefCell<StdRng>,
+}
+
 #[derive(Default)]
 pub struct WasiSnapshotPreview1 {
     file_table: FileTable,
+    deterministic: Option<Deterministic>,
 }
 
 impl Importer for WasiSnapshotPreview1 {
@@ -27,6 +52,15 @@ impl Importer for WasiSnapshotPreview1 {
         func: ExternalFuncInst,
         args: Vec<Value>,
     ) -> Result<Option<Value>> {
+        // NOTE: the guest may import the function with fewer params, it's still a valid module
+        let expected = params_len(&func.field);
+        if args.len() < expected {
+            bail!(
+                "wasi function {} expects {expected} args, got {}",
+                func.field,
+                args.len()
+            );
+        }
         let value = match func.field.as_str() {
             "fd_read" => self.fd_read(store, args),
             "fd_write" => self.fd_write(store, args),
@@ -38,8 +72,26 @@ impl Importer for WasiSnapshotPreview1 {
             "args_get" => self.args_get(store, args),
             "args_sizes_get" => self.args_sizes_get(store, args),
             "random_get" => self.random_get(store, args),
+            "clock_time_get" => self.clock_time_get(store, args),
             "fd_fdstat_get" => self.fd_fdstat_get(store, args),
-            _ => todo!(),
+            "fd_fdstat_set_flags" => self.fd_fdstat_set_flags(args),
+            "fd_filestat_get" => self.fd_filestat_get(store, args),
+            "path_filestat_get" => self.path_filestat_get(store, args),
+            "fd_readdir" => self.fd_readdir(store, args),
+            "path_create_directory" => {
+                self.path_op(store, args, |_, path| std::fs::create_dir(path))
+            }
+            "path_remove_directory" => self.path_op(store, args, |dir, path| {
+                // NOTE: the preopened directory itself can't be removed
+                if dir == path {
+                    return Err(ErrorKind::InvalidInput.into());
+                }
+                std::fs::remove_dir(path)
+            }),
+            "path_unlink_file" => self.path_op(store, args, |_, path| std::fs::remove_file(path)),
+            "path_open" => self.path_open(store, args),
+            "sched_yield" => self.sched_yield(),
+            field => bail!(Error::Unsupported(format!("wasi function {field}"))),
         }?;
         Ok(Some(value))
     }
@@ -48,7 +100,26 @@ impl Importer for WasiSnapshotPreview1 {
 impl WasiSnapshotPreview1 {
     pub fn with_io(files: Vec<Arc<Mutex<FileEntry>>>) -> Self {
         let file_table = FileTable::with_io(files);
-        Self { file_table }
+        Self {
+            file_table,
+            ..Default::default()
+        }
+    }
+
+    // expose the host directory to the guest as the next fd
+    pub fn preopen_dir(self, path: impl Into<std::path::PathBuf>) -> Self {
+        let dir = FileEntry::new(Box::new(WasiDir::new(path)), FileCaps::FilestatGet);
+        self.file_table.add(Arc::new(Mutex::new(dir)));
+        self
+    }
+
+    // make clock_time_get and random_get return reproducible values
+    pub fn deterministic(mut self) -> Self {
+        self.deterministic = Some(Deterministic {
+            time: Cell::new(0),
+            rng: RefCell::new(StdRng::seed_from_u64(DETERMINISTIC_SEED)),
+        });
+        self
     }
 
     fn proc_exit(&self, args: Vec<Value>) -> ! {
@@ -56,12 +127,16 @@ impl WasiSnapshotPreview1 {
             .get(0)
             .expect("no any argument in proc_exit")
             .clone()
-            .into();
+            .try_into()
+            .expect("exit code of proc_exit must be i32");
         std::process::exit(exit_code);
     }
 
     fn environ_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (mut offset, mut buf_offset) = (args[0] as usize, args[1] as usize);
 
         let store = store.borrow();
@@ -85,7 +160,10 @@ impl WasiSnapshotPreview1 {
     }
 
     fn environ_sizes_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (offset, buf_offset) = (args[0] as usize, args[1] as usize);
 
         let store = store.borrow();
@@ -109,7 +187,10 @@ impl WasiSnapshotPreview1 {
     }
 
     fn fd_read(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (fd, mut iovs, iovs_len, nread_offset) = (
             args[0] as usize,
             args[1] as usize,
@@ -121,14 +202,14 @@ impl WasiSnapshotPreview1 {
         let memory = store.memory.get(0).with_context(|| "not found memory")?;
         let mut memory = memory.borrow_mut();
 
-        let file = self
-            .file_table
-            .get(fd)
-            .with_context(|| format!("cannot get file with fd: {}", fd))?;
+        let Some(file) = self.file_table.get(fd) else {
+            return Ok(Errno::Badf.into());
+        };
 
-        let file = Arc::clone(file);
         let mut file = file.lock().expect("cannot lock file");
-        let file = file.capbable(FileCaps::Read)?;
+        let Ok(file) = file.capbable(FileCaps::Read) else {
+            return Ok(Errno::Notcapable.into());
+        };
 
         let mut nread = 0;
         for _ in 0..iovs_len {
@@ -138,10 +219,23 @@ impl WasiSnapshotPreview1 {
             let len: i32 = memory_load!(memory, 0, 4, iovs);
             iovs += 4;
 
-            let offset = offset as usize;
-            let end = offset + len as usize;
-
-            nread += file.read(&mut memory.data[offset..end])?;
+            let offset = offset as u32 as usize;
+            let len = len as u32 as usize;
+            let buf = offset
+                .checked_add(len)
+                .and_then(|end| memory.data.get_mut(offset..end))
+                .with_context(|| "out of bounds memory access")?;
+
+            let n = match file.read(buf) {
+                Ok(n) => n,
+                Err(e) => return errno_or_trap(e),
+            };
+            nread += n;
+
+            // NOTE: short read means that the file reached EOF
+            if n < len {
+                break;
+            }
         }
 
         memory_write!(memory, 0, 4, nread_offset, nread);
@@ -150,7 +244,10 @@ impl WasiSnapshotPreview1 {
     }
 
     fn fd_write(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (fd, mut iovs, iovs_len, rp) = (
             args[0] as usize,
             args[1] as usize,
@@ -162,14 +259,22 @@ impl WasiSnapshotPreview1 {
         let memory = store.memory.get(0).with_context(|| "not found memory")?;
         let mut memory = memory.borrow_mut();
 
-        let file = self
-            .file_table
-            .get(fd)
-            .with_context(|| format!("cannot get file with fd: {}", fd))?;
-        let file = Arc::clone(file);
+        let Some(file) = self.file_table.get(fd) else {
+            return Ok(Errno::Badf.into());
+        };
 
         let mut file = file.lock().expect("cannot lock file");
-        let file = file.capbable(FileCaps::Write)?;
+        let append = file.has_flag(FdFlags::Append);
+        let Ok(file) = file.capbable(FileCaps::Write) else {
+            return Ok(Errno::Notcapable.into());
+        };
+        // NOTE: the data is written to the end of the file, even if the cursor was moved,
+        // but files other than regular files, such as pipes, cannot be seeked
+        if append && matches!(file.filetype(), Ok(FileType::RegularFile)) {
+            if let Err(e) = file.seek_end() {
+                return errno_or_trap(e);
+            }
+        }
 
         let mut written = 0;
 
@@ -180,11 +285,20 @@ impl WasiSnapshotPreview1 {
             let len: i32 = memory_load!(memory, 0, 4, iovs);
             iovs += 4;
 
-            let offset = offset as usize;
-            let end = offset + len as usize;
-            let buf = &memory.data[offset..end];
-
-            written += file.write(buf)?;
+            let offset = offset as u32 as usize;
+            let len = len as u32 as usize;
+            let buf = offset
+                .checked_add(len)
+                .and_then(|end| memory.data.get(offset..end))
+                .with_context(|| "out of bounds memory access")?;
+
+            written += match file.write_all(buf) {
+                Ok(n) => n,
+                Err(e) => return errno_or_trap(e),
+            };
+        }
+        if let Err(e) = file.flush() {
+            return errno_or_trap(e);
         }
 
         memory_write!(memory, 0, 4, rp, written);
@@ -193,7 +307,10 @@ impl WasiSnapshotPreview1 {
     }
 
     fn args_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (mut offset, mut buf_offset) = (args[0] as usize, args[1] as usize);
 
         let store = store.borrow();
@@ -217,7 +334,10 @@ impl WasiSnapshotPreview1 {
     }
 
     fn args_sizes_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (offset, buf_offset) = (args[0] as usize, args[1] as usize);
 
         let store = store.borrow();
@@ -241,18 +361,28 @@ impl WasiSnapshotPreview1 {
     }
 
     fn random_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (mut offset, buf_len) = (args[0] as usize, args[1] as usize);
 
         let store = store.borrow();
         let memory = store.memory.get(0).with_context(|| "not found memory")?;
         let mut memory = memory.borrow_mut();
 
-        let mut rng = thread_rng();
-
         let distr = rand::distributions::Uniform::new_inclusive(1u32, 100);
-        for _ in 0..buf_len {
-            let x = rng.sample(distr);
+        let values: Vec<u32> = match &self.deterministic {
+            Some(deterministic) => {
+                let mut rng = deterministic.rng.borrow_mut();
+                (0..buf_len).map(|_| rng.sample(distr)).collect()
+            }
+            None => {
+                let mut rng = thread_rng();
+                (0..buf_len).map(|_| rng.sample(distr)).collect()
+            }
+        };
+        for x in values {
             let mut buf = std::io::Cursor::new(Vec::new());
             leb128::write::unsigned(&mut buf, x as u64)?;
             memory.write_bytes(offset, buf.into_inner().as_slice())?;
@@ -262,18 +392,47 @@ impl WasiSnapshotPreview1 {
         Ok(0.into())
     }
 
+    // NOTE: there is no other thread to yield to
+    fn sched_yield(&self) -> Result<Value> {
+        Ok(Errno::Success.into())
+    }
+
+    fn clock_time_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
+        // NOTE: the clock id and the precision are ignored
+        let offset: i32 = args[2].clone().try_into()?;
+        let offset = offset as usize;
+
+        let time = match &self.deterministic {
+            Some(deterministic) => {
+                let time = deterministic.time.get() + DETERMINISTIC_TICK;
+                deterministic.time.set(time);
+                time
+            }
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+        };
+
+        let store = store.borrow();
+        let memory = store.memory.get(0).with_context(|| "not found memory")?;
+        let mut memory = memory.borrow_mut();
+        memory.write_bytes(offset, &time.to_le_bytes())?;
+
+        Ok(0.into())
+    }
+
     fn fd_fdstat_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
-        let args: Vec<i32> = args.into_iter().map(Into::into).collect();
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
         let (fd, offset) = (args[0] as usize, args[1] as usize);
 
         let store = store.borrow();
         let memory = store.memory.get(0).with_context(|| "not found memory")?;
         let mut memory = memory.borrow_mut();
 
-        let file = self
-            .file_table
-            .get(fd)
-            .with_context(|| format!("cannot get file with fd: {}", fd))?;
+        let Some(file) = self.file_table.get(fd) else {
+            return Ok(Errno::Badf.into());
+        };
         let file = file.lock().expect("cannot lock file");
         let stat = file.get_fdstat()?;
 
@@ -283,6 +442,310 @@ impl WasiSnapshotPreview1 {
 
         Ok(0.into())
     }
+
+    // https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_fdstat_set_flagsfd-fd-flags-fdflags---result-errno
+    // NOTE: the flags are only stored except append, nonblocking io is not supported
+    fn fd_fdstat_set_flags(&self, args: Vec<Value>) -> Result<Value> {
+        let fd: i32 = args[0].clone().try_into()?;
+        let flags: i32 = args[1].clone().try_into()?;
+
+        let Some(file) = self.file_table.get(fd as usize) else {
+            return Ok(Errno::Badf.into());
+        };
+        let Ok(flags) = u16::try_from(flags) else {
+            return Ok(Errno::Inval.into());
+        };
+        if flags & !FdFlags::ALL != 0 {
+            return Ok(Errno::Inval.into());
+        }
+        let mut file = file.lock().expect("cannot lock file");
+        if file.capbable(FileCaps::FdstatSetFlags).is_err() {
+            return Ok(Errno::Notcapable.into());
+        }
+        file.set_flags(flags);
+
+        Ok(Errno::Success.into())
+    }
+
+    fn fd_filestat_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (fd, offset) = (args[0] as usize, args[1] as usize);
+
+        let store = store.borrow();
+        let memory = store.memory.get(0).with_context(|| "not found memory")?;
+        let mut memory = memory.borrow_mut();
+
+        let Some(file) = self.file_table.get(fd) else {
+            return Ok(Errno::Badf.into());
+        };
+        let mut file = file.lock().expect("cannot lock file");
+        let Ok(file) = file.capbable(FileCaps::FilestatGet) else {
+            return Ok(Errno::Notcapable.into());
+        };
+        let stat = match file.filestat() {
+            Ok(stat) => stat,
+            Err(e) => return errno_or_trap(e),
+        };
+        memory.write_bytes(offset, &stat.to_bytes())?;
+
+        Ok(Errno::Success.into())
+    }
+
+    fn path_filestat_get(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (fd, flags, path, path_len, offset) = (
+            args[0] as usize,
+            args[1],
+            args[2] as usize,
+            args[3] as usize,
+            args[4] as usize,
+        );
+
+        let store = store.borrow();
+        let memory = store.memory.get(0).with_context(|| "not found memory")?;
+        let mut memory = memory.borrow_mut();
+
+        let Some(file) = self.file_table.get(fd) else {
+            return Ok(Errno::Badf.into());
+        };
+        let mut file = file.lock().expect("cannot lock file");
+        let Ok(file) = file.capbable(FileCaps::FilestatGet) else {
+            return Ok(Errno::Notcapable.into());
+        };
+        let Some(dir) = file.dir_path() else {
+            return Ok(Errno::Notdir.into());
+        };
+
+        let path = path
+            .checked_add(path_len)
+            .and_then(|end| memory.data.get(path..end))
+            .with_context(|| "out of bounds memory access")?;
+        let Ok(path) = std::str::from_utf8(path) else {
+            return Ok(Errno::Inval.into());
+        };
+        // NOTE: the first bit of lookupflags means following symbolic links,
+        // the resolved path has no link to follow except the last one without the flag
+        let path = match resolve_path(dir, path, flags & 1 != 0) {
+            Ok(path) => path,
+            Err(errno) => return Ok(errno.into()),
+        };
+        let stat = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => FileStat::from(&metadata),
+            Err(e) => return Ok(Errno::from_io_error(&e).into()),
+        };
+        memory.write_bytes(offset, &stat.to_bytes())?;
+
+        Ok(Errno::Success.into())
+    }
+
+    // apply the operation to the guest path in the preopened directory,
+    // it's called with the directory and the resolved path
+    fn path_op(
+        &self,
+        store: Rc<RefCell<Store>>,
+        args: Vec<Value>,
+        op: impl FnOnce(&Path, &Path) -> std::io::Result<()>,
+    ) -> Result<Value> {
+        let args = args
+            .into_iter()
+            .map(i32::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (fd, path, path_len) = (args[0] as usize, args[1] as usize, args[2] as usize);
+
+        let store = store.borrow();
+        let memory = store.memory.get(0).with_context(|| "not found memory")?;
+        let memory = memory.borrow();
+
+        let Some(file) = self.file_table.get(fd) else {
+            return Ok(Errno::Badf.into());
+        };
+        let mut file = file.lock().expect("cannot lock file");
+        let Ok(file) = file.capbable(FileCaps::Write) else {
+            return Ok(Errno::Notcapable.into());
+        };
+        let Some(dir) = file.dir_path() else {
+            return Ok(Errno::Notdir.into());
+        };
+
+        let path = path
+            .checked_add(path_len)
+            .and_then(|end| memory.data.get(path..end))
+            .with_context(|| "out of bounds memory access")?;
+        let Ok(path) = std::str::from_utf8(path) else {
+            return Ok(Errno::Inval.into());
+        };
+        // NOTE: the last symbolic link is not followed, so that the link itself is removed
+        let path = match resolve_path(dir, path, false) {
+            Ok(path) => path,
+            Err(errno) => return Ok(errno.into()),
+        };
+
+        match op(dir, &path) {
+            Ok(()) => Ok(Errno::Success.into()),
+            Err(e) => Ok(Errno::from_io_error(&e).into()),
+        }
+    }
+
+    // https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-path_openfd-fd-dirflags-lookupflags-path-string-oflags-oflags-fs_rights_base-rights-fs_rights_inheriting-rights-fdflags-fdflags---resultfd-errno
+    // NOTE: the inheriting rights are ignored
+    fn path_open(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
+        let fd: i32 = args[0].clone().try_into()?;
+        let lookupflags: i32 = args[1].clone().try_into()?;
+        let path: i32 = args[2].clone().try_into()?;
+        let path_len: i32 = args[3].clone().try_into()?;
+        let oflags: i32 = args[4].clone().try_into()?;
+        let rights: i64 = args[5].clone().try_into()?;
+        let fdflags: i32 = args[7].clone().try_into()?;
+        let fd_offset: i32 = args[8].clone().try_into()?;
+        let (path, path_len) = (path as u32 as usize, path_len as u32 as usize);
+
+        let store = store.borrow();
+        let memory = store.memory.get(0).with_context(|| "not found memory")?;
+        let mut memory = memory.borrow_mut();
+
+        let Some(file) = self.file_table.get(fd as usize) else {
+            return Ok(Errno::Badf.into());
+        };
+        let mut file = file.lock().expect("cannot lock file");
+        let Ok(file) = file.capbable(FileCaps::Read) else {
+            return Ok(Errno::Notcapable.into());
+        };
+        let Some(dir) = file.dir_path() else {
+            return Ok(Errno::Notdir.into());
+        };
+
+        let path = path
+            .checked_add(path_len)
+            .and_then(|end| memory.data.get(path..end))
+            .with_context(|| "out of bounds memory access")?;
+        let Ok(path) = std::str::from_utf8(path) else {
+            return Ok(Errno::Inval.into());
+        };
+        // NOTE: the first bit of lookupflags means following symbolic links
+        let follow = lookupflags & 1 != 0;
+        let path = match resolve_path(dir, path, follow) {
+            Ok(path) => path,
+            Err(errno) => return Ok(errno.into()),
+        };
+        // NOTE: the last symbolic link is not opened without following it, same as O_NOFOLLOW
+        if !follow && std::fs::symlink_metadata(&path).is_ok_and(|m| m.is_symlink()) {
+            return Ok(Errno::Loop.into());
+        }
+
+        // https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-oflags-flagsu16
+        let (creat, directory, excl, trunc) = (
+            oflags & 0b1 != 0,
+            oflags & 0b10 != 0,
+            oflags & 0b100 != 0,
+            oflags & 0b1000 != 0,
+        );
+        let entry = if directory {
+            match std::fs::metadata(&path) {
+                Ok(metadata) if metadata.is_dir() => {
+                    FileEntry::new(Box::new(WasiDir::new(path)), FileCaps::FilestatGet)
+                }
+                Ok(_) => return Ok(Errno::Notdir.into()),
+                Err(e) => return Ok(Errno::from_io_error(&e).into()),
+            }
+        } else {
+            // NOTE: std requires the write access to create or truncate the file
+            let write = rights & FileCaps::Write as i64 != 0 || creat || trunc;
+            // NOTE: the host file is not opened in append mode, since fd_write emulates it
+            // by the flags, which can be changed by fd_fdstat_set_flags
+            let append = fdflags & FdFlags::Append as i32 != 0;
+            let opened = std::fs::OpenOptions::new()
+                .read(rights & FileCaps::Read as i64 != 0 || !write)
+                .write(write || append)
+                .create(creat && !excl)
+                .create_new(creat && excl)
+                .truncate(trunc)
+                .open(&path);
+            match opened {
+                Ok(opened) => FileEntry::new(Box::new(WasiFile::new(opened)), FileCaps::Sync)
+                    .with_flags(fdflags as u16),
+                Err(e) => return Ok(Errno::from_io_error(&e).into()),
+            }
+        };
+
+        let opened_fd = self.file_table.add(Arc::new(Mutex::new(entry)));
+        memory_write!(memory, 0, 4, fd_offset as u32 as usize, opened_fd);
+
+        Ok(Errno::Success.into())
+    }
+
+    // NOTE: the last entry is truncated when the buffer is too small,
+    // the guest can resume reading with the cookie of the previous entry
+    fn fd_readdir(&self, store: Rc<RefCell<Store>>, args: Vec<Value>) -> Result<Value> {
+        let fd: i32 = args[0].clone().try_into()?;
+        let buf: i32 = args[1].clone().try_into()?;
+        let buf_len: i32 = args[2].clone().try_into()?;
+        let cookie: i64 = args[3].clone().try_into()?;
+        let bufused_offset: i32 = args[4].clone().try_into()?;
+        let (buf, buf_len) = (buf as u32 as usize, buf_len as u32 as usize);
+
+        let store = store.borrow();
+        let memory = store.memory.get(0).with_context(|| "not found memory")?;
+        let mut memory = memory.borrow_mut();
+
+        let Some(file) = self.file_table.get(fd as usize) else {
+            return Ok(Errno::Badf.into());
+        };
+        let mut file = file.lock().expect("cannot lock file");
+        let Ok(file) = file.capbable(FileCaps::Read) else {
+            return Ok(Errno::Notcapable.into());
+        };
+        let Some(dir) = file.dir_path() else {
+            return Ok(Errno::Notdir.into());
+        };
+        let dirents = match read_dir(dir) {
+            Ok(dirents) => dirents,
+            Err(e) => return Ok(Errno::from_io_error(&e).into()),
+        };
+
+        let bytes: Vec<u8> = dirents
+            .iter()
+            .skip(cookie as u64 as usize)
+            .flat_map(Dirent::to_bytes)
+            .collect();
+        let bufused = bytes.len().min(buf_len);
+        let data = buf
+            .checked_add(bufused)
+            .and_then(|end| memory.data.get_mut(buf..end))
+            .with_context(|| "out of bounds memory access")?;
+        data.copy_from_slice(&bytes[..bufused]);
+        memory_write!(memory, 0, 4, bufused_offset, bufused);
+
+        Ok(Errno::Success.into())
+    }
+}
+
+// number of params of the wasi function, 0 for the unknown function
+fn params_len(field: &str) -> usize {
+    match field {
+        "proc_exit" => 1,
+        "environ_get" | "environ_sizes_get" | "args_get" | "args_sizes_get" | "random_get" => 2,
+        "fd_fdstat_get" | "fd_fdstat_set_flags" | "fd_filestat_get" => 2,
+        "clock_time_get" => 3,
+        "path_create_directory" | "path_remove_directory" | "path_unlink_file" => 3,
+        "fd_read" | "fd_write" => 4,
+        "path_filestat_get" | "fd_readdir" => 5,
+        "path_open" => 9,
+        _ => 0,
+    }
+}
+
+// report the io error of the host to the guest as errno, the other errors are traps
+fn errno_or_trap(e: anyhow::Error) -> Result<Value> {
+    match e.downcast_ref::<std::io::Error>() {
+        Some(io_error) => Ok(Errno::from_io_error(io_error).into()),
+        None => Err(e),
+    }
 }
 
 fn get_memory<T>(input: &T) -> &[u8] {
@@ -295,11 +758,40 @@ mod tests {
 
     use super::*;
     use crate::{
-        wasi::{file::FileEntry, wasi_snapshot_preview1::virtual_file::VirtualFile},
-        Runtime,
+        module::MemoryInst,
+        wasi::{
+            file::{FileEntry, FileType},
+            wasi_snapshot_preview1::virtual_file::VirtualFile,
+        },
+        Exports, Runtime,
     };
     use pretty_assertions::assert_eq;
 
+    // temporary host directory, which is removed even if the test fails
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Result<Self> {
+            let dir = std::env::temp_dir().join(format!("chibiwasm-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+            Ok(Self(dir))
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
     #[test]
     fn test_fd_write() -> Result<()> {
         let code = r#"
@@ -345,7 +837,7 @@ mod tests {
         let result: i32 = runtime
             .call("_start".into(), vec![])?
             .expect("not found result")
-            .into();
+            .try_into()?;
         assert_eq!(result, 0);
 
         let mut stdout = stdout.lock().expect("cannot lock stdout");
@@ -355,6 +847,142 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fd_write_partial() -> Result<()> {
+        // NOTE: accepts only one byte per write, and is interrupted every other write
+        struct Trickle {
+            buf: std::io::Cursor<Vec<u8>>,
+            interrupt: bool,
+        }
+        impl std::io::Write for Trickle {
+            fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+                self.interrupt = !self.interrupt;
+                if self.interrupt {
+                    return Err(std::io::ErrorKind::Interrupted.into());
+                }
+                self.buf.write(&data[..data.len().min(1)])
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl std::io::Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.buf.read(buf)
+            }
+        }
+        impl std::io::Seek for Trickle {
+            fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+                self.buf.seek(pos)
+            }
+        }
+
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32))
+  )
+  (memory 1)
+  (data (i32.const 0) "Hello, World!\n")
+
+  (func $hello_world (result i32)
+    (i32.store (i32.const 16) (i32.const 0))
+    (i32.store (i32.const 20) (i32.const 7))
+    (i32.store (i32.const 24) (i32.const 7))
+    (i32.store (i32.const 28) (i32.const 7))
+
+    (drop (call $fd_write
+      (i32.const 1)
+      (i32.const 16)
+      (i32.const 2)
+      (i32.const 32)
+    ))
+    (i32.load (i32.const 32))
+  )
+  (export "_start" (func $hello_world))
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let stdin = Arc::new(Mutex::new(FileEntry::new(
+            Box::<VirtualFile>::default(),
+            FileCaps::Sync,
+        )));
+        let trickle = Trickle {
+            buf: Default::default(),
+            interrupt: false,
+        };
+        let stdout = Arc::new(Mutex::new(FileEntry::new(
+            Box::new(VirtualFile::from_io(trickle)),
+            FileCaps::Sync,
+        )));
+
+        let wasi = WasiSnapshotPreview1::with_io(vec![stdin, stdout.clone()]);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        let written: i32 = runtime
+            .call("_start".into(), vec![])?
+            .expect("not found result")
+            .try_into()?;
+        assert_eq!(written, 14);
+
+        let mut stdout = stdout.lock().expect("cannot lock stdout");
+        let stdout = stdout.capbable(FileCaps::Seek)?;
+        stdout.seek(0)?;
+        assert_eq!(stdout.read_string()?, "Hello, World!\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fd_write_out_of_bounds() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32))
+  )
+  (memory 1)
+
+  (func (export "write") (param $offset i32) (param $len i32) (result i32)
+    (i32.store (i32.const 0) (local.get $offset))
+    (i32.store (i32.const 4) (local.get $len))
+    (call $fd_write
+      (i32.const 1)
+      (i32.const 0)
+      (i32.const 1)
+      (i32.const 8)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let stdin = Arc::new(Mutex::new(FileEntry::new(
+            Box::<VirtualFile>::default(),
+            FileCaps::Sync,
+        )));
+        let stdout = Arc::new(Mutex::new(FileEntry::new(
+            Box::<VirtualFile>::default(),
+            FileCaps::Sync,
+        )));
+
+        let wasi = WasiSnapshotPreview1::with_io(vec![stdin, stdout.clone()]);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        for (offset, len) in [(65530, 100), (-1, 2), (0, -1)] {
+            let result = runtime.call("write".into(), vec![Value::I32(offset), Value::I32(len)]);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "out of bounds memory access"
+            );
+        }
+
+        let mut stdout = stdout.lock().expect("cannot lock stdout");
+        let stdout = stdout.capbable(FileCaps::Seek)?;
+        stdout.seek(0)?;
+        assert_eq!(stdout.read_string()?, "");
+        Ok(())
+    }
+
     #[test]
     fn test_args_get() -> Result<()> {
         let wasm = wat::parse_file("examples/args_get.wasm")?;
@@ -407,4 +1035,969 @@ mod tests {
         assert_eq!(stdout.read_string()?, "input: got: hello world\n");
         Ok(())
     }
+
+    #[test]
+    fn test_fd_read_multiple_iovecs() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_read"
+    (func $fd_read (param i32 i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+
+  (func $read (export "read") (result i32)
+    ;; iovecs
+    (i32.store (i32.const 0) (i32.const 100))
+    (i32.store (i32.const 4) (i32.const 3))
+    (i32.store (i32.const 8) (i32.const 200))
+    (i32.store (i32.const 12) (i32.const 3))
+    (i32.store (i32.const 16) (i32.const 300))
+    (i32.store (i32.const 20) (i32.const 16))
+
+    (call $fd_read
+      (i32.const 0)
+      (i32.const 0)
+      (i32.const 3)
+      (i32.const 64)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let stdin = Arc::new(Mutex::new(FileEntry::new(
+            Box::new(VirtualFile::new(b"0123456789")),
+            FileCaps::Sync,
+        )));
+
+        let wasi = WasiSnapshotPreview1::with_io(vec![stdin]);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        let result: i32 = runtime
+            .call("read".into(), vec![])?
+            .expect("not found result")
+            .try_into()?;
+        assert_eq!(result, 0);
+
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("not found memory");
+        };
+        let memory = memory.borrow();
+        assert_eq!(&memory.data[100..103], b"012");
+        assert_eq!(&memory.data[200..203], b"345");
+        assert_eq!(&memory.data[300..304], b"6789");
+        assert_eq!(&memory.data[304..316], &[0; 12]);
+        assert_eq!(&memory.data[64..68], &10_i32.to_le_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_fd() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_read"
+    (func $fd_read (param i32 i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_fdstat_get"
+    (func $fd_fdstat_get (param i32 i32) (result i32))
+  )
+  (memory 1)
+
+  (func (export "fd_read") (param i32) (result i32)
+    (call $fd_read (local.get 0) (i32.const 0) (i32.const 0) (i32.const 8))
+  )
+  (func (export "fd_write") (param i32) (result i32)
+    (call $fd_write (local.get 0) (i32.const 0) (i32.const 0) (i32.const 8))
+  )
+  (func (export "fd_fdstat_get") (param i32) (result i32)
+    (call $fd_fdstat_get (local.get 0) (i32.const 8))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let wasi = WasiSnapshotPreview1::default();
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        for name in ["fd_read", "fd_write", "fd_fdstat_get"] {
+            let result = runtime.call(name.into(), vec![Value::I32(10)])?;
+            assert_eq!(result, Some(Errno::Badf.into()), "{name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_args() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_fdstat_set_flags"
+    (func $fd_fdstat_set_flags (param i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "path_unlink_file"
+    (func $path_unlink_file (result i32))
+  )
+  (memory 1)
+
+  (func (export "path_open") (result i32)
+    (call $path_open (i32.const 3) (i32.const 0))
+  )
+  (func (export "fd_fdstat_set_flags") (result i32)
+    (call $fd_fdstat_set_flags (i32.const 1))
+  )
+  (func (export "path_unlink_file") (result i32)
+    (call $path_unlink_file)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let wasi = WasiSnapshotPreview1::default();
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        for (name, expected) in [
+            ("path_open", "expects 9 args, got 2"),
+            ("fd_fdstat_set_flags", "expects 2 args, got 1"),
+            ("path_unlink_file", "expects 3 args, got 0"),
+        ] {
+            let err = runtime.call(name.into(), vec![]).unwrap_err();
+            assert!(err.to_string().contains(expected), "{name}: {err}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sched_yield() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "sched_yield" (func $sched_yield (result i32)))
+  (func (export "_start") (result i32)
+    (call $sched_yield)
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+        let wasi = WasiSnapshotPreview1::default();
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        let result = runtime.call("_start".into(), vec![])?;
+        assert_eq!(result, Some(Value::I32(0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deterministic() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "random_get"
+    (func $random_get (param i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "clock_time_get"
+    (func $clock_time_get (param i32 i64 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+
+  (func $start (export "_start")
+    (drop (call $random_get (i32.const 0) (i32.const 16)))
+    (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 32)))
+    (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 40)))
+
+    ;; write random bytes to stdout
+    (i32.store (i32.const 48) (i32.const 0))
+    (i32.store (i32.const 52) (i32.const 16))
+    (drop (call $fd_write (i32.const 1) (i32.const 48) (i32.const 1) (i32.const 56)))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let run = || -> Result<(String, u64, u64)> {
+            let stdin = Arc::new(Mutex::new(FileEntry::new(
+                Box::<VirtualFile>::default(),
+                FileCaps::Sync,
+            )));
+            let stdout = Arc::new(Mutex::new(FileEntry::new(
+                Box::<VirtualFile>::default(),
+                FileCaps::Sync,
+            )));
+
+            let wasi = WasiSnapshotPreview1::with_io(vec![stdin, stdout.clone()]).deterministic();
+            let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+            runtime.call("_start".into(), vec![])?;
+
+            let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+                panic!("not found memory");
+            };
+            let memory = memory.borrow();
+            let time1 = u64::from_le_bytes(memory.data[32..40].try_into()?);
+            let time2 = u64::from_le_bytes(memory.data[40..48].try_into()?);
+
+            let mut stdout = stdout.lock().expect("cannot lock stdout");
+            let stdout = stdout.capbable(FileCaps::Read)?;
+            stdout.seek(0)?;
+            Ok((stdout.read_string()?, time1, time2))
+        };
+
+        let (output1, time1, time2) = run()?;
+        assert_eq!(output1.len(), 16);
+        assert!(time1 < time2);
+
+        let (output2, time3, time4) = run()?;
+        assert_eq!(output1, output2);
+        assert_eq!((time1, time2), (time3, time4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filestat_get() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_filestat_get"
+    (func $fd_filestat_get (param i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "path_filestat_get"
+    (func $path_filestat_get (param i32 i32 i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "hello.txt")
+  (data (i32.const 16) "../hello.txt")
+
+  (func (export "fd_filestat_get") (param i32) (result i32)
+    (call $fd_filestat_get (local.get 0) (i32.const 64))
+  )
+  (func (export "path_filestat_get") (param i32 i32) (result i32)
+    (call $path_filestat_get
+      (i32.const 3)
+      (i32.const 1)
+      (local.get 0)
+      (local.get 1)
+      (i32.const 64)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = TempDir::new("filestat")?;
+        std::fs::write(dir.join("hello.txt"), b"hello world")?;
+
+        let files = (0..3)
+            .map(|_| {
+                Arc::new(Mutex::new(FileEntry::new(
+                    Box::new(VirtualFile::new(b"abc")),
+                    FileCaps::Sync,
+                )))
+            })
+            .collect();
+        let wasi = WasiSnapshotPreview1::with_io(files).preopen_dir(&*dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("not found memory");
+        };
+        // filetype and size of the filestat
+        let stat = |memory: &MemoryInst| -> Result<(u8, u64)> {
+            let memory = memory.borrow();
+            let filetype = memory.data[64 + 16];
+            let size = u64::from_le_bytes(memory.data[64 + 32..64 + 40].try_into()?);
+            Ok((filetype, size))
+        };
+
+        let args = vec![Value::I32(0), Value::I32(9)];
+        let result = runtime.call("path_filestat_get".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(stat(&memory)?, (FileType::RegularFile as u8, 11));
+
+        // not found in the directory
+        let args = vec![Value::I32(0), Value::I32(5)];
+        let result = runtime.call("path_filestat_get".into(), args)?;
+        assert_eq!(result, Some(Errno::Noent.into()));
+
+        // escaping the directory
+        let args = vec![Value::I32(16), Value::I32(12)];
+        let result = runtime.call("path_filestat_get".into(), args)?;
+        assert_eq!(result, Some(Errno::Notcapable.into()));
+
+        let result = runtime.call("fd_filestat_get".into(), vec![Value::I32(0)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(stat(&memory)?, (FileType::RegularFile as u8, 3));
+
+        let result = runtime.call("fd_filestat_get".into(), vec![Value::I32(3)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(stat(&memory)?.0, FileType::Directory as u8);
+
+        let result = runtime.call("fd_filestat_get".into(), vec![Value::I32(4)])?;
+        assert_eq!(result, Some(Errno::Badf.into()));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_filestat_get_symlink() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_filestat_get"
+    (func $path_filestat_get (param i32 i32 i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "out/secret.txt")
+  (data (i32.const 16) "escape")
+  (data (i32.const 32) "link")
+
+  (func (export "path_filestat_get") (param i32 i32 i32) (result i32)
+    (call $path_filestat_get
+      (i32.const 3)
+      (local.get 0) ;; lookupflags
+      (local.get 1)
+      (local.get 2)
+      (i32.const 64)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = TempDir::new("filestat-symlink")?;
+        let outside = TempDir::new("filestat-outside")?;
+        std::fs::write(dir.join("hello.txt"), b"hello world")?;
+        std::fs::write(outside.join("secret.txt"), b"secret")?;
+        symlink(&*outside, dir.join("out"))?;
+        symlink(outside.join("secret.txt"), dir.join("escape"))?;
+        symlink("hello.txt", dir.join("link"))?;
+
+        let wasi = WasiSnapshotPreview1::default().preopen_dir(&*dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("not found memory");
+        };
+        let mut call = |lookupflags: i32, path: i32, len: i32| -> Result<Option<Value>> {
+            let args = vec![Value::I32(lookupflags), Value::I32(path), Value::I32(len)];
+            runtime.call("path_filestat_get".into(), args)
+        };
+        // filetype and size of the filestat
+        let stat = || -> Result<(u8, u64)> {
+            let memory = memory.borrow();
+            let filetype = memory.data[64 + 16];
+            let size = u64::from_le_bytes(memory.data[64 + 32..64 + 40].try_into()?);
+            Ok((filetype, size))
+        };
+
+        // links in the middle of the path are followed regardless of the flag
+        assert_eq!(call(1, 0, 14)?, Some(Errno::Notcapable.into()));
+        assert_eq!(call(0, 0, 14)?, Some(Errno::Notcapable.into()));
+
+        // the last link is followed only with the flag
+        assert_eq!(call(1, 16, 6)?, Some(Errno::Notcapable.into()));
+        assert_eq!(call(0, 16, 6)?, Some(Errno::Success.into()));
+        assert_eq!(stat()?.0, FileType::SymbolicLink as u8);
+
+        assert_eq!(call(1, 32, 4)?, Some(Errno::Success.into()));
+        assert_eq!(stat()?, (FileType::RegularFile as u8, 11));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fd_readdir() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_readdir"
+    (func $fd_readdir (param i32 i32 i32 i64 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+
+  (func (export "fd_readdir") (param i32 i32 i64) (result i32)
+    (call $fd_readdir
+      (local.get 0)
+      (i32.const 0)
+      (local.get 1)
+      (local.get 2)
+      (i32.const 4096)
+    )
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = TempDir::new("readdir")?;
+        std::fs::write(dir.join("a.txt"), b"a")?;
+        std::fs::write(dir.join("b.txt"), b"b")?;
+
+        let files = (0..3)
+            .map(|_| {
+                Arc::new(Mutex::new(FileEntry::new(
+                    Box::<VirtualFile>::default(),
+                    FileCaps::Sync,
+                )))
+            })
+            .collect();
+        let wasi = WasiSnapshotPreview1::with_io(files).preopen_dir(&*dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("not found memory");
+        };
+        // (next, type, name) of each dirent, and the used buffer size
+        type Dirents = (Vec<(u64, u8, String)>, usize);
+        let dirents = |memory: &MemoryInst| -> Result<Dirents> {
+            let memory = memory.borrow();
+            let bufused = u32::from_le_bytes(memory.data[4096..4100].try_into()?) as usize;
+            let mut dirents = vec![];
+            let mut offset = 0;
+            while offset + 24 <= bufused {
+                let next = u64::from_le_bytes(memory.data[offset..offset + 8].try_into()?);
+                let namlen = u32::from_le_bytes(memory.data[offset + 16..offset + 20].try_into()?);
+                let name = &memory.data[offset + 24..offset + 24 + namlen as usize];
+                let name = String::from_utf8(name.to_vec())?;
+                dirents.push((next, memory.data[offset + 20], name));
+                offset += 24 + namlen as usize;
+            }
+            Ok((dirents, bufused))
+        };
+        let file = FileType::RegularFile as u8;
+
+        let args = vec![Value::I32(3), Value::I32(1024), Value::I64(0)];
+        let result = runtime.call("fd_readdir".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(
+            dirents(&memory)?,
+            (
+                vec![(1, file, "a.txt".into()), (2, file, "b.txt".into())],
+                58
+            )
+        );
+
+        // resume from the cookie of the first entry
+        let args = vec![Value::I32(3), Value::I32(1024), Value::I64(1)];
+        let result = runtime.call("fd_readdir".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(dirents(&memory)?, (vec![(2, file, "b.txt".into())], 29));
+
+        // the second entry is truncated
+        let args = vec![Value::I32(3), Value::I32(40), Value::I64(0)];
+        let result = runtime.call("fd_readdir".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(dirents(&memory)?, (vec![(1, file, "a.txt".into())], 40));
+
+        let args = vec![Value::I32(0), Value::I32(1024), Value::I64(0)];
+        let result = runtime.call("fd_readdir".into(), args)?;
+        assert_eq!(result, Some(Errno::Notdir.into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_create_and_remove() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_create_directory"
+    (func $path_create_directory (param i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "path_remove_directory"
+    (func $path_remove_directory (param i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "path_unlink_file"
+    (func $path_unlink_file (param i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "sub")
+  (data (i32.const 16) "sub/a.txt")
+  (data (i32.const 32) "../sub")
+  (data (i32.const 48) ".")
+
+  (func (export "path_create_directory") (param i32 i32) (result i32)
+    (call $path_create_directory (i32.const 3) (local.get 0) (local.get 1))
+  )
+  (func (export "path_remove_directory") (param i32 i32) (result i32)
+    (call $path_remove_directory (i32.const 3) (local.get 0) (local.get 1))
+  )
+  (func (export "path_unlink_file") (param i32 i32) (result i32)
+    (call $path_unlink_file (i32.const 3) (local.get 0) (local.get 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = std::env::temp_dir().join(format!("chibiwasm-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let files = (0..3)
+            .map(|_| {
+                Arc::new(Mutex::new(FileEntry::new(
+                    Box::<VirtualFile>::default(),
+                    FileCaps::Sync,
+                )))
+            })
+            .collect();
+        let wasi = WasiSnapshotPreview1::with_io(files).preopen_dir(&dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let mut call = |name: &str, path: i32, len: i32| -> Result<Option<Value>> {
+            runtime.call(name.into(), vec![Value::I32(path), Value::I32(len)])
+        };
+
+        assert_eq!(
+            call("path_create_directory", 0, 3)?,
+            Some(Errno::Success.into())
+        );
+        assert!(dir.join("sub").is_dir());
+        assert_eq!(
+            call("path_create_directory", 0, 3)?,
+            Some(Errno::Exist.into())
+        );
+
+        std::fs::write(dir.join("sub/a.txt"), b"a")?;
+        assert_eq!(
+            call("path_remove_directory", 0, 3)?,
+            Some(Errno::Notempty.into())
+        );
+        // NOTE: unlinking a directory is EISDIR on linux, but EPERM (mapped to acces) on macOS
+        let errno = call("path_unlink_file", 0, 3)?;
+        assert!(
+            [Errno::Isdir, Errno::Acces]
+                .into_iter()
+                .any(|e| errno == Some(e.into())),
+            "{errno:?}"
+        );
+        assert_eq!(
+            call("path_unlink_file", 16, 9)?,
+            Some(Errno::Success.into())
+        );
+        assert_eq!(call("path_unlink_file", 16, 9)?, Some(Errno::Noent.into()));
+
+        assert_eq!(
+            call("path_remove_directory", 0, 3)?,
+            Some(Errno::Success.into())
+        );
+        assert!(!dir.join("sub").exists());
+        assert_eq!(
+            call("path_remove_directory", 0, 3)?,
+            Some(Errno::Noent.into())
+        );
+
+        // escaping the directory, or removing the directory itself
+        assert_eq!(
+            call("path_create_directory", 32, 6)?,
+            Some(Errno::Notcapable.into())
+        );
+        assert_eq!(
+            call("path_remove_directory", 48, 1)?,
+            Some(Errno::Inval.into())
+        );
+        assert!(dir.is_dir());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_op_symlink() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_create_directory"
+    (func $path_create_directory (param i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "path_remove_directory"
+    (func $path_remove_directory (param i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "path_unlink_file"
+    (func $path_unlink_file (param i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "out/new")
+  (data (i32.const 16) "out/sub")
+  (data (i32.const 32) "out/secret.txt")
+  (data (i32.const 48) "out")
+
+  (func (export "path_create_directory") (param i32 i32) (result i32)
+    (call $path_create_directory (i32.const 3) (local.get 0) (local.get 1))
+  )
+  (func (export "path_remove_directory") (param i32 i32) (result i32)
+    (call $path_remove_directory (i32.const 3) (local.get 0) (local.get 1))
+  )
+  (func (export "path_unlink_file") (param i32 i32) (result i32)
+    (call $path_unlink_file (i32.const 3) (local.get 0) (local.get 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = TempDir::new("path-symlink")?;
+        let outside = TempDir::new("path-outside")?;
+        std::fs::create_dir(outside.join("sub"))?;
+        std::fs::write(outside.join("secret.txt"), b"secret")?;
+        symlink(&*outside, dir.join("out"))?;
+
+        let wasi = WasiSnapshotPreview1::default().preopen_dir(&*dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let mut call = |name: &str, path: i32, len: i32| -> Result<Option<Value>> {
+            runtime.call(name.into(), vec![Value::I32(path), Value::I32(len)])
+        };
+
+        // nothing is changed outside the preopened directory through the symlinked directory
+        assert_eq!(
+            call("path_create_directory", 0, 7)?,
+            Some(Errno::Notcapable.into())
+        );
+        assert!(!outside.join("new").exists());
+        assert_eq!(
+            call("path_remove_directory", 16, 7)?,
+            Some(Errno::Notcapable.into())
+        );
+        assert!(outside.join("sub").is_dir());
+        assert_eq!(
+            call("path_unlink_file", 32, 14)?,
+            Some(Errno::Notcapable.into())
+        );
+        assert!(outside.join("secret.txt").is_file());
+
+        // the link itself is removed, not the linked directory
+        assert_eq!(
+            call("path_unlink_file", 48, 3)?,
+            Some(Errno::Success.into())
+        );
+        assert!(!dir.join("out").exists());
+        assert!(outside.is_dir());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_open() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_read"
+    (func $fd_read (param i32 i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "a.txt")
+  (data (i32.const 16) "missing.txt")
+  (data (i32.const 32) "../a.txt")
+
+  ;; iovec of 16 bytes at 128
+  (data (i32.const 64) "\80\00\00\00\10\00\00\00")
+
+  (func (export "path_open") (param i32 i32 i32) (result i32)
+    (call $path_open
+      (i32.const 3) ;; preopened directory
+      (i32.const 1) ;; follow symlinks
+      (local.get 0) (local.get 1)
+      (local.get 2) ;; oflags
+      (i64.const 2) ;; fd_read
+      (i64.const 0)
+      (i32.const 0)
+      (i32.const 96) ;; opened fd
+    )
+  )
+  (func (export "fd_read") (param i32) (result i32)
+    (call $fd_read (local.get 0) (i32.const 64) (i32.const 1) (i32.const 72))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = std::env::temp_dir().join(format!("chibiwasm-open-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("a.txt"), b"hello")?;
+
+        let files = (0..3)
+            .map(|_| {
+                Arc::new(Mutex::new(FileEntry::new(
+                    Box::<VirtualFile>::default(),
+                    FileCaps::Sync,
+                )))
+            })
+            .collect();
+        let wasi = WasiSnapshotPreview1::with_io(files).preopen_dir(&dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+        let mut call = |path: i32, len: i32, oflags: i32| -> Result<Option<Value>> {
+            let args = vec![Value::I32(path), Value::I32(len), Value::I32(oflags)];
+            runtime.call("path_open".into(), args)
+        };
+
+        assert_eq!(call(16, 11, 0)?, Some(Errno::Noent.into()));
+        assert_eq!(call(32, 8, 0)?, Some(Errno::Notcapable.into()));
+        assert_eq!(call(0, 5, 0b10)?, Some(Errno::Notdir.into()));
+        assert_eq!(call(0, 5, 0b101)?, Some(Errno::Exist.into()));
+
+        assert_eq!(call(0, 5, 0)?, Some(Errno::Success.into()));
+        let fd = i32::from_le_bytes(memory.borrow().data[96..100].try_into()?);
+        assert_eq!(fd, 4);
+
+        let result = runtime.call("fd_read".into(), vec![Value::I32(fd)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        let memory = memory.borrow();
+        let nread = i32::from_le_bytes(memory.data[72..76].try_into()?);
+        assert_eq!(&memory.data[128..128 + nread as usize], b"hello");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_open_symlink() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_read"
+    (func $fd_read (param i32 i32 i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "out/secret.txt")
+  (data (i32.const 16) "root/etc/passwd")
+  (data (i32.const 32) "link")
+
+  ;; iovec of 16 bytes at 128
+  (data (i32.const 64) "\80\00\00\00\10\00\00\00")
+
+  (func (export "path_open") (param i32 i32 i32) (result i32)
+    (call $path_open
+      (i32.const 3) ;; preopened directory
+      (local.get 0) ;; lookupflags
+      (local.get 1) (local.get 2)
+      (i32.const 0) ;; oflags
+      (i64.const 2) ;; fd_read
+      (i64.const 0)
+      (i32.const 0)
+      (i32.const 96) ;; opened fd
+    )
+  )
+  (func (export "fd_read") (param i32) (result i32)
+    (call $fd_read (local.get 0) (i32.const 64) (i32.const 1) (i32.const 72))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = TempDir::new("open-symlink")?;
+        let outside = TempDir::new("open-outside")?;
+        std::fs::write(dir.join("a.txt"), b"hello")?;
+        std::fs::write(outside.join("secret.txt"), b"secret")?;
+        symlink(&*outside, dir.join("out"))?;
+        symlink("/", dir.join("root"))?;
+        symlink("a.txt", dir.join("link"))?;
+
+        let wasi = WasiSnapshotPreview1::default().preopen_dir(&*dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+        let mut call = |lookupflags: i32, path: i32, len: i32| -> Result<Option<Value>> {
+            let args = vec![Value::I32(lookupflags), Value::I32(path), Value::I32(len)];
+            runtime.call("path_open".into(), args)
+        };
+
+        // links escaping the preopened directory are rejected
+        assert_eq!(call(1, 0, 14)?, Some(Errno::Notcapable.into()));
+        assert_eq!(call(1, 16, 15)?, Some(Errno::Notcapable.into()));
+        assert_eq!(call(0, 16, 15)?, Some(Errno::Notcapable.into()));
+
+        // the last link is not followed without the flag
+        assert_eq!(call(0, 32, 4)?, Some(Errno::Loop.into()));
+
+        assert_eq!(call(1, 32, 4)?, Some(Errno::Success.into()));
+        let fd = i32::from_le_bytes(memory.borrow().data[96..100].try_into()?);
+        let result = runtime.call("fd_read".into(), vec![Value::I32(fd)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        let memory = memory.borrow();
+        let nread = i32::from_le_bytes(memory.data[72..76].try_into()?);
+        assert_eq!(&memory.data[128..128 + nread as usize], b"hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fd_fdstat_set_flags() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_fdstat_set_flags"
+    (func $fd_fdstat_set_flags (param i32 i32) (result i32))
+  )
+  (memory 1)
+  (data (i32.const 0) "HE")
+  (data (i32.const 8) "!!")
+  ;; iovecs of "HE" and "!!"
+  (data (i32.const 16) "\00\00\00\00\02\00\00\00")
+  (data (i32.const 24) "\08\00\00\00\02\00\00\00")
+
+  (func (export "fd_write") (param i32 i32) (result i32)
+    (call $fd_write (local.get 0) (local.get 1) (i32.const 1) (i32.const 32))
+  )
+  (func (export "fd_fdstat_set_flags") (param i32 i32) (result i32)
+    (call $fd_fdstat_set_flags (local.get 0) (local.get 1))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let mut files: Vec<_> = (0..3)
+            .map(|_| {
+                Arc::new(Mutex::new(FileEntry::new(
+                    Box::<VirtualFile>::default(),
+                    FileCaps::Sync,
+                )))
+            })
+            .collect();
+        let file = Arc::new(Mutex::new(FileEntry::new(
+            Box::new(VirtualFile::new(b"hello")),
+            FileCaps::Sync,
+        )));
+        files.push(file.clone());
+        let wasi = WasiSnapshotPreview1::with_io(files);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+
+        // the data is overwritten from the cursor without append
+        let result = runtime.call("fd_write".into(), vec![Value::I32(3), Value::I32(16)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+
+        let args = vec![Value::I32(3), Value::I32(FdFlags::Append as i32)];
+        let result = runtime.call("fd_fdstat_set_flags".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        let args = vec![Value::I32(10), Value::I32(FdFlags::Append as i32)];
+        let result = runtime.call("fd_fdstat_set_flags".into(), args)?;
+        assert_eq!(result, Some(Errno::Badf.into()));
+        for flags in [0b100000, 0x10001, -1] {
+            let args = vec![Value::I32(3), Value::I32(flags)];
+            let result = runtime.call("fd_fdstat_set_flags".into(), args)?;
+            assert_eq!(result, Some(Errno::Inval.into()), "flags {flags:#x}");
+        }
+
+        // rewind the cursor, but the data is appended to the end
+        file.lock()
+            .expect("cannot lock file")
+            .capbable(FileCaps::Seek)?
+            .seek(0)?;
+        let result = runtime.call("fd_write".into(), vec![Value::I32(3), Value::I32(24)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+
+        // the data is written from the cursor again after append is cleared
+        let args = vec![Value::I32(3), Value::I32(0)];
+        let result = runtime.call("fd_fdstat_set_flags".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        file.lock()
+            .expect("cannot lock file")
+            .capbable(FileCaps::Seek)?
+            .seek(5)?;
+        let result = runtime.call("fd_write".into(), vec![Value::I32(3), Value::I32(16)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+
+        let mut file = file.lock().expect("cannot lock file");
+        let file = file.capbable(FileCaps::Read)?;
+        file.seek(0)?;
+        assert_eq!(file.read_string()?, "HElloHE");
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_open_append() -> Result<()> {
+        let code = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_fdstat_set_flags"
+    (func $fd_fdstat_set_flags (param i32 i32) (result i32))
+  )
+  (import "wasi_snapshot_preview1" "fd_fdstat_get"
+    (func $fd_fdstat_get (param i32 i32) (result i32))
+  )
+  (memory (export "memory") 1)
+  (data (i32.const 0) "a.txt")
+  (data (i32.const 8) "HE")
+  ;; iovec of "HE"
+  (data (i32.const 16) "\08\00\00\00\02\00\00\00")
+
+  (func (export "path_open") (result i32)
+    (call $path_open
+      (i32.const 3) ;; preopened directory
+      (i32.const 1) ;; follow symlinks
+      (i32.const 0) (i32.const 5)
+      (i32.const 0) ;; oflags
+      (i64.const 64) ;; fd_write
+      (i64.const 0)
+      (i32.const 1) ;; append
+      (i32.const 96) ;; opened fd
+    )
+  )
+  (func (export "fd_write") (param i32) (result i32)
+    (call $fd_write (local.get 0) (i32.const 16) (i32.const 1) (i32.const 32))
+  )
+  (func (export "fd_fdstat_set_flags") (param i32 i32) (result i32)
+    (call $fd_fdstat_set_flags (local.get 0) (local.get 1))
+  )
+  (func (export "fd_fdstat_get") (param i32) (result i32)
+    (call $fd_fdstat_get (local.get 0) (i32.const 64))
+  )
+)
+            "#;
+        let wasm = wat::parse_str(code)?;
+
+        let dir = TempDir::new("append")?;
+        std::fs::write(dir.join("a.txt"), b"hello")?;
+
+        let wasi = WasiSnapshotPreview1::default().preopen_dir(&*dir);
+        let mut runtime = Runtime::from_bytes(wasm.as_slice(), Some(vec![Box::new(wasi)]))?;
+        let Exports::Memory(memory) = runtime.exports("memory".into())? else {
+            panic!("memory must be exported");
+        };
+        let flags = |runtime: &mut Runtime, fd: i32| -> Result<u16> {
+            let result = runtime.call("fd_fdstat_get".into(), vec![Value::I32(fd)])?;
+            assert_eq!(result, Some(Errno::Success.into()));
+            Ok(u16::from_le_bytes(memory.borrow().data[66..68].try_into()?))
+        };
+
+        // stdio is in append mode
+        for fd in 0..3 {
+            assert_eq!(flags(&mut runtime, fd)?, FdFlags::Append as u16);
+        }
+
+        // the cursor of the opened file is at the start, but the data is appended
+        let result = runtime.call("path_open".into(), vec![])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        let fd = i32::from_le_bytes(memory.borrow().data[96..100].try_into()?);
+        assert_eq!(flags(&mut runtime, fd)?, FdFlags::Append as u16);
+        let result = runtime.call("fd_write".into(), vec![Value::I32(fd)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(std::fs::read(dir.join("a.txt"))?, b"helloHE");
+
+        // the data is written from the cursor after append is cleared
+        let result = runtime.call("path_open".into(), vec![])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        let fd = i32::from_le_bytes(memory.borrow().data[96..100].try_into()?);
+        let args = vec![Value::I32(fd), Value::I32(0)];
+        let result = runtime.call("fd_fdstat_set_flags".into(), args)?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(flags(&mut runtime, fd)?, 0);
+        let result = runtime.call("fd_write".into(), vec![Value::I32(fd)])?;
+        assert_eq!(result, Some(Errno::Success.into()));
+        assert_eq!(std::fs::read(dir.join("a.txt"))?, b"HElloHE");
+
+        Ok(())
+    }
 }
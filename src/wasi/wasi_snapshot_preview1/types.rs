@@ -1 +1,152 @@
+use std::io::{self, ErrorKind};
+
 pub type ExitCode = u32;
+
+// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-errno-variant
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Errno {
+    Success = 0,
+    TooBig = 1, // 2big
+    Acces = 2,
+    Addrinuse = 3,
+    Addrnotavail = 4,
+    Afnosupport = 5,
+    Again = 6,
+    Already = 7,
+    Badf = 8,
+    Badmsg = 9,
+    Busy = 10,
+    Canceled = 11,
+    Child = 12,
+    Connaborted = 13,
+    Connrefused = 14,
+    Connreset = 15,
+    Deadlk = 16,
+    Destaddrreq = 17,
+    Dom = 18,
+    Dquot = 19,
+    Exist = 20,
+    Fault = 21,
+    Fbig = 22,
+    Hostunreach = 23,
+    Idrm = 24,
+    Ilseq = 25,
+    Inprogress = 26,
+    Intr = 27,
+    Inval = 28,
+    Io = 29,
+    Isconn = 30,
+    Isdir = 31,
+    Loop = 32,
+    Mfile = 33,
+    Mlink = 34,
+    Msgsize = 35,
+    Multihop = 36,
+    Nametoolong = 37,
+    Netdown = 38,
+    Netreset = 39,
+    Netunreach = 40,
+    Nfile = 41,
+    Nobufs = 42,
+    Nodev = 43,
+    Noent = 44,
+    Noexec = 45,
+    Nolck = 46,
+    Nolink = 47,
+    Nomem = 48,
+    Nomsg = 49,
+    Noprotoopt = 50,
+    Nospc = 51,
+    Nosys = 52,
+    Notconn = 53,
+    Notdir = 54,
+    Notempty = 55,
+    Notrecoverable = 56,
+    Notsock = 57,
+    Notsup = 58,
+    Notty = 59,
+    Nxio = 60,
+    Overflow = 61,
+    Ownerdead = 62,
+    Perm = 63,
+    Pipe = 64,
+    Proto = 65,
+    Protonosupport = 66,
+    Prototype = 67,
+    Range = 68,
+    Rofs = 69,
+    Spipe = 70,
+    Srch = 71,
+    Stale = 72,
+    Timedout = 73,
+    Txtbsy = 74,
+    Xdev = 75,
+    Notcapable = 76,
+}
+
+impl Errno {
+    // NOTE: the errors which have no corresponding errno are reported as Io
+    pub fn from_io_error(e: &io::Error) -> Self {
+        match e.kind() {
+            ErrorKind::NotFound => Errno::Noent,
+            ErrorKind::PermissionDenied => Errno::Acces,
+            ErrorKind::ConnectionRefused => Errno::Connrefused,
+            ErrorKind::ConnectionReset => Errno::Connreset,
+            ErrorKind::ConnectionAborted => Errno::Connaborted,
+            ErrorKind::NotConnected => Errno::Notconn,
+            ErrorKind::AddrInUse => Errno::Addrinuse,
+            ErrorKind::AddrNotAvailable => Errno::Addrnotavail,
+            ErrorKind::BrokenPipe => Errno::Pipe,
+            ErrorKind::AlreadyExists => Errno::Exist,
+            ErrorKind::WouldBlock => Errno::Again,
+            ErrorKind::NotADirectory => Errno::Notdir,
+            ErrorKind::IsADirectory => Errno::Isdir,
+            ErrorKind::DirectoryNotEmpty => Errno::Notempty,
+            ErrorKind::ReadOnlyFilesystem => Errno::Rofs,
+            ErrorKind::InvalidInput => Errno::Inval,
+            ErrorKind::InvalidFilename => Errno::Nametoolong,
+            ErrorKind::TimedOut => Errno::Timedout,
+            ErrorKind::StorageFull => Errno::Nospc,
+            ErrorKind::FileTooLarge => Errno::Fbig,
+            ErrorKind::ResourceBusy => Errno::Busy,
+            ErrorKind::ExecutableFileBusy => Errno::Txtbsy,
+            ErrorKind::Deadlock => Errno::Deadlk,
+            ErrorKind::CrossesDevices => Errno::Xdev,
+            ErrorKind::TooManyLinks => Errno::Mlink,
+            ErrorKind::ArgumentListTooLong => Errno::TooBig,
+            ErrorKind::Interrupted => Errno::Intr,
+            ErrorKind::Unsupported => Errno::Notsup,
+            ErrorKind::OutOfMemory => Errno::Nomem,
+            _ => Errno::Io,
+        }
+    }
+}
+
+impl From<Errno> for crate::Value {
+    fn from(errno: Errno) -> Self {
+        (errno as i32).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Errno;
+    use std::io::{Error, ErrorKind};
+
+    #[test]
+    fn from_io_error() {
+        let tests = [
+            (ErrorKind::NotFound, Errno::Noent),
+            (ErrorKind::PermissionDenied, Errno::Acces),
+            (ErrorKind::AlreadyExists, Errno::Exist),
+            (ErrorKind::IsADirectory, Errno::Isdir),
+            (ErrorKind::Other, Errno::Io),
+        ];
+        for (kind, errno) in tests {
+            assert_eq!(Errno::from_io_error(&Error::from(kind)), errno, "{kind:?}");
+        }
+        // NOTE: the raw os error is mapped by its kind
+        let e = Error::from_raw_os_error(2); // ENOENT
+        assert_eq!(Errno::from_io_error(&e), Errno::Noent);
+    }
+}
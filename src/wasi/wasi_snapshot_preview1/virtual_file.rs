@@ -1,6 +1,6 @@
-use super::file::{FdFlags, File, FileType, ReadWrite};
+use super::file::{File, FileStat, FileType, ReadWrite};
 use anyhow::Result;
-use std::io::Cursor;
+use std::io::{Cursor, SeekFrom};
 
 pub struct VirtualFile(Box<dyn ReadWrite>);
 
@@ -10,6 +10,10 @@ impl File for VirtualFile {
         Ok(written)
     }
 
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.0.flush()?)
+    }
+
     fn read(&mut self, data: &mut [u8]) -> Result<usize> {
         Ok(self.0.read(data)?)
     }
@@ -18,6 +22,10 @@ impl File for VirtualFile {
         Ok(self.0.seek(std::io::SeekFrom::Start(pos))?)
     }
 
+    fn seek_end(&mut self) -> Result<u64> {
+        Ok(self.0.seek(SeekFrom::End(0))?)
+    }
+
     fn read_string(&mut self) -> Result<String> {
         let mut buf = String::new();
         self.0.read_to_string(&mut buf)?;
@@ -28,8 +36,17 @@ impl File for VirtualFile {
         Ok(FileType::RegularFile)
     }
 
-    fn fdflags(&self) -> Result<super::file::FdFlags> {
-        Ok(FdFlags::Append)
+    fn filestat(&mut self) -> Result<FileStat> {
+        // NOTE: restore the cursor after seeking to the end to get the size
+        let pos = self.0.stream_position()?;
+        let size = self.0.seek(SeekFrom::End(0))?;
+        self.0.seek(SeekFrom::Start(pos))?;
+        Ok(FileStat {
+            filetype: FileType::RegularFile,
+            nlink: 1,
+            size,
+            ..Default::default()
+        })
     }
 }
 
@@ -43,4 +60,8 @@ impl VirtualFile {
     pub fn new(data: &[u8]) -> Self {
         Self(Box::new(Cursor::new(data.to_vec())))
     }
+
+    pub fn from_io(io: impl ReadWrite) -> Self {
+        Self(Box::new(io))
+    }
 }
@@ -0,0 +1,179 @@
+use super::{
+    file::{Dirent, File, FileStat, FileType},
+    types::Errno,
+};
+use anyhow::{bail, Result};
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+// preopened host directory, guest paths are resolved relative to it
+pub struct WasiDir(PathBuf);
+
+impl File for WasiDir {
+    fn write(&mut self, _data: &[u8]) -> Result<usize> {
+        bail!("cannot write to directory: {}", self.0.display())
+    }
+
+    fn read(&mut self, _data: &mut [u8]) -> Result<usize> {
+        bail!("cannot read from directory: {}", self.0.display())
+    }
+
+    fn seek(&mut self, _pos: u64) -> Result<u64> {
+        bail!("cannot seek directory: {}", self.0.display())
+    }
+
+    fn seek_end(&mut self) -> Result<u64> {
+        bail!("cannot seek directory: {}", self.0.display())
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        bail!("cannot read from directory: {}", self.0.display())
+    }
+
+    fn filetype(&self) -> Result<FileType> {
+        Ok(FileType::Directory)
+    }
+
+    fn filestat(&mut self) -> Result<FileStat> {
+        let m = std::fs::metadata(&self.0)?;
+        Ok((&m).into())
+    }
+
+    fn dir_path(&self) -> Option<&Path> {
+        Some(&self.0)
+    }
+}
+
+impl WasiDir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self(path.into())
+    }
+}
+
+#[cfg(unix)]
+fn inode(entry: &std::fs::DirEntry) -> u64 {
+    use std::os::unix::fs::DirEntryExt;
+    entry.ino()
+}
+
+// NOTE: the inode is not available on non-unix platforms
+#[cfg(not(unix))]
+fn inode(_entry: &std::fs::DirEntry) -> u64 {
+    0
+}
+
+// entries of the host directory sorted by name, the cookie is the index of the entry
+// NOTE: "." and ".." are not included
+pub fn read_dir(dir: &Path) -> std::io::Result<Vec<Dirent>> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            let filetype = entry.file_type()?.into();
+            Ok((entry.file_name(), inode(&entry), filetype))
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let dirents = entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, (name, ino, filetype))| Dirent {
+            next: i as u64 + 1,
+            ino,
+            filetype,
+            name: name.to_string_lossy().into_owned(),
+        })
+        .collect();
+    Ok(dirents)
+}
+
+// max number of symbolic links followed in a path, same as linux
+const MAX_SYMLINKS: usize = 40;
+
+// resolve the guest path relative to the preopened directory,
+// absolute paths and paths escaping the directory are rejected as notcapable.
+// symbolic links in the middle of the path are always followed, and the last one is followed
+// only if `follow` is set. links to absolute paths or outside the directory are rejected too
+// NOTE: the host file system may be changed between the resolution and the use of the path
+pub fn resolve_path(dir: &Path, path: &str, follow: bool) -> Result<PathBuf, Errno> {
+    let mut resolved: Vec<OsString> = vec![];
+    let mut pending: VecDeque<OsString> = VecDeque::new();
+    push_components(&mut pending, Path::new(path))?;
+
+    let mut links = 0usize;
+    while let Some(name) = pending.pop_front() {
+        if name == ".." {
+            resolved.pop().ok_or(Errno::Notcapable)?;
+            continue;
+        }
+
+        let candidate: PathBuf = std::iter::once(dir.as_os_str())
+            .chain(resolved.iter().map(OsString::as_os_str))
+            .chain(std::iter::once(name.as_os_str()))
+            .collect();
+        let is_link = std::fs::symlink_metadata(&candidate).is_ok_and(|m| m.is_symlink());
+        if is_link && (follow || !pending.is_empty()) {
+            links += 1;
+            if links > MAX_SYMLINKS {
+                return Err(Errno::Loop);
+            }
+            let target = std::fs::read_link(&candidate).map_err(|e| Errno::from_io_error(&e))?;
+            let mut components = VecDeque::new();
+            push_components(&mut components, &target)?;
+            components.append(&mut pending);
+            pending = components;
+        } else {
+            resolved.push(name);
+        }
+    }
+
+    Ok(std::iter::once(dir.as_os_str())
+        .chain(resolved.iter().map(OsString::as_os_str))
+        .collect())
+}
+
+// push the names of the relative path, "." is skipped
+fn push_components(pending: &mut VecDeque<OsString>, path: &Path) -> Result<(), Errno> {
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => pending.push_back(name.to_os_string()),
+            Component::CurDir => {}
+            Component::ParentDir => pending.push_back("..".into()),
+            Component::RootDir | Component::Prefix(_) => return Err(Errno::Notcapable),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_path;
+    use crate::wasi::Errno;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_resolve_path() {
+        let dir = Path::new("/sandbox");
+        assert_eq!(
+            resolve_path(dir, "a/b.txt", true),
+            Ok(PathBuf::from("/sandbox/a/b.txt"))
+        );
+        assert_eq!(
+            resolve_path(dir, "./a/../b.txt", true),
+            Ok(PathBuf::from("/sandbox/b.txt"))
+        );
+        assert_eq!(resolve_path(dir, ".", true), Ok(PathBuf::from("/sandbox")));
+        assert_eq!(resolve_path(dir, "../b.txt", true), Err(Errno::Notcapable));
+        assert_eq!(
+            resolve_path(dir, "a/../../b.txt", true),
+            Err(Errno::Notcapable)
+        );
+        assert_eq!(
+            resolve_path(dir, "/etc/passwd", true),
+            Err(Errno::Notcapable)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use super::file::{FdFlags, File, FileType};
+use super::file::{File, FileStat, FileType};
 use anyhow::Result;
 use std::{io::prelude::*, os::fd::FromRawFd};
 
@@ -10,6 +10,10 @@ impl File for WasiFile {
         Ok(written)
     }
 
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.0.flush()?)
+    }
+
     fn read(&mut self, data: &mut [u8]) -> Result<usize> {
         Ok(self.0.read(data)?)
     }
@@ -18,6 +22,10 @@ impl File for WasiFile {
         Ok(self.0.seek(std::io::SeekFrom::Start(pos))?)
     }
 
+    fn seek_end(&mut self) -> Result<u64> {
+        Ok(self.0.seek(std::io::SeekFrom::End(0))?)
+    }
+
     fn read_string(&mut self) -> Result<String> {
         let mut buf = String::new();
         self.0.read_to_string(&mut buf)?;
@@ -25,27 +33,21 @@ impl File for WasiFile {
     }
 
     fn filetype(&self) -> Result<FileType> {
-        // FIXME: this is not correct
         let m = self.0.metadata()?;
-        let filetype = if m.is_file() {
-            FileType::RegularFile
-        } else if m.is_dir() {
-            FileType::Directory
-        } else if m.is_symlink() {
-            FileType::SymbolicLink
-        } else {
-            FileType::Unknown
-        };
-        Ok(filetype)
-    }
-
-    fn fdflags(&self) -> Result<FdFlags> {
-        // TODO: implement fdflags
-        Ok(FdFlags::Append)
+        Ok(m.file_type().into())
+    }
+
+    fn filestat(&mut self) -> Result<FileStat> {
+        let m = self.0.metadata()?;
+        Ok((&m).into())
     }
 }
 
 impl WasiFile {
+    pub fn new(file: std::fs::File) -> Self {
+        Self(file)
+    }
+
     pub fn from_raw_fd(fd: u32) -> Self {
         let file = unsafe { std::fs::File::from_raw_fd(fd as i32) };
         Self(file)
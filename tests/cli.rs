@@ -0,0 +1,107 @@
+use anyhow::Result;
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+// run the cli with the module compiled from wat
+fn output(name: &str, code: &str, args: &[&str]) -> Result<Output> {
+    let wasm = wat::parse_str(code)?;
+    let path: PathBuf = std::env::temp_dir().join(format!(
+        "chibiwasm-cli-{}-{}.wasm",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, wasm)?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_chibiwasm"))
+        .arg(&path)
+        .args(args)
+        .output();
+    fs::remove_file(&path)?;
+    Ok(output?)
+}
+
+// run the cli with the module compiled from wat, returns stdout
+fn run(name: &str, code: &str, args: &[&str]) -> Result<String> {
+    let output = output(name, code, args)?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[test]
+fn void_function() -> Result<()> {
+    let code = r#"(module (func (export "nop")))"#;
+    let stdout = run("void", code, &["nop"])?;
+    assert_eq!(stdout, "");
+    Ok(())
+}
+
+#[test]
+fn function_with_result() -> Result<()> {
+    let code = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+)
+    "#;
+    let stdout = run("result", code, &["add", "1", "2"])?;
+    assert_eq!(stdout, "3\n");
+    Ok(())
+}
+
+#[test]
+fn list_exports_and_imports() -> Result<()> {
+    let code = r#"
+(module
+  (import "env" "print_i32" (func (param i32)))
+  (memory (export "memory") 1 2)
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+)
+    "#;
+    let stdout = run("list", code, &["--list"])?;
+    assert_eq!(
+        stdout,
+        r#"exports:
+  "memory" (memory 1 2)
+  "add" (func (param i32 i32) (result i32))
+imports:
+  "env" "print_i32" (func (param i32))
+"#
+    );
+    Ok(())
+}
+
+#[test]
+fn invoke_by_index() -> Result<()> {
+    let code = r#"
+(module
+  (func (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))
+  )
+  (func (param i32 i32) (result i32)
+    (i32.sub (local.get 0) (local.get 1))
+  )
+)
+    "#;
+    let stdout = run("index", code, &["--index", "1", "5", "3"])?;
+    assert_eq!(stdout, "2\n");
+
+    let output = output("index-out-of-range", code, &["--index", "2"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("failed to call function: 2"), "{stderr}");
+    assert!(
+        stderr.contains("not found function by index: 2"),
+        "{stderr}"
+    );
+    Ok(())
+}
@@ -55,8 +55,7 @@ mod importer {
 
                     let export_inst = store
                         .module
-                        .exports
-                        .get(field)
+                        .export(field)
                         .context(format!("not found exported table '{field}' from {name}"))?;
 
                     let external_val = &export_inst.desc;
@@ -84,8 +83,7 @@ mod importer {
                     let store = store.borrow();
                     let export_inst = store
                         .module
-                        .exports
-                        .get(field)
+                        .export(field)
                         .context(format!("not found exported global '{field}' from {name}"))?;
                     let external_val = &export_inst.desc;
 
@@ -111,10 +109,10 @@ mod importer {
                 Some(store) => {
                     let store = store.borrow();
 
-                    let export_inst =
-                        store.module.exports.get(field).context(format!(
-                            "not found exported function '{field}' from {name}"
-                        ))?;
+                    let export_inst = store
+                        .module
+                        .export(field)
+                        .context(format!("not found exported function '{field}' from {name}"))?;
                     let external_val = &export_inst.desc;
 
                     let ExternalVal::Func(idx) = external_val else {
@@ -145,8 +143,7 @@ mod importer {
 
                     let export_inst = store
                         .module
-                        .exports
-                        .get(field)
+                        .export(field)
                         .context(format!("not found exported memory '{field}' from {name}"))?;
                     let external_val = &export_inst.desc;
 
@@ -172,11 +169,13 @@ mod importer {
 mod tests {
     use super::importer::Import;
     use anyhow::Result;
+    use chibiwasm::binary::{Instruction, Opcode};
     use chibiwasm::execution::{Exports, Importer, Runtime, Store, Value};
     use log::debug;
+    use num_traits::FromPrimitive;
     use paste::paste;
     use std::cell::RefCell;
-    use std::collections::HashMap;
+    use std::collections::{BTreeSet, HashMap};
     use std::io::{Cursor, Read};
     use std::rc::Rc;
     use std::sync::Once;
@@ -184,6 +183,11 @@ mod tests {
     use wabt::{script::*, Features};
     static INIT: Once = Once::new();
 
+    thread_local! {
+        // opcodes executed so far, recorded only while the coverage report is running
+        static COVERAGE: RefCell<Option<BTreeSet<String>>> = const { RefCell::new(None) };
+    }
+
     #[derive(Default)]
     struct Spec {
         modules: HashMap<Option<String>, Rc<RefCell<Runtime>>>,
@@ -203,6 +207,49 @@ mod tests {
             .collect()
     }
 
+    fn opcode_name(inst: &Instruction) -> String {
+        match inst {
+            Instruction::MemoryCopy(..)
+            | Instruction::MemoryFill(_)
+            | Instruction::MemoryInit(..)
+            | Instruction::DataDrop(_) => format!("{:?}", Opcode::MmeoryCopyOrFill),
+            _ => {
+                let name = format!("{inst:?}");
+                name.split('(').next().unwrap_or_default().to_string()
+            }
+        }
+    }
+
+    // same as Runtime::call, but steps through the instructions to record
+    // the executed opcodes when the coverage report is running
+    // NOTE: functions of other modules called via imports are not recorded
+    fn call(runtime: &mut Runtime, field: String, args: Vec<Value>) -> Result<Option<Value>> {
+        let recording = COVERAGE.with(|coverage| coverage.borrow().is_some());
+        if !recording || runtime.prepare_call(field.clone(), args.clone()).is_err() {
+            return runtime.call(field, args);
+        }
+
+        loop {
+            let frame = runtime.call_stack.last();
+            if let Some(inst) = frame.and_then(|frame| frame.insts.get((frame.pc + 1) as usize)) {
+                let name = opcode_name(inst);
+                COVERAGE.with(|coverage| coverage.borrow_mut().as_mut().map(|c| c.insert(name)));
+            }
+            match runtime.step() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    runtime.stack.clear();
+                    runtime.call_stack.clear();
+                    return Err(e);
+                }
+            }
+        }
+        let result = runtime.stack.pop();
+        runtime.stack.clear();
+        Ok(result)
+    }
+
     fn run_test(spec_file: &str) -> Result<()> {
         INIT.call_once(|| {
             // enable logger
@@ -215,9 +262,9 @@ mod tests {
         let testspec = {
             let code = r#"
 (module
-  (table (export "table") 10 funcref)
+  (table (export "table") 10 20 funcref)
   (global (export "global_i32") i32 (i32.const 42))
-  (memory (export "memory") 1)
+  (memory (export "memory") 1 2)
   
   (func $print (export "print")
     (nop)
@@ -272,25 +319,31 @@ mod tests {
         )?;
 
         fn assert_values(results: Vec<Value>, expected: Vec<wabt::script::Value>) -> Result<()> {
+            // NOTE: wabt script values cannot express references, so funcref results are skipped
+            if results.iter().any(|v| matches!(v, Value::FuncRef(_))) {
+                return Ok(());
+            }
+
             let got: Vec<_> = results
                 .into_iter()
-                .map(|result| match result {
-                    Value::I32(v) => wabt::script::Value::I32(v),
-                    Value::I64(v) => wabt::script::Value::I64(v),
+                .filter_map(|result| match result {
+                    Value::I32(v) => Some(wabt::script::Value::I32(v)),
+                    Value::I64(v) => Some(wabt::script::Value::I64(v)),
                     Value::F32(v) => {
                         if v.is_nan() {
-                            wabt::script::Value::F32(0_f32)
+                            Some(wabt::script::Value::F32(0_f32))
                         } else {
-                            wabt::script::Value::F32(v)
+                            Some(wabt::script::Value::F32(v))
                         }
                     }
                     Value::F64(v) => {
                         if v.is_nan() {
-                            wabt::script::Value::F64(0_f64)
+                            Some(wabt::script::Value::F64(0_f64))
                         } else {
-                            wabt::script::Value::F64(v)
+                            Some(wabt::script::Value::F64(v))
                         }
                     }
+                    Value::FuncRef(_) => None,
                 })
                 .collect();
 
@@ -323,7 +376,7 @@ mod tests {
             expected: Vec<wabt::script::Value>,
         ) -> Result<()> {
             let args = into_wasm_value(args);
-            let result = runtime.call(field, args)?;
+            let result = call(runtime, field, args)?;
             match result {
                 Some(result) => assert_values(vec![result], expected),
                 None => Ok(()),
@@ -397,7 +450,7 @@ mod tests {
                         let runtime = spec.modules.get(&module).expect("not found mdoule").clone();
                         let runtime = &mut *runtime.borrow_mut();
                         let args = into_wasm_value(args);
-                        let result = runtime.call(field.clone(), args.clone());
+                        let result = call(runtime, field.clone(), args.clone());
 
                         match result {
                             Err(err) => {
@@ -456,85 +509,121 @@ mod tests {
     }
 
     macro_rules! test {
-        ($ty: ident) => {
-            paste! {
-                #[test]
-                fn [<test_ $ty>]() -> Result<()> {
-                    let file = format!("{}.wast", stringify!($ty));
-                    run_test(&file)?;
-                    Ok(())
+        ($($ty: ident),* $(,)?) => {
+            const SPEC_FILES: &[&str] = &[$(stringify!($ty)),*];
+
+            $(
+                paste! {
+                    #[test]
+                    fn [<test_ $ty>]() -> Result<()> {
+                        let file = format!("{}.wast", stringify!($ty));
+                        run_test(&file)?;
+                        Ok(())
+                    }
                 }
-            }
+            )*
         };
     }
 
-    test!(i32);
-    test!(i64);
-    test!(f32);
-    test!(f32_cmp);
-    test!(f32_bitwise);
-    test!(f64);
-    test!(f64_cmp);
-    test!(f64_bitwise);
-    test!(load);
-    test!(nop);
-    test!(store);
-    test!(loop);
-    test!(int_literals);
-    test!(if);
-    test!(br_if);
-    test!(globals);
-    test!(func);
-    test!(block);
-    test!(comments);
-    test!(binary);
-    test!(break_drop);
-    test!(const);
-    test!(forward);
-    test!(inline_module);
-    test!(names);
-    test!(stack);
-    test!(return);
-    test!(br);
-    test!(br_table);
-    test!(local_set);
-    test!(local_get);
-    test!(local_tee);
-    test!(select);
-    test!(labels);
-    test!(unreachable);
-    test!(type);
-    test!(fac);
-    test!(memory_size);
-    test!(address);
-    test!(memory_trap);
-    test!(align);
-    test!(memory);
-    test!(float_misc);
-    test!(int_exprs);
-    test!(memory_grow);
-    test!(memory_redundancy);
-    test!(call);
-    test!(call_indirect);
-    test!(float_memory);
-    test!(float_exprs);
-    test!(left_to_right);
-    test!(skip_stack_guard_page);
-    test!(unwind);
-    test!(binary_leb128);
-    test!(exports);
-    test!(switch);
-    test!(custom);
-    test!(start);
-    test!(imports);
-    test!(func_ptrs);
-    test!(elem);
-    test!(data);
-    test!(float_literals);
-    test!(endianness);
-    test!(conversions);
-    test!(traps);
-    test!(linking);
+    test!(
+        i32,
+        i64,
+        f32,
+        f32_cmp,
+        f32_bitwise,
+        f64,
+        f64_cmp,
+        f64_bitwise,
+        load,
+        nop,
+        store,
+        loop,
+        int_literals,
+        if,
+        br_if,
+        globals,
+        func,
+        block,
+        comments,
+        binary,
+        break_drop,
+        const,
+        forward,
+        inline_module,
+        names,
+        stack,
+        return,
+        br,
+        br_table,
+        local_set,
+        local_get,
+        local_tee,
+        select,
+        labels,
+        unreachable,
+        type,
+        fac,
+        memory_size,
+        address,
+        memory_trap,
+        align,
+        memory,
+        float_misc,
+        int_exprs,
+        memory_grow,
+        memory_redundancy,
+        call,
+        call_indirect,
+        float_memory,
+        float_exprs,
+        left_to_right,
+        skip_stack_guard_page,
+        unwind,
+        binary_leb128,
+        exports,
+        switch,
+        custom,
+        start,
+        imports,
+        func_ptrs,
+        elem,
+        data,
+        float_literals,
+        endianness,
+        conversions,
+        traps,
+        linking,
+    );
+
+    // print the opcodes executed by the spec tests and the uncovered ones, run with
+    // `cargo test --test spec opcode_coverage -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn opcode_coverage() -> Result<()> {
+        COVERAGE.with(|coverage| *coverage.borrow_mut() = Some(BTreeSet::new()));
+        for spec_file in SPEC_FILES {
+            run_test(&format!("{spec_file}.wast"))?;
+        }
+        let covered = COVERAGE
+            .with(|coverage| coverage.borrow_mut().take())
+            .unwrap_or_default();
+
+        let opcodes: Vec<_> = (0..=u8::MAX)
+            .filter_map(Opcode::from_u8)
+            .map(|opcode| format!("{opcode:?}"))
+            .collect();
+        let (covered, uncovered): (Vec<_>, Vec<_>) =
+            opcodes.iter().partition(|opcode| covered.contains(*opcode));
+
+        println!("opcode coverage: {}/{}", covered.len(), opcodes.len());
+        for opcode in covered {
+            println!("covered: {opcode}");
+        }
+        for opcode in uncovered {
+            println!("uncovered: {opcode}");
+        }
+        Ok(())
+    }
 
     // Skip invalid tests
     //test!(token);